@@ -0,0 +1,1841 @@
+use log::{info, error};
+use lopdf::content::Operation;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::*;
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent};
+
+fn matches_part_title(s: &str) -> bool {
+    let t = s.to_lowercase();
+
+    if t.starts_with("intro")
+        || t.starts_with("part")
+        || t.starts_with("int") && !t.starts_with("into")
+        || t.starts_with("bridge")
+        || t.starts_with("bdg")
+        || t.starts_with("end")
+        || t.starts_with("a ")
+        || t.starts_with("b ")
+        || t.starts_with("c ")
+        || t.starts_with("d ")
+        || t.starts_with("e ")
+        || t.starts_with("f ")
+    {
+        return true;
+    }
+    false
+}
+
+#[derive(Debug, Default)]
+struct State {
+    pub has_title: bool,
+    pub has_parts: bool,
+    pub relevant_element: bool,
+    pub ignore_content: bool,
+    pub new_line: bool,
+    pub new_part: bool,
+    pub sequence: bool,
+    pub list_depth: usize,
+}
+
+/// Result of a conversion. The text of a PDF is only an approximation of the cue sheet, so its
+/// conversion reports why it should be reviewed before the cue card is used.
+#[derive(Debug, Default)]
+pub struct Conversion {
+    pub warnings: Vec<String>,
+}
+
+impl Conversion {
+    /// Returns whether the cue card should be checked by the user.
+    pub fn needs_review(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+}
+
+/// Converts a cue sheet to markdown. PDF documents are detected by their header, otherwise the
+/// format is detected by the document of the archive: `word/document.xml` for DOCX and
+/// `content.xml` for ODT.
+pub fn convert_to_markdown<R, W>(input: &mut R, writer: &mut W) -> Result<Conversion>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    if is_pdf(input)? {
+        return convert_pdf(input, writer);
+    }
+
+    let mut archive = match zip::read::ZipArchive::new(input) {
+        Ok(archive) => archive,
+        Err(err) => {
+            error!("Unable to read archive: {:?}", err);
+            return Err(Error::new(ErrorKind::InvalidData, err));
+        }
+    };
+
+    if let Ok(result) = archive.by_name("word/document.xml") {
+        convert_docx(BufReader::new(result), writer);
+        return Ok(Conversion::default());
+    }
+
+    let mut styles = Styles::default();
+
+    if let Ok(result) = archive.by_name("styles.xml") {
+        styles.read(BufReader::new(result));
+    }
+
+    if let Ok(result) = archive.by_name("content.xml") {
+        convert_odt(BufReader::new(result), writer, styles);
+    };
+
+    Ok(Conversion::default())
+}
+
+/// Text of a paragraph, as plain text for the heuristics and as Markdown with its formatting.
+#[derive(Debug, Default)]
+struct Text {
+    pub plain: String,
+    pub formatted: String,
+    /// End and emphasis of the last emphasized text, to join adjacent spans
+    closed: Option<(usize, Emphasis)>,
+}
+
+impl Text {
+    fn push(&mut self, s: &str) {
+        self.plain.push_str(s);
+        self.formatted.push_str(s);
+    }
+
+    fn is_blank(&self) -> bool {
+        self.plain.trim().is_empty()
+    }
+
+    /// Emphasizes the formatted text from `start`. Surrounding whitespace stays outside of the
+    /// markers, as Markdown does not allow it inside.
+    fn emphasize(&mut self, start: usize, emphasis: Emphasis) {
+        let markers = emphasis.markers();
+        let content = &self.formatted[start..];
+
+        if markers.is_empty() || content.trim().is_empty() {
+            return;
+        }
+
+        let leading = content[..content.len() - content.trim_start().len()].to_owned();
+        let trailing = content[content.trim_end().len()..].to_owned();
+        let trimmed = content.trim().to_owned();
+
+        // spans with the same emphasis directly after each other are joined
+        if self.closed == Some((start, emphasis)) {
+            self.formatted.truncate(start - markers.len());
+            self.formatted.push_str(&leading);
+        } else {
+            self.formatted.truncate(start);
+            self.formatted.push_str(&leading);
+            self.formatted.push_str(markers);
+        }
+
+        self.formatted.push_str(&trimmed);
+        self.formatted.push_str(markers);
+        self.closed = Some((self.formatted.len(), emphasis));
+        self.formatted.push_str(&trailing);
+    }
+
+    /// Turns the formatted text from `start` into a link to `href`.
+    fn link(&mut self, start: usize, href: &str) {
+        let content = &self.formatted[start..];
+        let trimmed = content.trim();
+
+        if href.is_empty() || trimmed.is_empty() {
+            return;
+        }
+
+        let leading = &content[..content.len() - content.trim_start().len()];
+        let trailing = &content[content.trim_end().len()..];
+        let link = format!("{}[{}]({}){}", leading, trimmed, href, trailing);
+
+        self.formatted.truncate(start);
+        self.formatted.push_str(&link);
+        self.closed = None;
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Emphasis {
+    bold: bool,
+    italic: bool,
+}
+
+impl Emphasis {
+    fn markers(self) -> &'static str {
+        match (self.bold, self.italic) {
+            (true, true) => "***",
+            (true, false) => "**",
+            (false, true) => "*",
+            (false, false) => "",
+        }
+    }
+
+    fn union(self, other: Emphasis) -> Emphasis {
+        Emphasis {
+            bold: self.bold || other.bold,
+            italic: self.italic || other.italic,
+        }
+    }
+
+    /// Returns the emphasis of `self` which is not already in `other`.
+    fn without(self, other: Emphasis) -> Emphasis {
+        Emphasis {
+            bold: self.bold && !other.bold,
+            italic: self.italic && !other.italic,
+        }
+    }
+}
+
+/// Text properties of an ODT style. Properties which are not set are inherited from the parent.
+#[derive(Debug, Default)]
+struct StyleProperties {
+    parent: Option<String>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+}
+
+/// Styles of an ODT document, from `styles.xml` and the automatic styles of `content.xml`.
+#[derive(Debug, Default)]
+struct Styles {
+    styles: HashMap<String, StyleProperties>,
+    reading: Option<(String, StyleProperties)>,
+}
+
+fn attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|a| a.name.local_name == name)
+        .map(|a| a.value.as_str())
+}
+
+impl Styles {
+    fn read<R: Read>(&mut self, reader: R) {
+        for e in EventReader::new(reader) {
+            match e {
+                Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                    self.start_element(&name.local_name, &attributes);
+                }
+                Ok(XmlEvent::EndElement { name }) => {
+                    self.end_element(&name.local_name);
+                }
+                Err(e) => {
+                    error!("Error in styles: {}", e);
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn start_element(&mut self, name: &str, attributes: &[OwnedAttribute]) {
+        match name {
+            "style" => {
+                if let Some(style_name) = attribute(attributes, "name") {
+                    let properties = StyleProperties {
+                        parent: attribute(attributes, "parent-style-name").map(str::to_owned),
+                        ..StyleProperties::default()
+                    };
+                    self.reading = Some((style_name.to_owned(), properties));
+                }
+            }
+            "text-properties" => {
+                if let Some((_, properties)) = self.reading.as_mut() {
+                    if let Some(weight) = attribute(attributes, "font-weight") {
+                        let numeric = weight.parse::<u32>().unwrap_or(0);
+                        properties.bold = Some(weight == "bold" || numeric >= 600);
+                    }
+                    if let Some(style) = attribute(attributes, "font-style") {
+                        properties.italic = Some(style == "italic" || style == "oblique");
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn end_element(&mut self, name: &str) {
+        if name == "style" {
+            if let Some((style_name, properties)) = self.reading.take() {
+                self.styles.insert(style_name, properties);
+            }
+        }
+    }
+
+    /// Returns the emphasis of a style including the properties of its parents.
+    fn emphasis(&self, name: Option<&str>) -> Emphasis {
+        let mut bold = None;
+        let mut italic = None;
+        let mut name = name;
+
+        // the depth is limited in case of cyclic parents
+        for _ in 0..10 {
+            let properties = match name.and_then(|n| self.styles.get(n)) {
+                Some(properties) => properties,
+                None => break,
+            };
+
+            bold = bold.or(properties.bold);
+            italic = italic.or(properties.italic);
+            name = properties.parent.as_deref();
+        }
+
+        Emphasis {
+            bold: bold.unwrap_or(false),
+            italic: italic.unwrap_or(false),
+        }
+    }
+}
+
+/// A table of an ODT document with the paragraphs of its cells.
+#[derive(Debug, Default)]
+struct Table {
+    rows: Vec<Vec<Vec<Text>>>,
+}
+
+impl Table {
+    fn current_cell(&mut self) -> Option<&mut Vec<Text>> {
+        self.rows.last_mut().and_then(|row| row.last_mut())
+    }
+}
+
+/// Header fields of cue sheets with the metadata names of the indexer.
+const METADATA_FIELDS: [(&str, &str); 8] = [
+    ("choreographers", "Choreographer"),
+    ("choreographer", "Choreographer"),
+    ("music", "Music"),
+    ("rhythm", "Rhythm"),
+    ("phase", "Phase"),
+    ("footwork", "Footwork"),
+    ("sequence", "Sequence"),
+    ("difficulty", "Difficulty"),
+];
+
+fn metadata_name(label: &str) -> Option<&'static str> {
+    let label = label.trim().trim_end_matches(':').trim().to_lowercase();
+
+    METADATA_FIELDS
+        .iter()
+        .find(|(field, _)| *field == label)
+        .map(|(_, name)| *name)
+}
+
+/// Returns a phase the way the indexer recognizes it, like `IV+2 (Telemark)` for
+/// `Phase 4 + 2 (Telemark)`. Other text after the phase is dropped.
+fn normalize_phase(value: &str) -> String {
+    let mut text = value.trim();
+
+    if text.to_lowercase().starts_with("phase") {
+        text = text[5..].trim_start();
+    }
+
+    let end = text
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(text.len());
+    let phase = match text[..end].to_uppercase().as_ref() {
+        "I" | "1" => "I",
+        "II" | "2" => "II",
+        "III" | "3" => "III",
+        "IV" | "4" => "IV",
+        "V" | "5" => "V",
+        "VI" | "6" => "VI",
+        _ => return value.trim().to_owned(),
+    };
+    let rest = text[end..].trim();
+
+    match rest.strip_prefix('+') {
+        Some(plusfigures) => format!("{}+{}", phase, plusfigures.trim_start()),
+        None => phase.to_owned(),
+    }
+}
+
+/// Returns the header fields in a line like `Rhythm: Waltz  Phase: IV+1`. The line has to start
+/// with a field.
+fn metadata_fields(line: &str) -> Vec<(&'static str, String)> {
+    // ASCII lowercase keeps the positions of the line
+    let lower = line.to_ascii_lowercase();
+    let mut found: Vec<(usize, usize, &'static str)> = Vec::new();
+
+    for (field, name) in METADATA_FIELDS.iter() {
+        for (start, _) in lower.match_indices(field) {
+            let after = &lower[start + field.len()..];
+            let word_start = !lower[..start].ends_with(char::is_alphanumeric);
+
+            if word_start && after.trim_start().starts_with(':') {
+                let colon = start + field.len() + after.find(':').unwrap_or(0);
+                found.push((start, colon + 1, name));
+            }
+        }
+    }
+
+    found.sort_by_key(|(start, _, _)| *start);
+
+    match found.first() {
+        Some((start, _, _)) if line[..*start].trim().is_empty() => (),
+        _ => return Vec::new(),
+    }
+
+    let mut fields = Vec::new();
+
+    for (i, (_, value_start, name)) in found.iter().enumerate() {
+        let value_end = found.get(i + 1).map_or(line.len(), |(start, _, _)| *start);
+        let value = line[*value_start..value_end]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let value = value.trim_end_matches(&[',', ';', '|'][..]).trim();
+
+        if value.is_empty() {
+            continue;
+        }
+
+        let value = match *name {
+            "Phase" => normalize_phase(value),
+            _ => value.to_owned(),
+        };
+
+        fields.push((*name, value));
+    }
+
+    fields
+}
+
+/// Returns the header fields of a table row, either as pairs of label and value cells or as
+/// cells like `Phase: IV`.
+fn row_metadata(row: &[Vec<Text>]) -> Vec<(&'static str, String)> {
+    let cells: Vec<String> = row
+        .iter()
+        .map(|cell| {
+            cell.iter()
+                .map(|p| p.plain.trim())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect();
+
+    if cells
+        .chunks(2)
+        .all(|c| c.len() == 2 && metadata_name(&c[0]).is_some())
+    {
+        return cells
+            .chunks(2)
+            .filter_map(|c| {
+                let name = metadata_name(&c[0])?;
+                let line = format!("{}: {}", name, c[1]);
+                metadata_fields(&line).pop()
+            })
+            .collect();
+    }
+
+    let fields: Vec<Vec<_>> = cells
+        .iter()
+        .filter(|c| !c.is_empty())
+        .map(|c| metadata_fields(c))
+        .collect();
+
+    if fields.iter().all(|f| !f.is_empty()) {
+        fields.into_iter().flatten().collect()
+    } else {
+        Vec::new()
+    }
+}
+
+fn write_metadata<W>(fields: &[(&str, String)], writer: &mut W)
+where
+    W: Write,
+{
+    for (name, value) in fields {
+        writeln!(writer).unwrap();
+        write!(writer, "* **{}**: {}", name, value).unwrap();
+        info!("* **{}**: {}", name, value);
+    }
+}
+
+/// Writes a table. Tables with a single column are used for the layout and are written as
+/// paragraphs, other tables as Markdown tables.
+fn write_table<W>(table: &Table, writer: &mut W, state: &mut State)
+where
+    W: Write,
+{
+    let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+
+    if columns == 1 {
+        for paragraph in table.rows.iter().flatten().flatten() {
+            if state.has_title || !paragraph.is_blank() {
+                state.new_line = true;
+                write_paragraph(paragraph, &mut *writer, state);
+                writeln!(writer).unwrap();
+            }
+            state.new_part = false;
+            state.sequence = false;
+        }
+        return;
+    }
+
+    let mut rows = Vec::new();
+
+    for row in &table.rows {
+        // header tables of cue sheets contain the metadata
+        if !state.has_parts {
+            let fields = row_metadata(row);
+
+            if !fields.is_empty() {
+                write_metadata(&fields, &mut *writer);
+                writeln!(writer).unwrap();
+                continue;
+            }
+        }
+
+        let cells: Vec<String> = (0..columns)
+            .map(|i| match row.get(i) {
+                Some(cell) => cell
+                    .iter()
+                    .map(|p| p.formatted.trim().replace('|', "\\|"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                None => String::new(),
+            })
+            .collect();
+
+        if cells.iter().any(|c| !c.is_empty()) {
+            rows.push(cells);
+        }
+    }
+
+    if rows.is_empty() {
+        return;
+    }
+
+    writeln!(writer).unwrap();
+
+    for (i, cells) in rows.iter().enumerate() {
+        writeln!(writer, "| {} |", cells.join(" | ")).unwrap();
+
+        if i == 0 {
+            writeln!(writer, "|{}", " --- |".repeat(columns)).unwrap();
+        }
+    }
+}
+
+/// Converts the content of an ODT document. Paragraph and text styles are read from the
+/// automatic styles of the content in addition to `styles`.
+fn convert_odt<R, W>(reader: R, writer: &mut W, mut styles: Styles)
+where
+    R: Read,
+    W: Write,
+{
+    let mut state = State::default();
+
+    let parser = EventReader::new(reader);
+
+    let mut text = Text::default();
+    // text boxes contain paragraphs within paragraphs
+    let mut paragraph_depth = 0;
+    // start of the formatted text of the paragraph and its spans, with the emphasis they add
+    // and the emphasis of their text
+    let mut spans: Vec<(usize, Emphasis, Emphasis)> = Vec::new();
+    let mut links: Vec<(usize, String)> = Vec::new();
+    let mut table: Option<Table> = None;
+    let mut table_depth = 0;
+    // comments and footnotes are not part of the cues
+    let mut ignored_depth = 0;
+
+    for e in parser {
+        match e {
+            Ok(XmlEvent::StartDocument { encoding, ..}) => {
+                info!("File encoding {} for content", encoding);
+            }
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                let local_name = name.local_name.as_str();
+
+                if local_name == "annotation" || local_name == "note" {
+                    ignored_depth += 1;
+                }
+                if ignored_depth > 0 {
+                    continue;
+                }
+
+                styles.start_element(local_name, &attributes);
+
+                match local_name {
+                    "p" | "h" => {
+                        paragraph_depth += 1;
+
+                        if paragraph_depth == 1 {
+                            let emphasis = styles.emphasis(attribute(&attributes, "style-name"));
+                            spans.push((0, emphasis, emphasis));
+                            state.new_line = true;
+                            state.new_part = local_name == "h";
+                        } else {
+                            text.push(" ");
+                        }
+                    }
+                    "span" if paragraph_depth > 0 => {
+                        let enclosing = spans.last().map(|s| s.2).unwrap_or_default();
+                        let emphasis = enclosing
+                            .union(styles.emphasis(attribute(&attributes, "style-name")));
+                        spans.push((text.formatted.len(), emphasis.without(enclosing), emphasis));
+                    }
+                    "a" if paragraph_depth > 0 => {
+                        let href = attribute(&attributes, "href").unwrap_or_default();
+                        links.push((text.formatted.len(), href.to_owned()));
+                    }
+                    "s" if paragraph_depth > 0 => {
+                        let count = attribute(&attributes, "c")
+                            .and_then(|c| c.parse().ok())
+                            .unwrap_or(1);
+                        text.push(&" ".repeat(count));
+                    }
+                    "tab" | "line-break" if paragraph_depth > 0 => {
+                        text.push(" ");
+                    }
+                    "list" => {
+                        state.list_depth += 1;
+                    }
+                    "table" => {
+                        table_depth += 1;
+
+                        if table_depth == 1 {
+                            table = Some(Table::default());
+                        }
+                    }
+                    "table-row" if table_depth == 1 => {
+                        if let Some(table) = table.as_mut() {
+                            table.rows.push(Vec::new());
+                        }
+                    }
+                    "table-cell" | "covered-table-cell" if table_depth == 1 => {
+                        if let Some(row) = table.as_mut().and_then(|t| t.rows.last_mut()) {
+                            row.push(Vec::new());
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            Ok(XmlEvent::Characters(s)) | Ok(XmlEvent::Whitespace(s))
+                if paragraph_depth > 0 && ignored_depth == 0 =>
+            {
+                text.push(&s);
+            }
+            Ok(XmlEvent::EndElement { name }) => {
+                let local_name = name.local_name.as_str();
+
+                if ignored_depth > 0 {
+                    if local_name == "annotation" || local_name == "note" {
+                        ignored_depth -= 1;
+                    }
+                    continue;
+                }
+
+                styles.end_element(local_name);
+
+                match local_name {
+                    "span" if spans.len() > 1 => {
+                        if let Some((start, emphasis, _)) = spans.pop() {
+                            text.emphasize(start, emphasis);
+                        }
+                    }
+                    "a" => {
+                        if let Some((start, href)) = links.pop() {
+                            text.link(start, &href);
+                        }
+                    }
+                    "p" | "h" if paragraph_depth > 0 => {
+                        paragraph_depth -= 1;
+
+                        if paragraph_depth > 0 {
+                            continue;
+                        }
+
+                        if let Some((start, emphasis, _)) = spans.pop() {
+                            text.emphasize(start, emphasis);
+                        }
+                        spans.clear();
+                        links.clear();
+
+                        let paragraph = std::mem::take(&mut text);
+
+                        match table.as_mut().and_then(Table::current_cell) {
+                            Some(cell) => cell.push(paragraph),
+                            // empty paragraphs before the first text would become the title
+                            None if state.has_title || !paragraph.is_blank() => {
+                                write_paragraph(&paragraph, &mut *writer, &mut state);
+                                writeln!(writer).unwrap();
+                            }
+                            None => (),
+                        }
+                        state.new_part = false;
+                        state.sequence = false;
+                    }
+                    "list" => {
+                        state.list_depth = state.list_depth.saturating_sub(1);
+                    }
+                    "table" if table_depth > 0 => {
+                        table_depth -= 1;
+
+                        if table_depth == 0 {
+                            if let Some(table) = table.take() {
+                                write_table(&table, &mut *writer, &mut state);
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            Err(e) => {
+                error!("Error: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+const WORD_NAMESPACE: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+
+/// Returns whether a paragraph style of Word is a heading, like `Heading1` or the localized
+/// `berschrift1`.
+fn is_heading_style(style: &str) -> bool {
+    let style = style.to_lowercase();
+
+    style.starts_with("heading") || style.starts_with("berschrift") || style.starts_with("titre")
+}
+
+fn convert_docx<R, W>(reader: R, writer: &mut W)
+where
+    R: Read,
+    W: Write,
+{
+    let mut state = State::default();
+
+    let parser = EventReader::new(reader);
+
+    let mut text = Text::default();
+
+    for e in parser {
+        match e {
+            Ok(XmlEvent::StartDocument { encoding, ..}) => {
+                info!("File encoding {} for document", encoding);
+            }
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                // the fallback of markup compatibility repeats content like text boxes
+                if name.local_name == "Fallback" {
+                    state.ignore_content = true;
+                }
+
+                if name.namespace.as_deref() != Some(WORD_NAMESPACE) {
+                    continue;
+                }
+
+                match name.local_name.as_ref() {
+                    "p" => {
+                        state.new_line = true;
+                    }
+                    "pStyle" => {
+                        let heading = attributes
+                            .iter()
+                            .any(|a| a.name.local_name == "val" && is_heading_style(&a.value));
+
+                        if heading {
+                            state.new_part = true;
+                        }
+                    }
+                    "outlineLvl" => {
+                        state.new_part = true;
+                    }
+                    // only text of runs is content, not deleted text or field instructions
+                    "t" => {
+                        state.relevant_element = true;
+                    }
+                    "tab" | "br" if !state.ignore_content => {
+                        text.push(" ");
+                    }
+                    _ => (),
+                }
+            }
+            Ok(XmlEvent::Characters(s)) | Ok(XmlEvent::Whitespace(s))
+                if state.relevant_element && !state.ignore_content =>
+            {
+                text.push(&s);
+            }
+            Ok(XmlEvent::EndElement { name }) => {
+                if name.local_name == "Fallback" {
+                    state.ignore_content = false;
+                }
+
+                if name.namespace.as_deref() != Some(WORD_NAMESPACE) {
+                    continue;
+                }
+
+                match name.local_name.as_ref() {
+                    "t" => {
+                        state.relevant_element = false;
+                    }
+                    "p" => {
+                        // empty paragraphs before the first text would become the title
+                        if state.has_title || !text.is_blank() {
+                            write_paragraph(&text, &mut *writer, &mut state);
+                            writeln!(writer).unwrap();
+                        }
+                        state.new_part = false;
+                        state.sequence = false;
+                        text = Text::default();
+                    }
+                    _ => (),
+                }
+            }
+            Err(e) => {
+                error!("Error: {}", e);
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns whether the input is a PDF document. Readers accept the header anywhere in the first
+/// kilobyte of the file.
+fn is_pdf<R>(input: &mut R) -> Result<bool>
+where
+    R: Read + Seek,
+{
+    let mut header = Vec::new();
+    input.by_ref().take(1024).read_to_end(&mut header)?;
+    input.seek(SeekFrom::Start(0))?;
+
+    Ok(header.windows(5).any(|w| w == b"%PDF-"))
+}
+
+/// Affine transformation `[a b c d e f]` of PDF coordinates
+type Matrix = [f64; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+fn multiply(m: &Matrix, n: &Matrix) -> Matrix {
+    [
+        m[0] * n[0] + m[1] * n[2],
+        m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2],
+        m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4],
+        m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+fn translate(m: &Matrix, tx: f64, ty: f64) -> Matrix {
+    multiply(&[1.0, 0.0, 0.0, 1.0, tx, ty], m)
+}
+
+fn number(object: &Object) -> Option<f64> {
+    match *object {
+        Object::Integer(i) => Some(i as f64),
+        Object::Real(f) => Some(f),
+        _ => None,
+    }
+}
+
+fn resolve<'a>(document: &'a Document, object: &'a Object) -> &'a Object {
+    document
+        .dereference(object)
+        .map(|(_, object)| object)
+        .unwrap_or(object)
+}
+
+/// Reads the character codes and their text from a `ToUnicode` CMap of a font. Returns the
+/// mapping and the number of bytes of a character code.
+fn parse_cmap(data: &[u8]) -> (HashMap<u32, String>, Option<usize>) {
+    enum Token {
+        Hex(Vec<u8>),
+        Array(Vec<Vec<u8>>),
+        Keyword(String),
+    }
+
+    let data = String::from_utf8_lossy(data);
+    let mut chars = data.chars().peekable();
+    let mut tokens = Vec::new();
+    let mut array: Option<Vec<Vec<u8>>> = None;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '<' if chars.peek() != Some(&'<') => {
+                let hex: String = chars.by_ref().take_while(|&c| c != '>').collect();
+                let hex: Vec<char> = hex.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+                let bytes = hex
+                    .chunks(2)
+                    .map(|pair| {
+                        let pair: String = pair.iter().collect();
+                        u8::from_str_radix(&format!("{:0<2}", pair), 16).unwrap_or_default()
+                    })
+                    .collect();
+
+                match array {
+                    Some(ref mut array) => array.push(bytes),
+                    None => tokens.push(Token::Hex(bytes)),
+                }
+            }
+            '[' => array = Some(Vec::new()),
+            ']' => {
+                if let Some(array) = array.take() {
+                    tokens.push(Token::Array(array));
+                }
+            }
+            '%' => {
+                // comments end with the line
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            c if c.is_alphanumeric() => {
+                let mut keyword = c.to_string();
+
+                while let Some(&c) = chars.peek() {
+                    if !c.is_alphanumeric() {
+                        break;
+                    }
+                    keyword.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Keyword(keyword));
+            }
+            _ => (),
+        }
+    }
+
+    fn code(bytes: &[u8]) -> u32 {
+        bytes.iter().fold(0, |code, &b| code << 8 | u32::from(b))
+    }
+
+    fn text(bytes: &[u8], offset: u32) -> String {
+        let mut units: Vec<u16> = bytes
+            .chunks(2)
+            .map(|pair| pair.iter().fold(0, |unit, &b| unit << 8 | u16::from(b)))
+            .collect();
+
+        if let Some(last) = units.last_mut() {
+            *last = last.wrapping_add(offset as u16);
+        }
+        String::from_utf16_lossy(&units)
+    }
+
+    let mut mapping = HashMap::new();
+    let mut code_length = None;
+    let mut section = "";
+    let mut operands: Vec<&Token> = Vec::new();
+
+    for token in &tokens {
+        match token {
+            Token::Keyword(keyword) => {
+                section = match keyword.as_str() {
+                    "begincodespacerange" => "codespace",
+                    "beginbfchar" => "bfchar",
+                    "beginbfrange" => "bfrange",
+                    _ => "",
+                };
+                operands.clear();
+            }
+            _ => operands.push(token),
+        }
+
+        match (section, operands.as_slice()) {
+            ("codespace", [Token::Hex(low), Token::Hex(_)]) => {
+                code_length = code_length.or(Some(low.len()));
+                operands.clear();
+            }
+            ("bfchar", [Token::Hex(source), Token::Hex(target)]) => {
+                code_length = code_length.or(Some(source.len()));
+                mapping.insert(code(source), text(target, 0));
+                operands.clear();
+            }
+            ("bfrange", [Token::Hex(low), Token::Hex(high), target]) => {
+                code_length = code_length.or(Some(low.len()));
+
+                for (offset, c) in (code(low)..=code(high)).take(65536).enumerate() {
+                    let target = match target {
+                        Token::Hex(target) => Some(text(target, offset as u32)),
+                        Token::Array(targets) => targets.get(offset).map(|t| text(t, 0)),
+                        Token::Keyword(_) => None,
+                    };
+
+                    if let Some(target) = target {
+                        mapping.insert(c, target);
+                    }
+                }
+                operands.clear();
+            }
+            _ => (),
+        }
+    }
+
+    (mapping, code_length)
+}
+
+/// Font of a PDF page, to read the text and the width of strings.
+#[derive(Debug)]
+struct PdfFont {
+    /// Number of bytes of a character code, two for most composite fonts
+    code_length: usize,
+    encoding: String,
+    to_unicode: HashMap<u32, String>,
+    /// Widths of the glyphs in thousandths of the font size
+    widths: HashMap<u32, f64>,
+    default_width: f64,
+}
+
+impl Default for PdfFont {
+    fn default() -> PdfFont {
+        PdfFont {
+            code_length: 1,
+            encoding: "StandardEncoding".to_string(),
+            to_unicode: HashMap::new(),
+            widths: HashMap::new(),
+            default_width: 500.0,
+        }
+    }
+}
+
+impl PdfFont {
+    fn read(document: &Document, font: &Dictionary) -> PdfFont {
+        let mut result = PdfFont::default();
+        let composite = font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0");
+
+        match font.get(b"Encoding").map(|e| resolve(document, e)) {
+            Ok(Object::Name(name)) => {
+                result.encoding = String::from_utf8_lossy(name).into_owned();
+            }
+            Ok(Object::Dictionary(encoding)) => {
+                if let Ok(name) = encoding.get(b"BaseEncoding").and_then(Object::as_name_str) {
+                    result.encoding = name.to_string();
+                }
+            }
+            _ => (),
+        }
+
+        if composite {
+            result.code_length = 2;
+            result.default_width = 1000.0;
+
+            let descendant = font
+                .get(b"DescendantFonts")
+                .map(|d| resolve(document, d))
+                .and_then(Object::as_array)
+                .ok()
+                .and_then(|fonts| fonts.first())
+                .and_then(|d| resolve(document, d).as_dict().ok());
+
+            if let Some(descendant) = descendant {
+                if let Some(width) = descendant.get(b"DW").ok().and_then(number) {
+                    result.default_width = width;
+                }
+                if let Ok(widths) = descendant
+                    .get(b"W")
+                    .map(|w| resolve(document, w))
+                    .and_then(Object::as_array)
+                {
+                    result.read_cid_widths(document, widths);
+                }
+            }
+        } else {
+            let first = font.get(b"FirstChar").ok().and_then(number).unwrap_or(0.0) as u32;
+
+            if let Ok(widths) = font
+                .get(b"Widths")
+                .map(|w| resolve(document, w))
+                .and_then(Object::as_array)
+            {
+                for (i, width) in widths.iter().enumerate() {
+                    let code = match first.checked_add(i as u32) {
+                        Some(code) => code,
+                        None => break,
+                    };
+
+                    if let Some(width) = number(resolve(document, width)) {
+                        result.widths.insert(code, width);
+                    }
+                }
+            }
+        }
+
+        let to_unicode = font
+            .get(b"ToUnicode")
+            .map(|t| resolve(document, t))
+            .and_then(Object::as_stream);
+
+        if let Ok(stream) = to_unicode {
+            let data = stream
+                .decompressed_content()
+                .unwrap_or_else(|_| stream.content.clone());
+            let (mapping, code_length) = parse_cmap(&data);
+
+            result.to_unicode = mapping;
+            if let Some(code_length) = code_length.filter(|&l| l == 1 || l == 2) {
+                result.code_length = code_length;
+            }
+        }
+
+        result
+    }
+
+    /// Reads the widths of composite fonts, given as `first [w1 w2 ...]` or `first last w`.
+    fn read_cid_widths(&mut self, document: &Document, widths: &[Object]) {
+        let mut i = 0;
+
+        while i + 1 < widths.len() {
+            let first = number(resolve(document, &widths[i])).unwrap_or(0.0) as u32;
+
+            match resolve(document, &widths[i + 1]) {
+                Object::Array(list) => {
+                    for (offset, width) in list.iter().enumerate() {
+                        let code = match first.checked_add(offset as u32) {
+                            Some(code) => code,
+                            None => break,
+                        };
+
+                        if let Some(width) = number(width) {
+                            self.widths.insert(code, width);
+                        }
+                    }
+                    i += 2;
+                }
+                last => {
+                    let last = number(last).unwrap_or(0.0) as u32;
+                    let width = widths.get(i + 2).and_then(number);
+
+                    if let Some(width) = width {
+                        for c in (first..=last).take(65536) {
+                            self.widths.insert(c, width);
+                        }
+                    }
+                    i += 3;
+                }
+            }
+        }
+    }
+
+    /// Decodes a string into its character codes and their text. Characters without a known
+    /// text are replaced by U+FFFD.
+    fn decode(&self, bytes: &[u8]) -> Vec<(u32, String)> {
+        bytes
+            .chunks(self.code_length)
+            .map(|c| {
+                let code = c.iter().fold(0, |code, &b| code << 8 | u32::from(b));
+                let text = match self.to_unicode.get(&code) {
+                    Some(text) => text.clone(),
+                    None if self.code_length == 1 => {
+                        let text = Document::decode_text(Some(&self.encoding), c);
+
+                        if text.is_empty() && code > 32 {
+                            char::REPLACEMENT_CHARACTER.to_string()
+                        } else {
+                            text
+                        }
+                    }
+                    None => char::REPLACEMENT_CHARACTER.to_string(),
+                };
+
+                (code, text)
+            })
+            .collect()
+    }
+
+    fn width(&self, code: u32) -> f64 {
+        self.widths
+            .get(&code)
+            .cloned()
+            .unwrap_or(self.default_width)
+    }
+}
+
+/// Text shown at a position of a page
+#[derive(Debug)]
+struct TextChunk {
+    x: f64,
+    y: f64,
+    /// Horizontal position after the text
+    end: f64,
+    size: f64,
+    text: String,
+}
+
+/// Part of the graphics state that is needed to place text, saved and restored by `q` and `Q`
+#[derive(Debug, Clone)]
+struct TextState {
+    transformation: Matrix,
+    font: Vec<u8>,
+    size: f64,
+    char_spacing: f64,
+    word_spacing: f64,
+    scaling: f64,
+    leading: f64,
+}
+
+impl Default for TextState {
+    fn default() -> TextState {
+        TextState {
+            transformation: IDENTITY,
+            font: Vec::new(),
+            size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            scaling: 1.0,
+            leading: 0.0,
+        }
+    }
+}
+
+/// Collects the text of a page with its position from the operations of its content.
+#[derive(Default)]
+struct PdfPage {
+    fonts: HashMap<Vec<u8>, PdfFont>,
+    state: TextState,
+    saved: Vec<TextState>,
+    text_matrix: Matrix,
+    line_matrix: Matrix,
+    chunks: Vec<TextChunk>,
+    characters: usize,
+    unknown_characters: usize,
+}
+
+impl PdfPage {
+    fn new(document: &Document, page_id: ObjectId) -> PdfPage {
+        let fonts = document
+            .get_page_fonts(page_id)
+            .into_iter()
+            .map(|(name, font)| (name, PdfFont::read(document, font)))
+            .collect();
+
+        PdfPage {
+            fonts,
+            text_matrix: IDENTITY,
+            line_matrix: IDENTITY,
+            ..PdfPage::default()
+        }
+    }
+
+    fn next_line(&mut self, tx: f64, ty: f64) {
+        self.line_matrix = translate(&self.line_matrix, tx, ty);
+        self.text_matrix = self.line_matrix;
+    }
+
+    fn show(&mut self, bytes: &[u8]) {
+        let fallback = PdfFont::default();
+        let font = self.fonts.get(&self.state.font).unwrap_or(&fallback);
+        let state = &self.state;
+        let mut text = String::new();
+        let mut advance = 0.0;
+
+        for (code, s) in font.decode(bytes) {
+            let spacing = if font.code_length == 1 && code == 32 {
+                state.char_spacing + state.word_spacing
+            } else {
+                state.char_spacing
+            };
+
+            advance += (font.width(code) / 1000.0 * state.size + spacing) * state.scaling;
+            text.push_str(&s);
+        }
+
+        let start = multiply(&self.text_matrix, &state.transformation);
+        let characters = text.chars().filter(|c| !c.is_whitespace());
+
+        self.characters += characters.clone().count();
+        self.unknown_characters += characters
+            .filter(|&c| c == char::REPLACEMENT_CHARACTER)
+            .count();
+        self.chunks.push(TextChunk {
+            x: start[4],
+            y: start[5],
+            end: start[4] + advance * start[0],
+            size: state.size * start[2].hypot(start[3]),
+            text,
+        });
+        self.text_matrix = translate(&self.text_matrix, advance, 0.0);
+    }
+
+    fn apply(&mut self, operation: &Operation) {
+        let operands = &operation.operands;
+        let numbers: Vec<f64> = operands.iter().filter_map(number).collect();
+
+        match (operation.operator.as_str(), numbers.as_slice()) {
+            ("q", _) => self.saved.push(self.state.clone()),
+            ("Q", _) => {
+                if let Some(state) = self.saved.pop() {
+                    self.state = state;
+                }
+            }
+            ("cm", [a, b, c, d, e, f]) => {
+                self.state.transformation =
+                    multiply(&[*a, *b, *c, *d, *e, *f], &self.state.transformation);
+            }
+            ("BT", _) => {
+                self.text_matrix = IDENTITY;
+                self.line_matrix = IDENTITY;
+            }
+            ("Tf", [size]) => {
+                if let Some(Ok(font)) = operands.first().map(Object::as_name) {
+                    self.state.font = font.to_vec();
+                }
+                self.state.size = *size;
+            }
+            ("Tc", [spacing]) => self.state.char_spacing = *spacing,
+            ("Tw", [spacing]) => self.state.word_spacing = *spacing,
+            ("Tz", [scaling]) => self.state.scaling = scaling / 100.0,
+            ("TL", [leading]) => self.state.leading = *leading,
+            ("Td", [tx, ty]) => self.next_line(*tx, *ty),
+            ("TD", [tx, ty]) => {
+                self.state.leading = -ty;
+                self.next_line(*tx, *ty);
+            }
+            ("Tm", [a, b, c, d, e, f]) => {
+                self.line_matrix = [*a, *b, *c, *d, *e, *f];
+                self.text_matrix = self.line_matrix;
+            }
+            ("T*", _) => self.next_line(0.0, -self.state.leading),
+            ("'", _) | ("\"", _) => {
+                if let [word_spacing, char_spacing] = numbers.as_slice() {
+                    self.state.word_spacing = *word_spacing;
+                    self.state.char_spacing = *char_spacing;
+                }
+                self.next_line(0.0, -self.state.leading);
+
+                if let Some(Ok(bytes)) = operands.last().map(Object::as_str) {
+                    self.show(bytes);
+                }
+            }
+            ("Tj", _) => {
+                if let Some(Ok(bytes)) = operands.first().map(Object::as_str) {
+                    self.show(bytes);
+                }
+            }
+            ("TJ", _) => {
+                let elements = operands.first().map(Object::as_array);
+
+                for element in elements.into_iter().flatten().flatten() {
+                    match element {
+                        Object::String(bytes, _) => self.show(bytes),
+                        adjustment => {
+                            if let Some(adjustment) = number(adjustment) {
+                                let tx =
+                                    -adjustment / 1000.0 * self.state.size * self.state.scaling;
+                                self.text_matrix = translate(&self.text_matrix, tx, 0.0);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Joins the text of the page into lines, from top to bottom and from left to right. Text
+    /// is separated by a space if there is a gap before it.
+    fn lines(mut self) -> Vec<String> {
+        self.chunks.retain(|chunk| !chunk.text.trim().is_empty());
+        self.chunks
+            .sort_by(|a, b| b.y.partial_cmp(&a.y).unwrap_or(Ordering::Equal));
+
+        let mut lines: Vec<Vec<TextChunk>> = Vec::new();
+
+        for chunk in self.chunks {
+            match lines.last_mut() {
+                Some(line) if (line[0].y - chunk.y).abs() < line[0].size.max(chunk.size) / 2.0 => {
+                    line.push(chunk)
+                }
+                _ => lines.push(vec![chunk]),
+            }
+        }
+
+        lines
+            .into_iter()
+            .map(|mut line| {
+                line.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap_or(Ordering::Equal));
+
+                let mut text = String::new();
+                let mut end: Option<f64> = None;
+
+                for chunk in line {
+                    match end {
+                        Some(end) if chunk.x - end > chunk.size * 0.15 => text.push(' '),
+                        _ => (),
+                    }
+                    text.push_str(&chunk.text);
+                    end = Some(chunk.end);
+                }
+
+                text.split_whitespace().collect::<Vec<_>>().join(" ")
+            })
+            .collect()
+    }
+}
+
+/// Converts the text of a PDF document. The lines are reconstructed from the position of the
+/// text and then read like the paragraphs of other documents, as PDF has no structure to tell
+/// titles, parts and cues apart. Documents without text, like scanned cue sheets, and text that
+/// can't be decoded or read as a cue sheet are reported for review.
+fn convert_pdf<R, W>(input: &mut R, writer: &mut W) -> Result<Conversion>
+where
+    R: Read,
+    W: Write,
+{
+    let document = match Document::load_from(input) {
+        Ok(document) => document,
+        Err(err) => {
+            error!("Unable to read PDF document: {:?}", err);
+            return Err(Error::new(ErrorKind::InvalidData, err.to_string()));
+        }
+    };
+
+    if document.trailer.get(b"Encrypt").is_ok() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Encrypted PDF documents are not supported",
+        ));
+    }
+
+    let mut state = State::default();
+    let mut conversion = Conversion::default();
+    let mut characters = 0;
+    let mut unknown_characters = 0;
+    let mut has_cues = false;
+
+    for (number, page_id) in document.get_pages() {
+        let mut page = PdfPage::new(&document, page_id);
+
+        match document.get_and_decode_page_content(page_id) {
+            Ok(content) => {
+                for operation in &content.operations {
+                    page.apply(operation);
+                }
+            }
+            Err(err) => {
+                error!("Unable to read page {} of PDF document: {:?}", number, err);
+                conversion
+                    .warnings
+                    .push(format!("Page {} could not be read", number));
+            }
+        }
+
+        characters += page.characters;
+        unknown_characters += page.unknown_characters;
+
+        for line in page.lines() {
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut text = Text::default();
+            text.push(&line);
+            has_cues |= state.has_title && line.contains(';');
+
+            state.new_line = true;
+            write_paragraph(&text, &mut *writer, &mut state);
+            writeln!(writer).unwrap();
+            state.sequence = false;
+        }
+    }
+
+    if characters == 0 {
+        conversion
+            .warnings
+            .push("No text was found, the PDF might only contain scanned pages".to_string());
+    } else {
+        if unknown_characters * 50 > characters {
+            conversion.warnings.push(format!(
+                "{} of {} characters could not be decoded",
+                unknown_characters, characters
+            ));
+        }
+        if !state.has_parts {
+            conversion
+                .warnings
+                .push("No parts were recognized".to_string());
+        }
+        if !has_cues {
+            conversion
+                .warnings
+                .push("No cues were recognized".to_string());
+        }
+    }
+
+    Ok(conversion)
+}
+
+fn write_paragraph<W>(text: &Text, writer: &mut W, state: &mut State)
+where
+    W: Write,
+{
+    let s = text.plain.trim();
+
+    if s.to_lowercase().contains("seq") {
+        state.sequence = true;
+    }
+
+    let fields = if state.has_parts {
+        Vec::new()
+    } else {
+        metadata_fields(s)
+    };
+
+    if !fields.is_empty() {
+        write_metadata(&fields, writer);
+    } else if !state.has_title {
+        writeln!(writer, "# {}", s).unwrap();
+        info!("# {}", s);
+        state.has_title = true;
+    } else if state.new_part {
+        writeln!(writer).unwrap();
+        write!(writer, "# {}", s).unwrap();
+        info!("# {}", s);
+        state.new_part = false;
+        state.has_parts = true;
+    } else if matches_part_title(s) && !state.sequence {
+        writeln!(writer).unwrap();
+        write!(writer, "# {}", s).unwrap();
+        info!("#: {}", s);
+        state.has_parts = true;
+    } else if s.contains(';') {
+        if state.new_line {
+            writeln!(writer).unwrap();
+            write!(writer, "> ").unwrap();
+            
+            state.new_line = false;
+        }
+        let text = text.formatted.trim().replace("\n", "").replace("\r", "");
+        write!(writer, "{}", text).unwrap();
+        info!("> {}", text);
+    } else {
+        if state.new_line {
+            writeln!(writer).unwrap();
+        }
+        if state.list_depth > 0 {
+            write!(writer, "{}* ", "  ".repeat(state.list_depth - 1)).unwrap();
+        }
+        let text = text.formatted.trim().replace("\n", "").replace("\r", "");
+        write!(writer, "{}", text).unwrap();
+        info!("{}", text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn docx(body: &[&str]) -> Cursor<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("word/document.xml", FileOptions::default())
+            .unwrap();
+        write!(
+            zip,
+            r#"<?xml version="1.0"?><w:document xmlns:w="{}"><w:body>{}</w:body></w:document>"#,
+            WORD_NAMESPACE,
+            body.concat()
+        )
+        .unwrap();
+
+        let mut input = zip.finish().unwrap();
+        input.set_position(0);
+        input
+    }
+
+    #[test]
+    fn test_convert_docx() {
+        let mut input = docx(&[
+            "<w:p/>",
+            "<w:p><w:r><w:t>Test Waltz</w:t></w:r></w:p>",
+            "<w:p><w:r><w:t>Sequence: Intro, A, B, End</w:t></w:r></w:p>",
+            r#"<w:p><w:pPr><w:pStyle w:val="Heading2"/></w:pPr><w:r><w:t>Intro</w:t></w:r></w:p>"#,
+            r#"<w:p><w:r><w:t xml:space="preserve">1-4 </w:t></w:r>"#,
+            "<w:r><w:t>Wait;; Apart Point;</w:t></w:r></w:p>",
+            "<w:p><w:r><w:t>Part A</w:t></w:r></w:p>",
+            r#"<w:p><w:r><w:t xml:space="preserve">Step </w:t></w:r><w:hyperlink>"#,
+            "<w:r><w:t>Video</w:t></w:r></w:hyperlink></w:p>",
+        ]);
+
+        let mut output = Vec::new();
+        convert_to_markdown(&mut input, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "# Test Waltz\n\n\n* **Sequence**: Intro, A, B, End\n\n# Intro\n\n\
+             > 1-4 Wait;; Apart Point;\n\n# Part A\n\nStep Video\n"
+        );
+    }
+
+    fn odt(styles: &str, body: &[&str]) -> Cursor<Vec<u8>> {
+        let namespaces = concat!(
+            r#"xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" "#,
+            r#"xmlns:style="urn:oasis:names:tc:opendocument:xmlns:style:1.0" "#,
+            r#"xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" "#,
+            r#"xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" "#,
+            r#"xmlns:fo="urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0" "#,
+            r#"xmlns:xlink="http://www.w3.org/1999/xlink""#
+        );
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("content.xml", FileOptions::default())
+            .unwrap();
+        write!(
+            zip,
+            r#"<?xml version="1.0"?><office:document-content {}>"#,
+            namespaces
+        )
+        .unwrap();
+        write!(
+            zip,
+            "<office:automatic-styles>{}</office:automatic-styles>",
+            styles
+        )
+        .unwrap();
+        write!(
+            zip,
+            "<office:body><office:text>{}</office:text></office:body></office:document-content>",
+            body.concat()
+        )
+        .unwrap();
+
+        let mut input = zip.finish().unwrap();
+        input.set_position(0);
+        input
+    }
+
+    #[test]
+    fn test_convert_odt() {
+        let mut input = odt(
+            concat!(
+                r#"<style:style style:name="T1" style:family="text">"#,
+                r#"<style:text-properties fo:font-weight="bold"/></style:style>"#,
+                r#"<style:style style:name="T2" style:family="text">"#,
+                r#"<style:text-properties fo:font-style="italic"/></style:style>"#,
+            ),
+            &[
+                "<text:p/>",
+                "<text:h>Test Waltz</text:h>",
+                "<table:table><table:table-row>",
+                "<table:table-cell><text:p>Choreographer:</text:p></table:table-cell>",
+                "<table:table-cell><text:p>Jane Doe</text:p></table:table-cell>",
+                "</table:table-row><table:table-row>",
+                "<table:table-cell><text:p>Music</text:p></table:table-cell>",
+                "<table:table-cell><text:p>Moon River</text:p></table:table-cell>",
+                "</table:table-row></table:table>",
+                "<text:p>Rhythm: Waltz<text:tab/>Phase: 4 + 1 (Hover)</text:p>",
+                "<text:p>Sequence: Intro A B End<text:note><text:note-body>",
+                "<text:p>Note</text:p></text:note-body></text:note></text:p>",
+                "<text:list><text:list-item><text:p>Slow down at the end",
+                "</text:p></text:list-item></text:list>",
+                "<text:h>Intro</text:h>",
+                "<text:p>1-4<text:s/><text:span text:style-name=\"T1\">Wait;;</text:span>",
+                "<text:s text:c=\"2\"/><text:span text:style-name=\"T1\">Apart</text:span>",
+                "<text:span text:style-name=\"T1\"> Point;</text:span></text:p>",
+                "<text:p>Part A</text:p>",
+                "<text:p><text:span text:style-name=\"T2\">Step </text:span>",
+                "<text:a xlink:href=\"https://example.com\">Video</text:a></text:p>",
+                "<table:table><table:table-row>",
+                "<table:table-cell><text:p>Lady</text:p></table:table-cell>",
+                "<table:table-cell><text:p>Man</text:p></table:table-cell>",
+                "</table:table-row><table:table-row>",
+                "<table:table-cell><text:p>Left|Right</text:p></table:table-cell>",
+                "<table:covered-table-cell/>",
+                "</table:table-row></table:table>",
+            ],
+        );
+
+        let mut output = Vec::new();
+        convert_to_markdown(&mut input, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "# Test Waltz\n\n\n* **Choreographer**: Jane Doe\n\n* **Music**: Moon River\n\n\
+             * **Rhythm**: Waltz\n* **Phase**: IV+1 (Hover)\n\n* **Sequence**: Intro A B End\n\n\
+             * Slow down at the end\n\n# Intro\n\n> 1-4 **Wait;;**  **Apart Point;**\n\n\
+             # Part A\n\n*Step* [Video](https://example.com)\n\n\
+             | Lady | Man |\n| --- | --- |\n| Left\\|Right |  |\n"
+        );
+    }
+
+    #[test]
+    fn test_metadata_fields() {
+        assert_eq!(
+            metadata_fields("Choreographers: Jane & John Doe, Sometown"),
+            vec![("Choreographer", "Jane & John Doe, Sometown".to_string())]
+        );
+        assert_eq!(
+            metadata_fields("Phase: Phase V   Footwork: Opposite"),
+            vec![
+                ("Phase", "V".to_string()),
+                ("Footwork", "Opposite".to_string())
+            ]
+        );
+        assert_eq!(
+            metadata_fields("Phase: Intermediate"),
+            vec![("Phase", "Intermediate".to_string())]
+        );
+        assert!(metadata_fields("Released 2019, Music: Moon River").is_empty());
+        assert!(metadata_fields("Intro: wait").is_empty());
+    }
+
+    fn pdf(pages: &[&str]) -> Cursor<Vec<u8>> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let font_id = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        });
+        let kids: Vec<Object> = pages
+            .iter()
+            .map(|content| {
+                let content_id =
+                    document.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+
+                document
+                    .add_object(dictionary! {
+                        "Type" => "Page",
+                        "Parent" => pages_id,
+                        "Contents" => content_id,
+                    })
+                    .into()
+            })
+            .collect();
+        let count = kids.len() as i64;
+
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => count,
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }),
+        );
+
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let mut output = Vec::new();
+        document.save_to(&mut output).unwrap();
+        Cursor::new(output)
+    }
+
+    #[test]
+    fn test_convert_pdf() {
+        let mut input = pdf(&[
+            concat!(
+                "BT /F1 16 Tf 72 760 Td (Moon River) Tj ET ",
+                "BT /F1 10 Tf 14 TL 72 730 Td (Choreographer: Jane Doe) Tj ",
+                "(Phase: Phase IV + 1) ' ET ",
+                "BT /F1 12 Tf 72 680 Td (Intro) Tj 0 -20 Td [(Wait;;) -300 (Apart, -, Point, -;)] TJ ",
+                "ET BT /F1 12 Tf 1 0 0 1 200 620 Tm (Fwd Waltz;) Tj ",
+                "1 0 0 1 72 620 Tm (Twirl Vine 3;) Tj ET"
+            ),
+            "q 1 0 0 1 0 700 cm BT /F1 12 Tf 72 0 Td (End) Tj 0 -20 Td (Apart & Point;) Tj ET Q",
+        ]);
+        let mut output = Vec::new();
+
+        let conversion = convert_to_markdown(&mut input, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "# Moon River\n\n",
+                "\n* **Choreographer**: Jane Doe\n",
+                "\n* **Phase**: IV+1\n",
+                "\n# Intro\n",
+                "\n> Wait;; Apart, -, Point, -;\n",
+                "\n> Twirl Vine 3; Fwd Waltz;\n",
+                "\n# End\n",
+                "\n> Apart & Point;\n",
+            )
+        );
+        assert!(!conversion.needs_review());
+    }
+
+    #[test]
+    fn test_convert_pdf_needs_review() {
+        let mut output = Vec::new();
+        let conversion = convert_to_markdown(&mut pdf(&["0 0 100 100 re f"]), &mut output).unwrap();
+
+        assert!(output.is_empty());
+        assert_eq!(
+            conversion.warnings,
+            vec!["No text was found, the PDF might only contain scanned pages"]
+        );
+
+        let mut output = Vec::new();
+        let conversion = convert_to_markdown(
+            &mut pdf(&["BT /F1 12 Tf 72 700 Td (Moon River) Tj ET"]),
+            &mut output,
+        )
+        .unwrap();
+
+        assert!(conversion.needs_review());
+        assert_eq!(
+            conversion.warnings,
+            vec!["No parts were recognized", "No cues were recognized"]
+        );
+    }
+
+    #[test]
+    fn test_parse_cmap() {
+        let (mapping, code_length) = parse_cmap(
+            concat!(
+                "/CIDInit /ProcSet findresource begin\n",
+                "1 begincodespacerange <0000> <FFFF> endcodespacerange\n",
+                "2 beginbfchar <0003> <0020> <0011> <00660066> endbfchar\n",
+                "2 beginbfrange <0024> <0026> <0041> <0030> <0031> [<00E4> <00F6>] endbfrange\n",
+                "endcmap"
+            )
+            .as_bytes(),
+        );
+
+        assert_eq!(code_length, Some(2));
+        assert_eq!(mapping[&0x03], " ");
+        assert_eq!(mapping[&0x11], "ff");
+        assert_eq!(mapping[&0x24], "A");
+        assert_eq!(mapping[&0x26], "C");
+        assert_eq!(mapping[&0x31], "ö");
+        assert_eq!(mapping.len(), 7);
+    }
+
+    #[test]
+    fn test_font_widths_overflow() {
+        let document = Document::with_version("1.5");
+        let last = i64::from(u32::max_value());
+
+        let font = PdfFont::read(
+            &document,
+            &dictionary! {
+                "Subtype" => "Type1",
+                "FirstChar" => last,
+                "Widths" => vec![500.into(), 600.into()],
+            },
+        );
+        assert_eq!(font.widths.len(), 1);
+        assert_eq!(font.widths[&u32::max_value()], 500.0);
+
+        let mut font = PdfFont::default();
+        font.read_cid_widths(
+            &document,
+            &[
+                last.into(),
+                vec![500.into(), 600.into()].into(),
+                3.into(),
+                vec![700.into()].into(),
+            ],
+        );
+        assert_eq!(font.widths.len(), 2);
+        assert_eq!(font.widths[&3], 700.0);
+    }
+}
@@ -0,0 +1,425 @@
+/**
+
+Import of many cue sheets at once.
+
+The ODT, DOCX and PDF documents of a zip archive or a directory are converted to Markdown and
+placed in the collection like single converted cue sheets, at the path of a placement template.
+Existing cue cards are never replaced, even if their file is missing: a document converted to
+the same content is skipped, others are stored with a number added to the file name. The result
+of every file is reported, so problems can be fixed and the remaining files imported again.
+Imported cue cards that should be checked, like unreliable conversions of PDF documents, are
+marked as needing review.
+
+**/
+use crate::content_metadata;
+use crate::convert;
+use crate::placement;
+use crate::placement::PlacementTemplate;
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::io::{Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Imported,
+    Skipped,
+    Failed,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportedFile {
+    /// Path of the document in the archive or directory
+    pub source: String,
+    /// Path of the cue card relative to the collection
+    pub target_path: Option<String>,
+    pub status: ImportStatus,
+    pub message: Option<String>,
+    /// The cue card was imported but should be checked, see `convert::Conversion`
+    pub needs_review: bool,
+}
+
+impl ImportedFile {
+    fn new(source: &str, status: ImportStatus, message: &str) -> ImportedFile {
+        ImportedFile {
+            source: source.to_string(),
+            target_path: None,
+            status,
+            message: Some(message.to_string()),
+            needs_review: false,
+        }
+    }
+}
+
+/// Returns whether the converter supports a document.
+pub fn is_supported(name: &str) -> bool {
+    let name = name.to_lowercase();
+
+    name.ends_with(".odt") || name.ends_with(".docx") || name.ends_with(".pdf")
+}
+
+/// Returns whether a file is hidden, like the resource forks macOS adds to archives.
+fn is_hidden(source: &str) -> bool {
+    let name = Path::new(source)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+
+    source.contains("__MACOSX") || name.is_empty() || name.starts_with('.')
+}
+
+/// Converts a document and stores it in the collection at `basepath`, `cuecard_paths` are the
+/// paths of the cue cards in the database.
+fn import_document(
+    source: &str,
+    data: Vec<u8>,
+    basepath: &Path,
+    template: &PlacementTemplate,
+    cuecard_paths: &HashSet<PathBuf>,
+) -> ImportedFile {
+    if !is_supported(source) {
+        return ImportedFile::new(
+            source,
+            ImportStatus::Skipped,
+            "Not an ODT, DOCX or PDF document",
+        );
+    }
+
+    let mut markdown = Vec::new();
+
+    let conversion = match convert::convert_to_markdown(&mut Cursor::new(data), &mut markdown) {
+        Ok(conversion) => conversion,
+        Err(err) => return ImportedFile::new(source, ImportStatus::Failed, &err.to_string()),
+    };
+
+    if markdown.is_empty() {
+        return ImportedFile::new(source, ImportStatus::Failed, "No content found");
+    }
+
+    let metadata = content_metadata(&String::from_utf8_lossy(&markdown));
+    let filename = Path::new(source)
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let mut target = template.path(&metadata, &filename);
+    let mut notes = Vec::new();
+
+    let taken =
+        |candidate: &Path| basepath.join(candidate).exists() || cuecard_paths.contains(candidate);
+
+    if taken(&target) {
+        // imports can be repeated, so the same content at the path or one of its numbered
+        // variants is taken as imported before
+        let copies = std::iter::once(target.clone())
+            .chain((2..).map(|number| placement::numbered_path(&target, number)))
+            .take_while(|candidate| taken(candidate));
+
+        for candidate in copies {
+            if fs::read(basepath.join(&candidate)).ok().as_ref() == Some(&markdown) {
+                info!("Skipped {}, {:?} already exists", source, candidate);
+
+                return ImportedFile {
+                    target_path: Some(candidate.to_string_lossy().into_owned()),
+                    ..ImportedFile::new(
+                        source,
+                        ImportStatus::Skipped,
+                        "The cue card already exists",
+                    )
+                };
+            }
+        }
+
+        let existing = target.to_string_lossy().into_owned();
+
+        if basepath.join(&target).exists() {
+            notes.push(format!("{} already exists with other content", existing));
+        } else {
+            notes.push(format!("{} belongs to another cue card", existing));
+        }
+
+        target = placement::unique_path(basepath, &target, None, cuecard_paths);
+    }
+
+    let target_path = Some(target.to_string_lossy().into_owned());
+    let path = basepath.join(&target);
+
+    let written = match path.parent() {
+        Some(parent) => fs::create_dir_all(parent).and_then(|_| fs::write(&path, markdown)),
+        None => fs::write(&path, markdown),
+    };
+
+    match written {
+        Ok(_) => {
+            info!("Imported {} as {:?}", source, path);
+
+            ImportedFile {
+                source: source.to_string(),
+                target_path,
+                status: ImportStatus::Imported,
+                message: Some([notes, conversion.warnings.clone()].concat().join("; "))
+                    .filter(|m| !m.is_empty()),
+                needs_review: conversion.needs_review(),
+            }
+        }
+        Err(err) => {
+            error!("Error writing {:?}: {:?}", path, err);
+            ImportedFile::new(source, ImportStatus::Failed, &err.to_string())
+        }
+    }
+}
+
+/// Imports the documents of a zip archive into the collection at `basepath`, see `import`.
+pub fn import_archive<R>(
+    input: R,
+    basepath: &Path,
+    template: &PlacementTemplate,
+    cuecard_paths: &HashSet<PathBuf>,
+) -> io::Result<Vec<ImportedFile>>
+where
+    R: Read + Seek,
+{
+    let mut archive = zip::ZipArchive::new(input)?;
+    let mut result = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let source = file.name().to_string();
+
+        if file.is_dir() || is_hidden(&source) {
+            continue;
+        }
+
+        let mut data = Vec::new();
+
+        match file.read_to_end(&mut data) {
+            Ok(_) => result.push(import_document(
+                &source,
+                data,
+                basepath,
+                template,
+                cuecard_paths,
+            )),
+            Err(err) => {
+                result.push(ImportedFile::new(
+                    &source,
+                    ImportStatus::Failed,
+                    &err.to_string(),
+                ));
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Imports the documents of a directory and its subdirectories into the collection at
+/// `basepath`, see `import`.
+pub fn import_directory(
+    directory: &Path,
+    basepath: &Path,
+    template: &PlacementTemplate,
+    cuecard_paths: &HashSet<PathBuf>,
+) -> Vec<ImportedFile> {
+    let mut result = Vec::new();
+
+    for entry in WalkDir::new(directory)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        let source = entry
+            .path()
+            .strip_prefix(directory)
+            .unwrap_or_else(|_| entry.path())
+            .to_string_lossy()
+            .into_owned();
+
+        if is_hidden(&source) {
+            continue;
+        }
+
+        match fs::read(entry.path()) {
+            Ok(data) => result.push(import_document(
+                &source,
+                data,
+                basepath,
+                template,
+                cuecard_paths,
+            )),
+            Err(err) => {
+                result.push(ImportedFile::new(
+                    &source,
+                    ImportStatus::Failed,
+                    &err.to_string(),
+                ));
+            }
+        }
+    }
+
+    result
+}
+
+/// Imports the documents of `source`, which is either a zip archive or a directory. The paths
+/// of the cue cards in the database, `cuecard_paths`, are taken even if their files are missing,
+/// see `placement::cuecard_paths`.
+pub fn import(
+    source: &Path,
+    basepath: &Path,
+    template: &PlacementTemplate,
+    cuecard_paths: &HashSet<PathBuf>,
+) -> io::Result<Vec<ImportedFile>> {
+    if source.is_dir() {
+        Ok(import_directory(source, basepath, template, cuecard_paths))
+    } else {
+        import_archive(fs::File::open(source)?, basepath, template, cuecard_paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Document, Object, Stream};
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (name, content) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn pdf(content: &str) -> Vec<u8> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let content_id =
+            document.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let mut output = Vec::new();
+        document.save_to(&mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_import_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("e")).unwrap();
+        fs::write(dir.path().join("e/existing.md"), "# Existing").unwrap();
+
+        let odt = zip(&[(
+            "content.xml",
+            concat!(
+                r#"<office:document-content xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" "#,
+                r#"xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0">"#,
+                "<office:body><office:text><text:p>Moon River</text:p></office:text></office:body>",
+                "</office:document-content>"
+            )
+            .as_bytes(),
+        )]);
+        let archive = zip(&[
+            ("workshop/Moon River.odt", &odt),
+            ("workshop/existing.odt", &odt),
+            (
+                "workshop/Waltz.pdf",
+                &pdf("BT /F1 12 Tf 72 700 Td (Waltz) Tj ET"),
+            ),
+            ("workshop/scan.pdf", &pdf("0 0 100 100 re f")),
+            ("workshop/broken.docx", b"broken"),
+            ("workshop/notes.txt", b"notes"),
+            ("__MACOSX/workshop/._Moon River.odt", b"fork"),
+        ]);
+
+        let template = PlacementTemplate::default();
+        let result =
+            import_archive(Cursor::new(archive), dir.path(), &template, &HashSet::new()).unwrap();
+        let statuses: Vec<(&str, &ImportStatus)> = result
+            .iter()
+            .map(|f| (f.source.as_str(), &f.status))
+            .collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                ("workshop/Moon River.odt", &ImportStatus::Imported),
+                ("workshop/existing.odt", &ImportStatus::Imported),
+                ("workshop/Waltz.pdf", &ImportStatus::Imported),
+                ("workshop/scan.pdf", &ImportStatus::Failed),
+                ("workshop/broken.docx", &ImportStatus::Failed),
+                ("workshop/notes.txt", &ImportStatus::Skipped),
+            ]
+        );
+        assert_eq!(result[0].target_path.as_deref(), Some("m/Moon River.md"));
+        assert!(!result[0].needs_review);
+        assert!(result[2].needs_review);
+        assert_eq!(
+            result[2].message.as_deref(),
+            Some("No parts were recognized; No cues were recognized")
+        );
+        assert_eq!(result[3].message.as_deref(), Some("No content found"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("m/Moon River.md")).unwrap(),
+            "# Moon River\n\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("e/existing.md")).unwrap(),
+            "# Existing"
+        );
+        assert_eq!(result[1].target_path.as_deref(), Some("e/existing (2).md"));
+        assert_eq!(
+            result[1].message.as_deref(),
+            Some("e/existing.md already exists with other content")
+        );
+
+        // importing the same documents again doesn't add them twice
+        let archive = zip(&[
+            ("workshop/Moon River.odt", &odt),
+            ("workshop/existing.odt", &odt),
+        ]);
+        let result =
+            import_archive(Cursor::new(archive), dir.path(), &template, &HashSet::new()).unwrap();
+        assert_eq!(result[0].status, ImportStatus::Skipped);
+        assert_eq!(result[0].target_path.as_deref(), Some("m/Moon River.md"));
+        assert_eq!(result[1].status, ImportStatus::Skipped);
+        assert_eq!(result[1].target_path.as_deref(), Some("e/existing (2).md"));
+
+        // the file of a cue card in the database may be missing
+        let archive = zip(&[("workshop/Sunrise.odt", &odt)]);
+        let cuecard_paths = vec![PathBuf::from("s/Sunrise.md")].into_iter().collect();
+        let result =
+            import_archive(Cursor::new(archive), dir.path(), &template, &cuecard_paths).unwrap();
+        assert_eq!(result[0].status, ImportStatus::Imported);
+        assert_eq!(result[0].target_path.as_deref(), Some("s/Sunrise (2).md"));
+        assert_eq!(
+            result[0].message.as_deref(),
+            Some("s/Sunrise.md belongs to another cue card")
+        );
+        assert!(!dir.path().join("s/Sunrise.md").exists());
+    }
+}
@@ -13,6 +13,12 @@ extern crate chrono;
 extern crate cuer_database;
 extern crate filetime;
 extern crate uuid as uuidcrate;
+extern crate xml;
+extern crate zip;
+
+pub mod convert;
+pub mod import;
+pub mod placement;
 
 use self::cuer_database::*;
 use self::diesel::prelude::*;
@@ -27,11 +33,14 @@ use uuidcrate::Uuid;
 use std::boxed::Box;
 use std::collections::HashMap;
 use std::fmt;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::SystemTime;
 use std::vec::Vec;
 
+const INDEX_FILE_PREFIX: &str = ".de.sopicki.cuelib.";
+
 pub struct Config {
     pub basepath: String,
     pub database_url: String,
@@ -112,37 +121,42 @@ impl IndexFileData {
         self.content = content.to_string();
     }
 
-    fn metadata(&mut self) -> &mut HashMap<MetaDataType, String> {
-        self.meta.as_mut()
-    }
-
     fn get_meta(&self, key: MetaDataType) -> Option<&String> {
         self.meta.as_ref().get(&key)
     }
 
     fn index_file(&self) -> Option<PathBuf> {
-        let mut filename = ".de.sopicki.cuelib.".to_string();
-        filename.push_str(self.path.file_name().to_str().unwrap());
-        filename = filename.to_string();
-        let path = Path::new(&filename).to_owned();
-        let parent = self.path.path().parent().unwrap();
-        Some(parent.join(&path))
+        index_file_path(self.path.path())
     }
 
     fn metadata_file(&self) -> PathBuf {
-        self.path.path().with_extension("meta.json")
+        metadata_file_path(self.path.path())
     }
 }
 
+/// Returns the path of the hidden file storing the UUID of the cue card at `path`.
+pub fn index_file_path(path: &Path) -> Option<PathBuf> {
+    let mut filename = INDEX_FILE_PREFIX.to_string();
+    filename.push_str(path.file_name()?.to_str()?);
+    Some(path.with_file_name(filename))
+}
+
+/// Returns the path of the file storing the metadata of the cue card at `path`.
+pub fn metadata_file_path(path: &Path) -> PathBuf {
+    path.with_extension("meta.json")
+}
+
 fn is_allowed(filename: &str) -> bool {
-    if filename.ends_with(".md") && !filename.starts_with(".de.sopicki.cuelib") {
+    if filename.ends_with(".md") && !filename.starts_with(INDEX_FILE_PREFIX) {
         return true;
     }
 
     false
 }
 
-fn process(entry: DirEntry, base_path: &str) -> IndexFileData {
+/// Reads the title and the metadata lines like `* **Phase**: IV+2` of the Markdown content of
+/// a cue card. The phase is split into phase and plus figures.
+fn parse_metadata(content: &str) -> HashMap<MetaDataType, String> {
     let title_pattern = Lazy::new(|| Regex::new(r"^#\s+(?P<title>.*)$").unwrap());
     let meta_pattern = Lazy::new(|| {
         Regex::new(r"^[\*]\s+[\*][\*](?P<metaname>\w+)[\*][\*]:\s+(?P<metatext>.*)$").unwrap()
@@ -151,7 +165,95 @@ fn process(entry: DirEntry, base_path: &str) -> IndexFileData {
 
     let mail_pattern = Lazy::new(|| Regex::new(r"\[(?P<name>.+)\]\(mailto.*\)").unwrap());
 
-    let content = std::fs::read_to_string(entry.path()).unwrap();
+    let mut meta_data = HashMap::new();
+    let mut has_title = false;
+
+    for line in content.lines() {
+        if !has_title {
+            if let Some(caps) = title_pattern.captures(line) {
+                meta_data.insert(
+                    MetaDataType::Title,
+                    caps.name("title").unwrap().as_str().to_string(),
+                );
+                has_title = true;
+            }
+        }
+
+        if let Some(caps) = meta_pattern.captures(line) {
+            let name = caps.name("metaname").unwrap().as_str();
+            let name = name.to_lowercase();
+            let key = name.as_str();
+            let mut text = caps.name("metatext").unwrap().as_str();
+
+            if mail_pattern.is_match(text) {
+                debug!("Mail detected. Extracting name.");
+                let mail_caps = mail_pattern.captures(text).unwrap();
+                text = mail_caps.name("name").unwrap().as_str();
+                debug!("Name extracted: {:?}", text);
+            }
+
+            meta_data.insert(MetaDataType::from_str(key).unwrap(), text.to_string());
+        }
+    }
+
+    let default = "unphased".to_string();
+
+    let phase = meta_data
+        .get(&MetaDataType::Phase)
+        .unwrap_or(&default)
+        .clone();
+
+    match phase_pattern.captures(&phase) {
+        Some(caps) => {
+            let p = match caps.get(1) {
+                Some(m) => m.as_str(),
+                _ => "unphased",
+            };
+
+            let plusfigures = match caps.get(2) {
+                Some(m) => m.as_str(),
+                _ => "",
+            };
+
+            meta_data.insert(MetaDataType::Phase, p.to_string());
+            meta_data.insert(MetaDataType::Plusfigures, plusfigures.to_string());
+        }
+        _ => {
+            meta_data.insert(MetaDataType::Phase, "unphased".to_owned());
+            meta_data.insert(MetaDataType::Plusfigures, "".to_owned());
+        }
+    }
+
+    meta_data
+}
+
+/// Returns the metadata the indexer reads from the Markdown content of a cue card, with keys
+/// like `title`, `phase` and `plusfigures`.
+pub fn content_metadata(content: &str) -> HashMap<String, String> {
+    parse_metadata(content)
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+}
+
+/// Returns the metadata the indexer stores for the cue card at `path` with the Markdown
+/// `content`, the metadata of the content overridden by the metadata file of the cue card.
+pub fn file_metadata(path: &Path, content: &str) -> io::Result<HashMap<String, String>> {
+    let mut metadata = parse_metadata(content);
+    let metadata_file = metadata_file_path(path);
+
+    if metadata_file.exists() {
+        process_metadata_file(&metadata_file, &mut metadata)?;
+    }
+
+    Ok(metadata
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect())
+}
+
+fn process(entry: DirEntry, base_path: &str) -> io::Result<IndexFileData> {
+    let content = std::fs::read_to_string(entry.path())?;
     let file_path = entry
         .path()
         .strip_prefix(base_path)
@@ -159,143 +261,90 @@ fn process(entry: DirEntry, base_path: &str) -> IndexFileData {
         .to_str()
         .expect("string conversion failed")
         .to_string();
+    let has_metadata_file = metadata_file_path(entry.path()).exists();
+    let meta = file_metadata(entry.path(), &content)?
+        .into_iter()
+        .filter_map(|(key, value)| MetaDataType::from_str(&key).ok().map(|key| (key, value)))
+        .collect();
     let mut index_file = IndexFileData {
         path: entry,
         content: "".to_owned(),
-        meta: Box::new(HashMap::new()),
+        meta: Box::new(meta),
         file_path,
     };
 
-    {
-        let meta_data = index_file.metadata();
-        let mut has_title = false;
-
-        for line in content.lines() {
-            if !has_title {
-                if let Some(caps) = title_pattern.captures(line) {
-                    meta_data.insert(
-                        MetaDataType::Title,
-                        caps.name("title").unwrap().as_str().to_string(),
-                    );
-                    has_title = true;
-                }
-            }
-
-            if let Some(caps) = meta_pattern.captures(line) {
-                let name = caps.name("metaname").unwrap().as_str();
-                let name = name.to_lowercase();
-                let key = name.as_str();
-                let mut text = caps.name("metatext").unwrap().as_str();
-
-                if mail_pattern.is_match(text) {
-                    debug!("Mail detected. Extracting name.");
-                    let mail_caps = mail_pattern.captures(text).unwrap();
-                    text = mail_caps.name("name").unwrap().as_str();
-                    debug!("Name extracted: {:?}", text);
-                }
-
-                meta_data.insert(MetaDataType::from_str(key).unwrap(), text.to_string());
-            }
-        }
-
-        let default = "unphased".to_string();
-
-        let phase = meta_data
-            .get(&MetaDataType::Phase)
-            .unwrap_or(&default)
-            .clone();
-
-        match phase_pattern.captures(&phase) {
-            Some(caps) => {
-                let p = match caps.get(1) {
-                    Some(m) => m.as_str(),
-                    _ => "unphased",
-                };
-
-                let plusfigures = match caps.get(2) {
-                    Some(m) => m.as_str(),
-                    _ => "",
-                };
-
-                meta_data.insert(MetaDataType::Phase, p.to_string());
-                meta_data.insert(MetaDataType::Plusfigures, plusfigures.to_string());
-            }
-            _ => {
-                meta_data.insert(MetaDataType::Phase, "unphased".to_owned());
-                meta_data.insert(MetaDataType::Plusfigures, "".to_owned());
-            }
-        }
-    }
     index_file.set_content(&content);
 
-    if index_file.metadata_file().exists() {
-        process_metadata_file(&index_file.metadata_file(), index_file.metadata());
-    } else {
-        write_metadata_file(&index_file);
+    if !has_metadata_file {
+        write_metadata_file(&index_file)?;
     }
 
-    index_file
+    Ok(index_file)
 }
 
-fn write_metadata_file(file: &IndexFileData) {
-    let unphased = "unphased".to_string();
-    let unknown = "unknown".to_string();
-    let empty = "".to_string();
+fn write_metadata_file(file: &IndexFileData) -> io::Result<()> {
+    let metadata = file
+        .meta
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect();
 
-    let choreographer = file
-        .get_meta(MetaDataType::Choreographer)
-        .unwrap_or(&unknown);
-    let phase = file.get_meta(MetaDataType::Phase).unwrap_or(&unphased);
-    let difficulty = file.get_meta(MetaDataType::Difficulty).unwrap_or(&empty);
-    let rhythm = file.get_meta(MetaDataType::Rhythm).unwrap_or(&unknown);
-    let plusfigures = file.get_meta(MetaDataType::Plusfigures).unwrap_or(&empty);
-    let steplevel = file.get_meta(MetaDataType::Steplevel).unwrap_or(&empty);
-    let music = file.get_meta(MetaDataType::Music).unwrap_or(&empty);
-    let music_file = file.get_meta(MetaDataType::MusicFile).unwrap_or(&empty);
+    std::fs::write(file.metadata_file(), metadata_file_content(&metadata))
+}
+
+/// Returns the content of the metadata file of a cue card with `metadata`, keyed like the
+/// result of `file_metadata`.
+pub fn metadata_file_content(metadata: &HashMap<String, String>) -> String {
+    let get = |key: &str, default: &str| {
+        metadata
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| default.to_string())
+    };
 
     let metadata = MetaData {
-        choreographer: choreographer.to_string(),
-        phase: phase.to_string(),
-        difficulty: Some(difficulty.to_string()),
-        rhythm: rhythm.to_string(),
-        plusfigures: Some(plusfigures.to_string()),
-        steplevel: Some(steplevel.to_string()),
-        music: Some(music.to_string()),
-        music_file: Some(music_file.to_string()),
+        choreographer: get("choreographer", "unknown"),
+        phase: get("phase", "unphased"),
+        difficulty: Some(get("difficulty", "")),
+        rhythm: get("rhythm", "unknown"),
+        plusfigures: Some(get("plusfigures", "")),
+        steplevel: Some(get("steplevel", "")),
+        music: Some(get("music", "")),
+        music_file: Some(get("music_file", "")),
     };
 
-    std::fs::write(
-        file.metadata_file(),
-        serde_json::to_string_pretty(&metadata).unwrap(),
-    )
-    .unwrap();
+    serde_json::to_string_pretty(&metadata).unwrap()
 }
 
-fn process_metadata_file(filepath: &PathBuf, data: &mut HashMap<MetaDataType, String>) {
-    if let Ok(metadata) =
-        serde_json::from_str::<MetaData>(&std::fs::read_to_string(filepath).unwrap())
-    {
-        data.insert(MetaDataType::Choreographer, metadata.choreographer);
-        data.insert(MetaDataType::Phase, metadata.phase);
-        data.insert(
-            MetaDataType::Difficulty,
-            metadata.difficulty.unwrap_or_default(),
-        );
-        data.insert(MetaDataType::Rhythm, metadata.rhythm);
-        data.insert(
-            MetaDataType::Plusfigures,
-            metadata.plusfigures.unwrap_or_default(),
-        );
-        data.insert(
-            MetaDataType::Steplevel,
-            metadata.steplevel.unwrap_or_default(),
-        );
-        data.insert(MetaDataType::Music, metadata.music.unwrap_or_default());
-        data.insert(
-            MetaDataType::MusicFile,
-            metadata.music_file.unwrap_or_default(),
-        );
-    }
+fn process_metadata_file(
+    filepath: &Path,
+    data: &mut HashMap<MetaDataType, String>,
+) -> io::Result<()> {
+    let metadata = serde_json::from_str::<MetaData>(&std::fs::read_to_string(filepath)?)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    data.insert(MetaDataType::Choreographer, metadata.choreographer);
+    data.insert(MetaDataType::Phase, metadata.phase);
+    data.insert(
+        MetaDataType::Difficulty,
+        metadata.difficulty.unwrap_or_default(),
+    );
+    data.insert(MetaDataType::Rhythm, metadata.rhythm);
+    data.insert(
+        MetaDataType::Plusfigures,
+        metadata.plusfigures.unwrap_or_default(),
+    );
+    data.insert(
+        MetaDataType::Steplevel,
+        metadata.steplevel.unwrap_or_default(),
+    );
+    data.insert(MetaDataType::Music, metadata.music.unwrap_or_default());
+    data.insert(
+        MetaDataType::MusicFile,
+        metadata.music_file.unwrap_or_default(),
+    );
+
+    Ok(())
 }
 
 fn index(connection: &SqliteConnection, file: &IndexFileData) {
@@ -354,6 +403,18 @@ fn index(connection: &SqliteConnection, file: &IndexFileData) {
     std::fs::write(index_file, u.to_hyphenated().to_string()).unwrap();
 }
 
+/// Returns whether the serialized metadata of a cue card differs from `data`. The order of the
+/// serialized entries is not fixed, so they are compared as maps.
+fn meta_changed(meta: &str, data: &HashMap<&String, &String>) -> bool {
+    match serde_json::from_str::<HashMap<String, String>>(meta) {
+        Ok(previous) => {
+            previous.len() != data.len()
+                || data.iter().any(|(key, value)| previous.get(*key) != Some(*value))
+        }
+        Err(_) => true,
+    }
+}
+
 fn update(connection: &SqliteConnection, file: &IndexFileData, cuecard: &Cuecard) {
     let unphased = "unphased".to_string();
     let unknown = "unknown".to_string();
@@ -387,6 +448,22 @@ fn update(connection: &SqliteConnection, file: &IndexFileData, cuecard: &Cuecard
 
     let time = Utc::now();
 
+    // the replaced version is kept, unless only the modification time of the file changed
+    if cuecard.content != file.content || meta_changed(&cuecard.meta, &data) {
+        let revision_uuid = Uuid::new_v4().to_string();
+        let date_created = time.format("%FT%T%.3fZ").to_string();
+        let revision = CuecardRevisionData::of(
+            cuecard,
+            &revision_uuid,
+            REVISION_SOURCE_INDEXER,
+            &date_created,
+        );
+
+        if let Err(err) = revision.create(connection) {
+            error!("Saving revision failed with error: {:?}", err);
+        }
+    }
+
     let mut karaoke_marks = "";
 
     if !cuecard.karaoke_marks.is_empty() {
@@ -518,8 +595,12 @@ fn get_index_files_list(basepath: &str, min_depth: usize) -> Vec<IndexFileData>
 
     for entry in walkdir {
         debug!("{}", entry.path().display());
-        let indexfile = process(entry, basepath);
-        files.push(indexfile);
+        let path = entry.path().to_path_buf();
+
+        match process(entry, basepath) {
+            Ok(indexfile) => files.push(indexfile),
+            Err(err) => error!("Error reading cuecard {:?}: {:?}", path, err),
+        }
     }
 
     files
@@ -565,4 +646,50 @@ pub fn run(config: &Config) {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_metadata() {
+        let content = "# Moon River\n\n\
+                       * **Choreographer**: [Jane Doe](mailto:jane@example.com)\n\
+                       * **Phase**: IV+1 (Hover)\n\
+                       * **Footwork**: Opposite\n\n\
+                       # Intro\n";
+
+        let metadata = content_metadata(content);
+
+        assert_eq!(metadata["title"], "Moon River");
+        assert_eq!(metadata["choreographer"], "Jane Doe");
+        assert_eq!(metadata["phase"], "IV");
+        assert_eq!(metadata["plusfigures"], "+1 (Hover)");
+        assert_eq!(metadata["footwork"], "Opposite");
+        assert!(!metadata.contains_key("rhythm"));
+    }
+
+    #[test]
+    fn test_file_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("moon-river.md");
+        let content = "# Moon River\n\n* **Phase**: IV+1\n* **Rhythm**: Waltz\n";
+
+        assert_eq!(file_metadata(&path, content).unwrap()["rhythm"], "Waltz");
+
+        std::fs::write(
+            metadata_file_path(&path),
+            r#"{"choreographer": "Jane Doe", "phase": "V", "rhythm": "Foxtrot"}"#,
+        )
+        .unwrap();
+
+        let metadata = file_metadata(&path, content).unwrap();
+
+        assert_eq!(metadata["title"], "Moon River");
+        assert_eq!(metadata["choreographer"], "Jane Doe");
+        assert_eq!(metadata["phase"], "V");
+        assert_eq!(metadata["rhythm"], "Foxtrot");
+        assert_eq!(metadata["plusfigures"], "");
+
+        std::fs::write(metadata_file_path(&path), "{").unwrap();
+        assert!(file_metadata(&path, content).is_err());
+    }
+}
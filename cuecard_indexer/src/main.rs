@@ -3,6 +3,7 @@ extern crate cuecard_indexer;
 extern crate env_logger;
 extern crate structopt;
 
+use cuecard_indexer::placement::{PlacementTemplate, DEFAULT_TEMPLATE};
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -19,6 +20,32 @@ struct ProgramOptions {
     #[structopt(parse(from_os_str))]
     /// Sets the base directory for the cue card collection
     input: PathBuf,
+
+    #[structopt(short, long, default_value = DEFAULT_TEMPLATE)]
+    /// Sets the path of imported cue cards in the collection, like
+    /// `{rhythm}/{phase}/{title} - {choreographer}.md`
+    placement: String,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Converts the ODT, DOCX and PDF cue sheets of a zip archive or a directory into the
+    /// collection before indexing it
+    Import {
+        #[structopt(parse(from_os_str))]
+        /// Sets the zip archive or directory with the cue sheets
+        source: PathBuf,
+    },
+    /// Moves the cue cards of the collection to the paths of the placement template before
+    /// indexing it, the cue cards keep their UUID
+    Reorganize {
+        #[structopt(long)]
+        /// Only prints the moves without moving any file
+        dry_run: bool,
+    },
 }
 
 fn main() {
@@ -39,5 +66,58 @@ fn main() {
         database_url,
     };
 
+    let template = match PlacementTemplate::new(&options.placement) {
+        Ok(template) => template,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(Command::Import { source }) = &options.command {
+        let connection = cuer_database::establish_connection(&config.database_url);
+        let cuecard_paths = match cuecard_indexer::placement::cuecard_paths(&connection) {
+            Ok(paths) => paths,
+            Err(err) => {
+                eprintln!("Unable to read the cue cards of the database: {}", err);
+                std::process::exit(1);
+            }
+        };
+        let input = &options.input;
+        let result = match cuecard_indexer::import::import(source, input, &template, &cuecard_paths)
+        {
+            Ok(result) => result,
+            Err(err) => {
+                eprintln!("Unable to read {:?}: {}", source, err);
+                std::process::exit(1);
+            }
+        };
+
+        for file in result {
+            println!(
+                "{:?}\t{}\t{}\t{}",
+                file.status,
+                file.source,
+                file.target_path.unwrap_or_default(),
+                file.message.unwrap_or_default()
+            );
+        }
+    }
+
+    if let Some(Command::Reorganize { dry_run }) = options.command {
+        for relocation in cuecard_indexer::placement::reorganize(&config, &template, dry_run) {
+            println!(
+                "{}\t{}\t{}",
+                relocation.source,
+                relocation.target,
+                relocation.error.unwrap_or_default()
+            );
+        }
+
+        if dry_run {
+            return;
+        }
+    }
+
     cuecard_indexer::run(&config);
 }
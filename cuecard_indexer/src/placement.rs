@@ -0,0 +1,469 @@
+/**
+
+Placement of cue cards in the collection.
+
+The path of a cue card relative to the collection is built from a template like
+`{rhythm}/{phase}/{title} - {choreographer}.md`. Placeholders are replaced by the metadata of the
+cue card, `{filename}` by the name of the converted document without its extension and
+`{initial}` by the first letter of that name. The default template `{initial}/{filename}.md`
+places cue cards in the directory of the first letter of their file name.
+
+Values are sanitized, so they can't add directories or hidden files to the path, and paths that
+are already taken, by a file or by a cue card in the database, get a number, like
+`Moon River (2).md`. Existing cue cards can be moved to
+match a template, their index and metadata files are moved with them, so they keep their UUID.
+
+**/
+use crate::{file_metadata, index_file_path, is_allowed, metadata_file_path, Config};
+use cuer_database::establish_connection;
+use diesel::prelude::*;
+use walkdir::WalkDir;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_TEMPLATE: &str = "{initial}/{filename}.md";
+
+/// Maximum length of a value in a path, in characters
+const MAX_VALUE_LENGTH: usize = 100;
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Placeholder(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PlacementTemplate {
+    /// The segments of every directory and the file name
+    components: Vec<Vec<Segment>>,
+}
+
+impl Default for PlacementTemplate {
+    fn default() -> PlacementTemplate {
+        PlacementTemplate::new(DEFAULT_TEMPLATE).unwrap()
+    }
+}
+
+fn invalid_template(template: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("Invalid placement template {:?}: {}", template, reason),
+    )
+}
+
+/// Makes a value usable as a part of a file name. Separators and characters not allowed in file
+/// names are replaced, surrounding dots and whitespace are removed, so values can't form hidden
+/// files or `..`. Empty values become `unknown`.
+pub fn sanitize(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .take(MAX_VALUE_LENGTH)
+        .collect();
+    let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    let value = value.trim_matches(|c: char| c == '.' || c.is_whitespace());
+
+    if value.is_empty() {
+        "unknown".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+impl PlacementTemplate {
+    /// Parses a template. The template has to be relative and place cue cards in a directory,
+    /// as the indexer ignores files at the top of the collection. The extension `md` is added
+    /// if the template doesn't end with it.
+    pub fn new(template: &str) -> io::Result<PlacementTemplate> {
+        let trimmed = template.trim();
+
+        if trimmed.starts_with('/') || trimmed.starts_with('\\') {
+            return Err(invalid_template(template, "the path has to be relative"));
+        }
+
+        let mut components = Vec::new();
+
+        for component in trimmed.split('/') {
+            if component.trim().is_empty() || component == "." || component == ".." {
+                return Err(invalid_template(template, "empty or relative directory"));
+            }
+
+            let mut segments = Vec::new();
+            let mut rest = component;
+
+            while let Some(start) = rest.find('{') {
+                let end = match rest[start..].find('}') {
+                    Some(end) => start + end,
+                    None => return Err(invalid_template(template, "unclosed placeholder")),
+                };
+                let name = rest[start + 1..end].trim().to_lowercase();
+
+                if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    return Err(invalid_template(template, "invalid placeholder"));
+                }
+                if start > 0 {
+                    segments.push(Segment::Text(rest[..start].to_string()));
+                }
+                segments.push(Segment::Placeholder(name));
+                rest = &rest[end + 1..];
+            }
+
+            if rest.contains('}') {
+                return Err(invalid_template(template, "unopened placeholder"));
+            }
+            if !rest.is_empty() {
+                segments.push(Segment::Text(rest.to_string()));
+            }
+
+            components.push(segments);
+        }
+
+        if components.len() < 2 {
+            return Err(invalid_template(
+                template,
+                "cue cards have to be placed in a directory",
+            ));
+        }
+
+        if let Some(Segment::Text(text)) = components.last().and_then(|c| c.last()) {
+            if text.to_lowercase().ends_with(".md") {
+                let text = text[..text.len() - 3].to_string();
+                let segments = components.last_mut().unwrap();
+
+                segments.pop();
+                if !text.is_empty() {
+                    segments.push(Segment::Text(text));
+                }
+            }
+        }
+
+        Ok(PlacementTemplate { components })
+    }
+
+    /// Returns the path of a cue card relative to the collection. `metadata` contains the
+    /// metadata as read by the indexer and `filename` the name of the converted document.
+    /// Metadata missing in the cue card is `unknown`.
+    pub fn path(&self, metadata: &HashMap<String, String>, filename: &str) -> PathBuf {
+        let filename = Path::new(filename)
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let filename = sanitize(filename);
+        let initial = filename.to_lowercase().chars().take(1).collect::<String>();
+
+        let value = |name: &str| match name {
+            "filename" => filename.clone(),
+            "initial" => initial.clone(),
+            name => sanitize(metadata.get(name).map_or("", String::as_str)),
+        };
+
+        let mut path = PathBuf::new();
+
+        for segments in &self.components {
+            let component: String = segments
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Text(text) => text.clone(),
+                    Segment::Placeholder(name) => value(name),
+                })
+                .collect();
+
+            path.push(sanitize(&component));
+        }
+
+        // the extension is added, as values like `2.5` would be taken for an extension
+        let filename = format!("{}.md", path.file_name().unwrap().to_string_lossy());
+        path.set_file_name(filename);
+
+        path
+    }
+}
+
+/// Returns the paths of the cue cards in the database, which are taken even if their files are
+/// missing or have been moved.
+pub fn cuecard_paths(conn: &SqliteConnection) -> QueryResult<HashSet<PathBuf>> {
+    use cuer_database::schema::cuecards::dsl;
+
+    let paths = dsl::cuecards.select(dsl::file_path).load::<String>(conn)?;
+
+    Ok(paths.into_iter().map(PathBuf::from).collect())
+}
+
+/// Returns `path` or, if a file already exists at that path in the collection at `basepath` or
+/// it is one of `cuecard_paths`, the path with the first free number added to the file name.
+/// `current` is the path of the cue card being placed, which doesn't collide with itself.
+pub fn unique_path(
+    basepath: &Path,
+    path: &Path,
+    current: Option<&Path>,
+    cuecard_paths: &HashSet<PathBuf>,
+) -> PathBuf {
+    let is_free = |candidate: &Path| {
+        current == Some(candidate)
+            || !(basepath.join(candidate).exists() || cuecard_paths.contains(candidate))
+    };
+
+    if is_free(path) {
+        return path.to_path_buf();
+    }
+
+    (2..)
+        .map(|number| numbered_path(path, number))
+        .find(|candidate| is_free(candidate))
+        .unwrap()
+}
+
+/// Returns `path` with `number` added to the file name, like `unique_path` does.
+pub fn numbered_path(path: &Path, number: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!("{} ({}).md", stem, number))
+}
+
+/// A cue card moved to match a placement template
+#[derive(Debug)]
+pub struct Relocation {
+    /// Path of the cue card relative to the collection
+    pub source: String,
+    pub target: String,
+    pub error: Option<String>,
+}
+
+/// Moves a file if it exists.
+fn move_file(source: &Path, target: &Path) -> io::Result<()> {
+    if source.exists() {
+        fs::rename(source, target)?;
+    }
+
+    Ok(())
+}
+
+/// Moves a cue card with its index and metadata files and updates its path in the database.
+fn relocate(
+    basepath: &Path,
+    source: &Path,
+    target: &Path,
+    connection: &SqliteConnection,
+) -> io::Result<()> {
+    use cuer_database::schema::cuecards::dsl;
+
+    let source_path = basepath.join(source);
+    let target_path = basepath.join(target);
+    let invalid_name = || io::Error::new(io::ErrorKind::InvalidInput, "Invalid file name");
+
+    if let Some(parent) = target_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let source_index = index_file_path(&source_path).ok_or_else(invalid_name)?;
+    let target_index = index_file_path(&target_path).ok_or_else(invalid_name)?;
+    let uuid = fs::read_to_string(&source_index).ok();
+
+    fs::rename(&source_path, &target_path)?;
+    move_file(&source_index, &target_index)?;
+    move_file(
+        &metadata_file_path(&source_path),
+        &metadata_file_path(&target_path),
+    )?;
+
+    if let Some(uuid) = uuid {
+        diesel::update(dsl::cuecards.filter(dsl::uuid.eq(uuid.trim())))
+            .set(dsl::file_path.eq(target.to_string_lossy()))
+            .execute(connection)
+            .map_err(io::Error::other)?;
+    }
+
+    // directories emptied by the move are removed, up to the collection
+    let mut directory = source_path.parent();
+
+    while let Some(dir) = directory.filter(|dir| *dir != basepath) {
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+        directory = dir.parent();
+    }
+
+    Ok(())
+}
+
+/// Moves the cue cards of the collection to the paths of `template`. Cue cards keep their UUID,
+/// their index and metadata files are moved with them and their path in the database is
+/// updated. With `dry_run` the moves are only reported.
+pub fn reorganize(config: &Config, template: &PlacementTemplate, dry_run: bool) -> Vec<Relocation> {
+    let basepath = Path::new(&config.basepath);
+    let connection = establish_connection(&config.database_url);
+    let cuecard_paths = match cuecard_paths(&connection) {
+        Ok(paths) => paths,
+        Err(err) => {
+            error!("Unable to read the paths of the cue cards: {}", err);
+            return Vec::new();
+        }
+    };
+    let mut cuecards = Vec::new();
+
+    for entry in WalkDir::new(basepath)
+        .min_depth(2)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| is_allowed(&e.file_name().to_string_lossy().to_lowercase()))
+    {
+        if let Ok(source) = entry.path().strip_prefix(basepath) {
+            cuecards.push(source.to_path_buf());
+        }
+    }
+
+    let mut result = Vec::new();
+
+    for source in cuecards {
+        let path = basepath.join(&source);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) => {
+                error!("Unable to read {:?}: {}", path, err);
+                continue;
+            }
+        };
+        let metadata = match file_metadata(&path, &content) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                error!("Unable to read the metadata of {:?}: {}", path, err);
+                continue;
+            }
+        };
+        let filename = source
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let target = template.path(&metadata, &filename);
+        let target = unique_path(basepath, &target, Some(&source), &cuecard_paths);
+
+        if target == source {
+            continue;
+        }
+
+        let error = if dry_run {
+            None
+        } else {
+            relocate(basepath, &source, &target, &connection)
+                .err()
+                .map(|err| err.to_string())
+        };
+
+        match &error {
+            Some(err) => error!("Error moving {:?} to {:?}: {}", source, target, err),
+            None => info!("Moved {:?} to {:?}", source, target),
+        }
+
+        result.push(Relocation {
+            source: source.to_string_lossy().into_owned(),
+            target: target.to_string_lossy().into_owned(),
+            error,
+        });
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_template_path() {
+        let template = PlacementTemplate::default();
+
+        assert_eq!(
+            template.path(&HashMap::new(), "Moon River.odt"),
+            PathBuf::from("m/Moon River.md")
+        );
+        assert_eq!(
+            template.path(&HashMap::new(), ""),
+            PathBuf::from("u/unknown.md")
+        );
+
+        let template =
+            PlacementTemplate::new("{Rhythm}/{phase}/{title} - {choreographer}").unwrap();
+        let metadata = metadata(&[
+            ("title", "../Moon/River: Reprise"),
+            ("rhythm", "Waltz"),
+            ("phase", "IV"),
+            ("choreographer", ".hidden"),
+        ]);
+
+        assert_eq!(
+            template.path(&metadata, "moon.odt"),
+            PathBuf::from("Waltz/IV/_Moon_River_ Reprise - hidden.md")
+        );
+        assert_eq!(
+            template.path(&HashMap::new(), "moon.odt"),
+            PathBuf::from("unknown/unknown/unknown - unknown.md")
+        );
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        assert!(PlacementTemplate::new("{title}.md").is_err());
+        assert!(PlacementTemplate::new("/cuecards/{title}.md").is_err());
+        assert!(PlacementTemplate::new("../{title}.md").is_err());
+        assert!(PlacementTemplate::new("{rhythm}//{title}.md").is_err());
+        assert!(PlacementTemplate::new("{rhythm/{title}.md").is_err());
+        assert!(PlacementTemplate::new("{rhythm}/{}.md").is_err());
+        assert!(PlacementTemplate::new("{rhythm}}/{title}.md").is_err());
+    }
+
+    #[test]
+    fn test_unique_path() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("m")).unwrap();
+        fs::write(dir.path().join("m/Moon River.md"), "# Moon River").unwrap();
+        fs::write(dir.path().join("m/Moon River (2).md"), "# Moon River").unwrap();
+
+        let path = Path::new("m/Moon River.md");
+        let none = HashSet::new();
+
+        assert_eq!(
+            unique_path(dir.path(), path, None, &none),
+            PathBuf::from("m/Moon River (3).md")
+        );
+        assert_eq!(
+            unique_path(
+                dir.path(),
+                path,
+                Some(Path::new("m/Moon River (2).md")),
+                &none
+            ),
+            PathBuf::from("m/Moon River (2).md")
+        );
+        assert_eq!(
+            unique_path(dir.path(), Path::new("w/Waltz.md"), None, &none),
+            PathBuf::from("w/Waltz.md")
+        );
+
+        // the file of a cue card in the database may be missing
+        let cuecard_paths = vec![PathBuf::from("m/Moon River (3).md")]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            unique_path(dir.path(), path, None, &cuecard_paths),
+            PathBuf::from("m/Moon River (4).md")
+        );
+    }
+}
@@ -12,6 +12,12 @@ use diesel::expression::AsExpression;
 use diesel::prelude::*;
 use diesel::{delete, sql_query};
 
+/// Sources of cue card revisions, what replaced the version of the revision.
+pub const REVISION_SOURCE_EDITOR: &str = "editor";
+pub const REVISION_SOURCE_INDEXER: &str = "indexer";
+pub const REVISION_SOURCE_IMPORT: &str = "import";
+pub const REVISION_SOURCE_RESTORE: &str = "restore";
+
 pub fn establish_connection(database_url: &str) -> SqliteConnection {
     SqliteConnection::establish(&database_url)
         .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
@@ -29,11 +35,23 @@ pub fn tip_by_uuid(u: &str, connection: &SqliteConnection) -> QueryResult<Tip> {
     tips.filter(uuid.eq(u)).first::<Tip>(connection)
 }
 
+/// Sets whether SQLite enforces foreign keys, which is off for every new connection.
+///
+/// This has no effect inside of a transaction.
+pub fn set_foreign_keys(enabled: bool, connection: &SqliteConnection) -> QueryResult<()> {
+    let pragma = if enabled {
+        "PRAGMA foreign_keys = ON"
+    } else {
+        "PRAGMA foreign_keys = OFF"
+    };
+
+    sql_query(pragma).execute(connection).map(|_| ())
+}
+
+/// Deletes a tip. Its cue cards are removed by the foreign keys, which have to be enabled.
 pub fn tip_delete(t: &Tip, connection: &SqliteConnection) -> QueryResult<usize> {
     use crate::schema::tips::dsl::*;
 
-    sql_query("PRAGMA foreign_keys = 1").execute(connection)?;
-
     delete(tips.filter(id.eq(t.id))).execute(connection)
 }
 
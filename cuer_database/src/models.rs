@@ -1,7 +1,12 @@
 #![allow(proc_macro_derive_resolution_fallback)]
+use super::schema::cuecard_revisions;
 use super::schema::cuecard_tags;
 use super::schema::cuecards;
+use super::schema::cues;
+use super::schema::event_series;
+use super::schema::event_tags;
 use super::schema::events;
+use super::schema::program_templates;
 use super::schema::programs;
 use super::schema::tags;
 use super::schema::tip_cuecards;
@@ -55,6 +60,22 @@ impl<'a> CuecardData<'a> {
         update(card).set(self).execute(conn)
     }
 
+    /// Updates the cuecard only if it wasn't modified since `card` was read, returns the number
+    /// of updated rows.
+    pub fn update_if_unchanged(
+        &self,
+        card: &Cuecard,
+        conn: &SqliteConnection,
+    ) -> QueryResult<usize> {
+        use crate::schema::cuecards::dsl;
+
+        let version = dsl::date_modified.eq(&card.date_modified);
+
+        update(dsl::cuecards.find(card.id).filter(version))
+            .set(self)
+            .execute(conn)
+    }
+
     /// Inserts the cuecard into the database, or updates an existing one.
     pub fn create(&self, conn: &SqliteConnection) -> QueryResult<usize> {
         use crate::schema::cuecards::dsl::*;
@@ -63,6 +84,79 @@ impl<'a> CuecardData<'a> {
     }
 }
 
+/// A previous version of the content and metadata of a cue card.
+#[derive(
+    Clone, Queryable, Identifiable, Associations, QueryableByName, Debug, Serialize, Deserialize,
+)]
+#[table_name = "cuecard_revisions"]
+#[belongs_to(Cuecard)]
+pub struct CuecardRevision {
+    pub id: i32,
+    pub uuid: String,
+    pub cuecard_id: i32,
+    pub phase: String,
+    pub rhythm: String,
+    pub title: String,
+    pub steplevel: String,
+    pub difficulty: String,
+    pub choreographer: String,
+    pub meta: String,
+    pub content: String,
+    /// What replaced the version, see the `REVISION_SOURCE_*` constants
+    pub source: String,
+    pub date_created: String,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "cuecard_revisions"]
+pub struct CuecardRevisionData<'a> {
+    pub uuid: &'a str,
+    pub cuecard_id: i32,
+    pub phase: &'a str,
+    pub rhythm: &'a str,
+    pub title: &'a str,
+    pub steplevel: &'a str,
+    pub difficulty: &'a str,
+    pub choreographer: &'a str,
+    pub meta: &'a str,
+    pub content: &'a str,
+    pub source: &'a str,
+    pub date_created: &'a str,
+}
+
+impl<'a> CuecardRevisionData<'a> {
+    /// Returns the current version of a cue card as a revision.
+    pub fn of(
+        cuecard: &'a Cuecard,
+        uuid: &'a str,
+        source: &'a str,
+        date_created: &'a str,
+    ) -> CuecardRevisionData<'a> {
+        CuecardRevisionData {
+            uuid,
+            cuecard_id: cuecard.id,
+            phase: &cuecard.phase,
+            rhythm: &cuecard.rhythm,
+            title: &cuecard.title,
+            steplevel: &cuecard.steplevel,
+            difficulty: &cuecard.difficulty,
+            choreographer: &cuecard.choreographer,
+            meta: &cuecard.meta,
+            content: &cuecard.content,
+            source,
+            date_created,
+        }
+    }
+
+    pub fn create(&self, conn: &SqliteConnection) -> QueryResult<CuecardRevision> {
+        use crate::schema::cuecard_revisions::dsl::*;
+
+        insert_into(cuecard_revisions).values(self).execute(conn)?;
+
+        cuecard_revisions.filter(uuid.eq(self.uuid)).get_result(conn)
+    }
+}
+
 #[derive(Queryable, Debug, Serialize, Deserialize)]
 pub struct Cardindex {
     pub rowid: i32,
@@ -84,6 +178,8 @@ pub struct Event {
     pub schedule: Option<String>,
     pub date_created: String,
     pub date_modified: String,
+    pub series_id: Option<i32>,
+    pub recurrence_id: Option<String>,
 }
 
 impl Event {
@@ -104,6 +200,8 @@ pub struct EventData<'a> {
     pub schedule: Option<&'a str>,
     pub date_created: &'a str,
     pub date_modified: &'a str,
+    pub series_id: Option<i32>,
+    pub recurrence_id: Option<&'a str>,
 }
 
 impl<'a> EventData<'a> {
@@ -123,6 +221,61 @@ impl<'a> EventData<'a> {
     }
 }
 
+#[derive(Clone, Queryable, Identifiable, QueryableByName, Debug, Serialize, Deserialize)]
+#[table_name = "event_series"]
+pub struct EventSeries {
+    pub id: i32,
+    pub uuid: String,
+    pub name: String,
+    pub date_start: String,
+    pub date_end: String,
+    pub rrule: String,
+    pub exceptions: String,
+    pub date_created: String,
+    pub date_modified: String,
+}
+
+impl EventSeries {
+    pub fn delete(&self, conn: &SqliteConnection) -> QueryResult<usize> {
+        use crate::schema::event_series::dsl::*;
+
+        delete(event_series.filter(id.eq(self.id))).execute(conn)
+    }
+}
+
+#[derive(Insertable, AsChangeset, Debug)]
+#[table_name = "event_series"]
+pub struct EventSeriesData<'a> {
+    pub uuid: &'a str,
+    pub name: &'a str,
+    pub date_start: &'a str,
+    pub date_end: &'a str,
+    pub rrule: &'a str,
+    pub exceptions: &'a str,
+    pub date_created: &'a str,
+    pub date_modified: &'a str,
+}
+
+impl<'a> EventSeriesData<'a> {
+    pub fn update(&self, conn: &SqliteConnection) -> QueryResult<EventSeries> {
+        use crate::schema::event_series::dsl::*;
+
+        update(event_series.filter(uuid.eq(self.uuid)))
+            .set(self)
+            .execute(conn)?;
+
+        event_series.filter(uuid.eq(self.uuid)).get_result(conn)
+    }
+
+    pub fn create(&self, conn: &SqliteConnection) -> QueryResult<EventSeries> {
+        use crate::schema::event_series::dsl::*;
+
+        insert_into(event_series).values(self).execute(conn)?;
+
+        event_series.filter(uuid.eq(self.uuid)).get_result(conn)
+    }
+}
+
 #[derive(Clone, Queryable, Identifiable, QueryableByName, Debug, Serialize, Deserialize)]
 #[table_name = "programs"]
 pub struct Program {
@@ -156,6 +309,22 @@ impl<'a> ProgramData<'a> {
         programs.filter(uuid.eq(self.uuid)).get_result(conn)
     }
 
+    /// Updates the program only if it wasn't modified since `program` was read, returns the
+    /// number of updated rows.
+    pub fn update_if_unchanged(
+        &self,
+        program: &Program,
+        conn: &SqliteConnection,
+    ) -> QueryResult<usize> {
+        use crate::schema::programs::dsl;
+
+        let version = dsl::date_modified.eq(&program.date_modified);
+
+        update(dsl::programs.find(program.id).filter(version))
+            .set(self)
+            .execute(conn)
+    }
+
     pub fn create(&self, conn: &SqliteConnection) -> QueryResult<Program> {
         use crate::schema::programs::dsl::*;
 
@@ -165,6 +334,47 @@ impl<'a> ProgramData<'a> {
     }
 }
 
+#[derive(Clone, Queryable, Identifiable, QueryableByName, Debug, Serialize, Deserialize)]
+#[table_name = "program_templates"]
+pub struct ProgramTemplate {
+    pub id: i32,
+    pub uuid: String,
+    pub name: String,
+    pub notes: Option<String>,
+    pub tips: String,
+    pub date_created: String,
+    pub date_modified: String,
+}
+
+impl ProgramTemplate {
+    pub fn delete(&self, conn: &SqliteConnection) -> QueryResult<usize> {
+        use crate::schema::program_templates::dsl::*;
+
+        delete(program_templates.filter(id.eq(self.id))).execute(conn)
+    }
+}
+
+#[derive(Insertable, AsChangeset, Debug)]
+#[table_name = "program_templates"]
+pub struct ProgramTemplateData<'a> {
+    pub uuid: &'a str,
+    pub name: &'a str,
+    pub notes: Option<&'a str>,
+    pub tips: &'a str,
+    pub date_created: &'a str,
+    pub date_modified: &'a str,
+}
+
+impl<'a> ProgramTemplateData<'a> {
+    pub fn create(&self, conn: &SqliteConnection) -> QueryResult<ProgramTemplate> {
+        use crate::schema::program_templates::dsl::*;
+
+        insert_into(program_templates).values(self).execute(conn)?;
+
+        program_templates.filter(uuid.eq(self.uuid)).get_result(conn)
+    }
+}
+
 #[derive(Clone, Queryable, Identifiable, QueryableByName, Debug, Serialize, Deserialize)]
 #[table_name = "tips"]
 pub struct Tip {
@@ -174,6 +384,9 @@ pub struct Tip {
     pub program_id: i32,
     pub date_start: String,
     pub date_end: String,
+    /// Changed by the database whenever the tip or its cue cards change
+    #[serde(default)]
+    pub date_modified: String,
 }
 
 #[derive(Insertable, AsChangeset, Debug)]
@@ -304,3 +517,70 @@ impl CuecardTagData {
             .execute(conn)
     }
 }
+
+#[derive(Clone, Queryable, Identifiable, QueryableByName, Debug, Serialize, Deserialize)]
+#[table_name = "event_tags"]
+pub struct EventTag {
+    pub id: i32,
+    pub event_id: i32,
+    pub tag_id: i32,
+}
+
+#[derive(Insertable, AsChangeset, Debug)]
+#[table_name = "event_tags"]
+pub struct EventTagData {
+    pub event_id: i32,
+    pub tag_id: i32,
+}
+
+impl EventTagData {
+    pub fn create(&self, conn: &SqliteConnection) -> QueryResult<usize> {
+        use crate::schema::event_tags::dsl::*;
+
+        insert_into(event_tags).values(self).execute(conn)
+    }
+
+    pub fn delete(&self, conn: &SqliteConnection) -> QueryResult<usize> {
+        use crate::schema::event_tags::dsl::*;
+
+        delete(event_tags)
+            .filter(event_id.eq(self.event_id))
+            .filter(tag_id.eq(self.tag_id))
+            .execute(conn)
+    }
+}
+
+#[derive(Clone, Queryable, Identifiable, QueryableByName, Debug, Serialize, Deserialize)]
+#[table_name = "cues"]
+pub struct Cue {
+    pub id: i32,
+    pub uuid: String,
+    pub cuecard_id: i32,
+    pub event_id: Option<i32>,
+    pub tip_id: Option<i32>,
+    pub source: String,
+    pub started_at: String,
+    pub stopped_at: Option<String>,
+}
+
+#[derive(Insertable, AsChangeset, Debug)]
+#[table_name = "cues"]
+pub struct CueData<'a> {
+    pub uuid: &'a str,
+    pub cuecard_id: i32,
+    pub event_id: Option<i32>,
+    pub tip_id: Option<i32>,
+    pub source: &'a str,
+    pub started_at: &'a str,
+    pub stopped_at: Option<&'a str>,
+}
+
+impl<'a> CueData<'a> {
+    pub fn create(&self, conn: &SqliteConnection) -> QueryResult<Cue> {
+        use crate::schema::cues::dsl::*;
+
+        insert_into(cues).values(self).execute(conn)?;
+
+        cues.filter(uuid.eq(self.uuid)).get_result(conn)
+    }
+}
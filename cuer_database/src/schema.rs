@@ -48,6 +48,24 @@ table! {
     }
 }
 
+table! {
+    cuecard_revisions (id) {
+        id -> Integer,
+        uuid -> Text,
+        cuecard_id -> Integer,
+        phase -> Text,
+        rhythm -> Text,
+        title -> Text,
+        steplevel -> Text,
+        difficulty -> Text,
+        choreographer -> Text,
+        meta -> Text,
+        content -> Text,
+        source -> Text,
+        date_created -> Text,
+    }
+}
+
 table! {
     cuecards (id) {
         id -> Integer,
@@ -68,6 +86,19 @@ table! {
     }
 }
 
+table! {
+    cues (id) {
+        id -> Integer,
+        uuid -> Text,
+        cuecard_id -> Integer,
+        event_id -> Nullable<Integer>,
+        tip_id -> Nullable<Integer>,
+        source -> Text,
+        started_at -> Text,
+        stopped_at -> Nullable<Text>,
+    }
+}
+
 table! {
     event_tags (id) {
         id -> Integer,
@@ -86,6 +117,22 @@ table! {
         schedule -> Nullable<Text>,
         date_created -> Text,
         date_modified -> Text,
+        series_id -> Nullable<Integer>,
+        recurrence_id -> Nullable<Text>,
+    }
+}
+
+table! {
+    event_series (id) {
+        id -> Integer,
+        uuid -> Text,
+        name -> Text,
+        date_start -> Text,
+        date_end -> Text,
+        rrule -> Text,
+        exceptions -> Text,
+        date_created -> Text,
+        date_modified -> Text,
     }
 }
 
@@ -116,6 +163,18 @@ table! {
     }
 }
 
+table! {
+    program_templates (id) {
+        id -> Integer,
+        uuid -> Text,
+        name -> Text,
+        notes -> Nullable<Text>,
+        tips -> Text,
+        date_created -> Text,
+        date_modified -> Text,
+    }
+}
+
 table! {
     tags (id) {
         id -> Integer,
@@ -141,6 +200,7 @@ table! {
         program_id -> Integer,
         date_start -> Text,
         date_end -> Text,
+        date_modified -> Text,
     }
 }
 
@@ -164,8 +224,13 @@ joinable!(tip_cuecards -> tips(tip_id));
 joinable!(tip_cuecards -> cuecards(cuecard_id));
 joinable!(event_tags -> tags(tag_id));
 joinable!(event_tags -> events(event_id));
+joinable!(events -> event_series(series_id));
 joinable!(cuecard_tags -> tags(tag_id));
 joinable!(cuecard_tags -> cuecards(cuecard_id));
+joinable!(cues -> cuecards(cuecard_id));
+joinable!(cuecard_revisions -> cuecards(cuecard_id));
+joinable!(cues -> events(event_id));
+joinable!(cues -> tips(tip_id));
 
 allow_tables_to_appear_in_same_query!(
     cardindex_content,
@@ -173,12 +238,16 @@ allow_tables_to_appear_in_same_query!(
     cardindex_segdir,
     cardindex_segments,
     cardindex_stat,
+    cuecard_revisions,
     cuecard_tags,
     cuecards,
+    cues,
+    event_series,
     event_tags,
     events,
     playlist_cuecards,
     playlists,
+    program_templates,
     programs,
     tags,
     tip_cuecards,
@@ -0,0 +1,395 @@
+/**
+
+Streaming of music files for the audio player.
+
+Browsers need byte range support (`Range` requests answered with `206 Partial Content`)
+to seek within a track, and a proper content type to decode it.
+
+**/
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::warn;
+
+use rocket::http::{ContentType, Status};
+use rocket::request::Request;
+use rocket::response::{self, Body, Responder, Response};
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CodecParameters, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Returns the content type for a music file based on its extension.
+pub fn content_type(path: &Path) -> ContentType {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    match extension.as_ref() {
+        "mp3" => ContentType::new("audio", "mpeg"),
+        "ogg" | "oga" | "opus" => ContentType::new("audio", "ogg"),
+        "flac" => ContentType::new("audio", "flac"),
+        "m4a" | "mp4" | "aac" => ContentType::new("audio", "mp4"),
+        "wav" => ContentType::new("audio", "wav"),
+        _ => ContentType::Binary,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    /// No usable range was requested, the whole file is sent.
+    Full,
+    /// Inclusive start and end offsets of the requested part.
+    Partial(u64, u64),
+    /// The requested range lies outside of the file.
+    Unsatisfiable,
+}
+
+/// Parses the value of a `Range` header for a file of `length` bytes.
+///
+/// Only single ranges are supported. Multiple ranges or malformed headers are ignored and
+/// the whole file is sent, as permitted by RFC 7233.
+pub fn parse_range(header: Option<&str>, length: u64) -> ByteRange {
+    let spec = match header.map(str::trim) {
+        Some(h) if h.starts_with("bytes=") => &h[6..],
+        _ => return ByteRange::Full,
+    };
+
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+
+    let mut parts = spec.splitn(2, '-');
+    let (start, end) = match (parts.next(), parts.next()) {
+        (Some(start), Some(end)) => (start.trim(), end.trim()),
+        _ => return ByteRange::Full,
+    };
+
+    if start.is_empty() {
+        // suffix range: the last n bytes of the file
+        return match end.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if length == 0 => ByteRange::Unsatisfiable,
+            Ok(n) => ByteRange::Partial(length.saturating_sub(n), length - 1),
+            Err(_) => ByteRange::Full,
+        };
+    }
+
+    let start = match start.parse::<u64>() {
+        Ok(s) => s,
+        Err(_) => return ByteRange::Full,
+    };
+
+    let end = if end.is_empty() {
+        length.saturating_sub(1)
+    } else {
+        match end.parse::<u64>() {
+            Ok(e) if e < start => return ByteRange::Full,
+            Ok(e) => std::cmp::min(e, length.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        }
+    };
+
+    if start >= length {
+        return ByteRange::Unsatisfiable;
+    }
+
+    ByteRange::Partial(start, end)
+}
+
+/// Decoded PCM data of a music file.
+pub struct DecodedAudio {
+    pub sample_rate: u32,
+    pub channels: usize,
+    /// Interleaved samples of all channels
+    pub samples: Vec<f32>,
+}
+
+fn decode_error(err: DecodeError) -> io::Error {
+    match err {
+        DecodeError::IoError(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+/// Opens a music file and finds its first audio track.
+fn probe(path: &Path) -> io::Result<(Box<dyn FormatReader>, u32, CodecParameters)> {
+    let source = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(decode_error)?;
+    let format = probed.format;
+
+    let (track_id, params) = match format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+    {
+        Some(track) => (track.id, track.codec_params.clone()),
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no audio track found",
+            ))
+        }
+    };
+
+    Ok((format, track_id, params))
+}
+
+/// Returns the playing time of a music file in seconds.
+///
+/// Most files state their length in the header. For the others, the lengths of all packets
+/// are added up, which requires reading but not decoding the whole file.
+pub fn duration(path: &Path) -> io::Result<f64> {
+    let (mut format, track_id, params) = probe(path)?;
+
+    if let (Some(frames), Some(sample_rate)) = (params.n_frames, params.sample_rate) {
+        return Ok(frames as f64 / f64::from(sample_rate));
+    }
+
+    let time_base = match params.time_base {
+        Some(time_base) => time_base,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unknown time base",
+            ))
+        }
+    };
+
+    let mut timestamp = 0;
+
+    loop {
+        match format.next_packet() {
+            Ok(packet) if packet.track_id() == track_id => timestamp += packet.dur(),
+            Ok(_) => (),
+            Err(DecodeError::IoError(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(err) => return Err(decode_error(err)),
+        }
+    }
+
+    let time = time_base.calc_time(timestamp);
+    Ok(time.seconds as f64 + time.frac)
+}
+
+/// Durations of music files, kept as long as the files don't change.
+#[derive(Default)]
+pub struct DurationCache(Mutex<HashMap<PathBuf, (SystemTime, f64)>>);
+
+impl DurationCache {
+    pub fn duration(&self, path: &Path) -> io::Result<f64> {
+        let modified = path.metadata()?.modified()?;
+
+        if let Some((time, duration)) = self.0.lock().unwrap().get(path) {
+            if *time == modified {
+                return Ok(*duration);
+            }
+        }
+
+        // the lock isn't held while reading, so other requests aren't blocked by large files
+        let duration = duration(path)?;
+        self.0
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (modified, duration));
+
+        Ok(duration)
+    }
+}
+
+/// Decodes the first audio track of a music file into interleaved `f32` samples.
+pub fn decode(path: &Path) -> io::Result<DecodedAudio> {
+    let (mut format, track_id, params) = probe(path)?;
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&params, &DecoderOptions::default())
+        .map_err(decode_error)?;
+
+    let mut sample_rate = params.sample_rate.unwrap_or(0);
+    let mut channels = params.channels.map(|c| c.count()).unwrap_or(0);
+    let mut samples: Vec<f32> = Vec::new();
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(err) => return Err(decode_error(err)),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                sample_rate = spec.rate;
+                channels = spec.channels.count();
+
+                let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                buffer.copy_interleaved_ref(decoded);
+                samples.extend_from_slice(buffer.samples());
+            }
+            // corrupt packets are skipped, the rest of the track is still usable
+            Err(DecodeError::DecodeError(err)) => warn!("Skipping undecodable packet: {}", err),
+            Err(err) => return Err(decode_error(err)),
+        }
+    }
+
+    if sample_rate == 0 || channels == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unknown sample format",
+        ));
+    }
+
+    Ok(DecodedAudio {
+        sample_rate,
+        channels,
+        samples,
+    })
+}
+
+/// A music file which answers `Range`, `If-Range` and `If-None-Match` requests.
+pub struct AudioFile {
+    path: PathBuf,
+    file: File,
+    length: u64,
+    etag: String,
+}
+
+impl AudioFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<AudioFile> {
+        let file = File::open(path.as_ref())?;
+        let metadata = file.metadata()?;
+
+        if !metadata.is_file() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "not a file"));
+        }
+
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Ok(AudioFile {
+            path: path.as_ref().to_path_buf(),
+            file,
+            length: metadata.len(),
+            etag: format!("\"{:x}-{:x}\"", metadata.len(), modified),
+        })
+    }
+
+    fn matches_etag(&self, header: &str) -> bool {
+        header
+            .split(',')
+            .map(|t| t.trim().trim_start_matches("W/"))
+            .any(|t| t == "*" || t == self.etag)
+    }
+}
+
+impl<'r> Responder<'r> for AudioFile {
+    fn respond_to(mut self, req: &Request) -> response::Result<'r> {
+        let mut builder = Response::build();
+        builder
+            .header(content_type(&self.path))
+            .raw_header("Accept-Ranges", "bytes")
+            .raw_header("ETag", self.etag.clone())
+            .raw_header("Cache-Control", "no-cache");
+
+        if let Some(tags) = req.headers().get_one("If-None-Match") {
+            if self.matches_etag(tags) {
+                return builder.status(Status::NotModified).ok();
+            }
+        }
+
+        // A range request for an outdated version of the file gets the whole file.
+        let range_header = match req.headers().get_one("If-Range") {
+            Some(tag) if tag.trim() != self.etag => None,
+            _ => req.headers().get_one("Range"),
+        };
+
+        match parse_range(range_header, self.length) {
+            ByteRange::Full => builder
+                .status(Status::Ok)
+                .raw_body(Body::Sized(self.file, self.length))
+                .ok(),
+            ByteRange::Partial(start, end) => {
+                if self.file.seek(SeekFrom::Start(start)).is_err() {
+                    return Err(Status::InternalServerError);
+                }
+
+                let size = end - start + 1;
+
+                builder
+                    .status(Status::PartialContent)
+                    .raw_header(
+                        "Content-Range",
+                        format!("bytes {}-{}/{}", start, end, self.length),
+                    )
+                    .raw_body(Body::Sized(self.file.take(size), size))
+                    .ok()
+            }
+            ByteRange::Unsatisfiable => builder
+                .status(Status::RangeNotSatisfiable)
+                .raw_header("Content-Range", format!("bytes */{}", self.length))
+                .ok(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range(None, 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=0-"), 100), ByteRange::Partial(0, 99));
+        assert_eq!(parse_range(Some("bytes=10-19"), 100), ByteRange::Partial(10, 19));
+        assert_eq!(parse_range(Some("bytes=90-200"), 100), ByteRange::Partial(90, 99));
+        assert_eq!(parse_range(Some("bytes=-10"), 100), ByteRange::Partial(90, 99));
+        assert_eq!(parse_range(Some("bytes=-200"), 100), ByteRange::Partial(0, 99));
+        assert_eq!(parse_range(Some("bytes=100-"), 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("items=0-1"), 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=20-10"), 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type(Path::new("a/b.MP3")), ContentType::new("audio", "mpeg"));
+        assert_eq!(content_type(Path::new("b.ogg")), ContentType::new("audio", "ogg"));
+        assert_eq!(content_type(Path::new("b.flac")), ContentType::new("audio", "flac"));
+        assert_eq!(content_type(Path::new("b.m4a")), ContentType::new("audio", "mp4"));
+    }
+}
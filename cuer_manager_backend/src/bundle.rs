@@ -0,0 +1,574 @@
+/**
+
+Offline bundles of event programs.
+
+A bundle is a zip file containing everything needed to run an event without access to the
+library it was created from:
+
+* `bundle.json` with the event, its program, the tips and all of their cue cards
+* `program.html` with the printable program
+* `cuecards/<uuid>.html` with the rendered content of every cue card
+* `cuecards/<uuid>.meta.json` with the metadata file of a cue card, if there is one
+* `music/<music_file>` with the music files referenced by the cue cards
+
+Importing a bundle merges it into the library by UUID. Cue cards and tips already present in
+the library are only updated if the bundled version has been modified more recently, the
+replaced version of a cue card is kept as a revision.
+
+**/
+use crate::guards::BackendConfig;
+use crate::printing;
+use crate::programming;
+use crate::programming::EventProgram;
+use crate::revisions;
+use crate::sandbox;
+use comrak::markdown_to_html;
+use cuecard_indexer::placement;
+use cuer_database::models::{
+    Cuecard, CuecardData, Event, EventData, Program, ProgramData, Tip, TipCuecardData, TipData,
+};
+use diesel::prelude::*;
+use log::{info, warn};
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const BUNDLE_VERSION: u32 = 1;
+const MANIFEST: &str = "bundle.json";
+
+#[derive(Serialize, Deserialize)]
+pub struct BundleTipCuecard {
+    pub cuecard: String,
+    pub sort_order: i32,
+    pub cued_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BundleTip {
+    pub tip: Tip,
+    pub cuecards: Vec<BundleTipCuecard>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub event: Event,
+    pub program: Program,
+    pub tips: Vec<BundleTip>,
+    pub cuecards: Vec<Cuecard>,
+}
+
+/// Summary of an import, listing the UUIDs of the affected cue cards.
+#[derive(Serialize, Deserialize, Default)]
+pub struct ImportReport {
+    pub event: String,
+    pub created_cuecards: Vec<String>,
+    pub updated_cuecards: Vec<String>,
+    pub unchanged_cuecards: Vec<String>,
+    pub copied_music_files: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+fn invalid_data<E: std::fmt::Display>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+fn database_error(err: diesel::result::Error) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+/// Zip entry name of a music file, always using `/` as separator.
+fn music_entry(music_file: &str) -> String {
+    let parts: Vec<String> = Path::new(music_file)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    format!("music/{}", parts.join("/"))
+}
+
+fn metadata_entry(uuid: &str) -> String {
+    format!("cuecards/{}.meta.json", uuid)
+}
+
+/// Writes the bundle of `program` to `writer`.
+pub fn export<W: Write + Seek>(
+    program: &EventProgram,
+    config: &BackendConfig,
+    conn: &SqliteConnection,
+    writer: W,
+) -> io::Result<()> {
+    let mut tips = Vec::new();
+    let mut cuecards: Vec<Cuecard> = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (tip, _) in &program.tips {
+        let tip_cuecards =
+            programming::get_tip_cuecards_with_cuecards(tip, conn).map_err(database_error)?;
+
+        let bundle_cuecards = tip_cuecards
+            .iter()
+            .map(|(tip_cuecard, cuecard)| BundleTipCuecard {
+                cuecard: cuecard.uuid.clone(),
+                sort_order: tip_cuecard.sort_order,
+                cued_at: tip_cuecard.cued_at.clone(),
+            })
+            .collect();
+
+        tips.push(BundleTip {
+            tip: tip.clone(),
+            cuecards: bundle_cuecards,
+        });
+
+        for (_, cuecard) in tip_cuecards {
+            if seen.insert(cuecard.uuid.clone()) {
+                cuecards.push(cuecard);
+            }
+        }
+    }
+
+    let manifest = Manifest {
+        version: BUNDLE_VERSION,
+        event: program.event.clone(),
+        program: program.program.clone(),
+        tips,
+        cuecards,
+    };
+
+    let mut zip = ZipWriter::new(writer);
+    let options = FileOptions::default();
+    // music files are compressed already
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    zip.start_file(MANIFEST, options)?;
+    serde_json::to_writer_pretty(&mut zip, &manifest).map_err(invalid_data)?;
+
+    zip.start_file("program.html", options)?;
+    zip.write_all(printing::to_html(program).as_bytes())?;
+
+    let mut music_files = HashSet::new();
+
+    for cuecard in &manifest.cuecards {
+        zip.start_file(format!("cuecards/{}.html", cuecard.uuid), options)?;
+        let html = markdown_to_html(&cuecard.content, &printing::markdown_options());
+        zip.write_all(html.as_bytes())?;
+
+        let metadata_file = sandbox::resolve_existing(&config.cuecards_lib_dir, &cuecard.file_path)
+            .map(|path| cuecard_indexer::metadata_file_path(&path));
+
+        if let Ok(path) = metadata_file {
+            if path.exists() {
+                zip.start_file(metadata_entry(&cuecard.uuid), options)?;
+                zip.write_all(&fs::read(path)?)?;
+            }
+        }
+
+        if cuecard.music_file.is_empty() || !music_files.insert(cuecard.music_file.clone()) {
+            continue;
+        }
+
+        match sandbox::resolve_existing(&config.music_files_dir, &cuecard.music_file) {
+            Ok(path) => {
+                zip.start_file(music_entry(&cuecard.music_file), stored)?;
+                io::copy(&mut fs::File::open(path)?, &mut zip)?;
+            }
+            Err(err) => warn!(
+                "Music file {:?} of cue card {} not bundled: {}",
+                cuecard.music_file, cuecard.uuid, err
+            ),
+        }
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> io::Result<Option<Vec<u8>>> {
+    match archive.by_name(name) {
+        Ok(mut entry) => {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            Ok(Some(data))
+        }
+        Err(zip::result::ZipError::FileNotFound) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+enum Merge {
+    Create,
+    Update(Box<Cuecard>),
+    Unchanged,
+}
+
+/// A bundled cue card and where it goes in the library.
+struct Placement<'a> {
+    cuecard: &'a Cuecard,
+    merge: Merge,
+    file_path: String,
+}
+
+/// Finds a path for a new cue card file which is taken neither by a file nor by a cue card of the
+/// database or of the same import, and adds it to `cuecard_paths`.
+fn free_file_path(
+    config: &BackendConfig,
+    cuecard: &Cuecard,
+    cuecard_paths: &mut HashSet<PathBuf>,
+) -> io::Result<String> {
+    let file_path = cuecard.file_path.replace('\\', "/");
+    sandbox::resolve(&config.cuecards_lib_dir, &file_path)?;
+
+    let path = placement::unique_path(
+        Path::new(&config.cuecards_lib_dir),
+        Path::new(&file_path),
+        None,
+        cuecard_paths,
+    );
+
+    // the database keeps `/` as separator, like in the bundle
+    let parts: Vec<String> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    cuecard_paths.insert(path);
+
+    Ok(parts.join("/"))
+}
+
+fn place<'a>(
+    cuecard: &'a Cuecard,
+    config: &BackendConfig,
+    cuecard_paths: &mut HashSet<PathBuf>,
+    conn: &SqliteConnection,
+) -> io::Result<Placement<'a>> {
+    match cuer_database::cuecard_by_uuid(&cuecard.uuid, conn) {
+        Ok(existing) => {
+            let file_path = existing.file_path.clone();
+            let merge = if cuecard.date_modified > existing.date_modified {
+                Merge::Update(Box::new(existing))
+            } else {
+                Merge::Unchanged
+            };
+
+            Ok(Placement {
+                cuecard,
+                merge,
+                file_path,
+            })
+        }
+        Err(diesel::result::Error::NotFound) => Ok(Placement {
+            cuecard,
+            merge: Merge::Create,
+            file_path: free_file_path(config, cuecard, cuecard_paths)?,
+        }),
+        Err(err) => Err(database_error(err)),
+    }
+}
+
+fn merge_cuecard(placement: &Placement, conn: &SqliteConnection) -> QueryResult<()> {
+    let cuecard = placement.cuecard;
+
+    let date_created = match &placement.merge {
+        Merge::Unchanged => return Ok(()),
+        Merge::Update(existing) => &existing.date_created,
+        Merge::Create => &cuecard.date_created,
+    };
+
+    let data = CuecardData {
+        uuid: &cuecard.uuid,
+        phase: &cuecard.phase,
+        rhythm: &cuecard.rhythm,
+        title: &cuecard.title,
+        steplevel: &cuecard.steplevel,
+        difficulty: &cuecard.difficulty,
+        choreographer: &cuecard.choreographer,
+        meta: &cuecard.meta,
+        content: &cuecard.content,
+        karaoke_marks: &cuecard.karaoke_marks,
+        music_file: &cuecard.music_file,
+        file_path: &placement.file_path,
+        date_created,
+        date_modified: &cuecard.date_modified,
+    };
+
+    match &placement.merge {
+        Merge::Update(existing) => {
+            revisions::record(existing, cuer_database::REVISION_SOURCE_IMPORT, conn)?;
+            data.update(existing, conn).map(|_| ())
+        }
+        _ => data.create(conn).map(|_| ()),
+    }
+}
+
+fn merge_program(manifest: &Manifest, conn: &SqliteConnection) -> QueryResult<Program> {
+    let bundled = &manifest.event;
+
+    let event = match programming::event_by_uuid(&bundled.uuid, conn) {
+        Ok(event) => event,
+        Err(diesel::result::Error::NotFound) => EventData {
+            uuid: &bundled.uuid,
+            name: &bundled.name,
+            date_start: &bundled.date_start,
+            date_end: &bundled.date_end,
+            schedule: bundled.schedule.as_deref(),
+            date_created: &bundled.date_created,
+            date_modified: &bundled.date_modified,
+            // the series of an occurrence is not part of the bundle
+            series_id: None,
+            recurrence_id: None,
+        }
+        .create(conn)?,
+        Err(err) => return Err(err),
+    };
+
+    let bundled = &manifest.program;
+
+    match programming::program_by_event_id(event.id, conn)? {
+        Some(program) => {
+            if bundled.date_modified > program.date_modified {
+                ProgramData {
+                    uuid: &program.uuid,
+                    notes: bundled.notes.as_deref(),
+                    event_id: event.id,
+                    date_created: &program.date_created,
+                    date_modified: &bundled.date_modified,
+                }
+                .update(conn)
+            } else {
+                Ok(program)
+            }
+        }
+        None => ProgramData {
+            uuid: &bundled.uuid,
+            notes: bundled.notes.as_deref(),
+            event_id: event.id,
+            date_created: &bundled.date_created,
+            date_modified: &bundled.date_modified,
+        }
+        .create(conn),
+    }
+}
+
+/// Replaces a tip and its cue cards by the bundled tip, unless the tip in the library has been
+/// modified more recently. Returns whether the tip was merged.
+fn merge_tip(bundled: &BundleTip, program: &Program, conn: &SqliteConnection) -> QueryResult<bool> {
+    let data = TipData {
+        uuid: &bundled.tip.uuid,
+        name: &bundled.tip.name,
+        program_id: &program.id,
+        date_start: &bundled.tip.date_start,
+        date_end: &bundled.tip.date_end,
+    };
+
+    let tip = match programming::get_tip_by_uuid(&bundled.tip.uuid, conn) {
+        Ok(tip) if bundled.tip.date_modified <= tip.date_modified => return Ok(false),
+        Ok(_) => data.update(conn)?,
+        Err(diesel::result::Error::NotFound) => data.create(conn)?,
+        Err(err) => return Err(err),
+    };
+
+    programming::clear_tip_cuecards(&tip, conn)?;
+
+    for tip_cuecard in &bundled.cuecards {
+        let cuecard = cuer_database::cuecard_by_uuid(&tip_cuecard.cuecard, conn)?;
+
+        TipCuecardData {
+            tip_id: &tip.id,
+            cuecard_id: &cuecard.id,
+            sort_order: &tip_cuecard.sort_order,
+            cued_at: tip_cuecard.cued_at.clone(),
+        }
+        .create(conn)?;
+    }
+
+    // the merged tip has the version of the bundled tip, not the time of the import
+    if !bundled.tip.date_modified.is_empty() {
+        programming::set_tip_modified(&tip, &bundled.tip.date_modified, conn)?;
+    }
+
+    Ok(true)
+}
+
+/// Writes the file, metadata file and index file of an imported cue card, so the indexer
+/// recognizes the file as belonging to the cue card.
+fn write_cuecard_files(
+    placement: &Placement,
+    metadata: Option<Vec<u8>>,
+    config: &BackendConfig,
+) -> io::Result<()> {
+    let path = sandbox::resolve(&config.cuecards_lib_dir, &placement.file_path)?;
+
+    if let Some(parent) = path.parent() {
+        fs::DirBuilder::new().recursive(true).create(parent)?;
+    }
+
+    fs::write(&path, &placement.cuecard.content)?;
+
+    if let Some(metadata) = metadata {
+        fs::write(cuecard_indexer::metadata_file_path(&path), metadata)?;
+    }
+
+    // written last, the index file must not be older than the cue card file
+    match cuecard_indexer::index_file_path(&path) {
+        Some(index_file) => fs::write(index_file, &placement.cuecard.uuid),
+        None => Err(invalid_data(format!("invalid file path {:?}", path))),
+    }
+}
+
+fn copy_music_file<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    music_file: &str,
+    config: &BackendConfig,
+) -> io::Result<bool> {
+    let target: PathBuf = sandbox::resolve(&config.music_files_dir, music_file)?;
+
+    if target.exists() {
+        return Ok(false);
+    }
+
+    let mut entry = match archive.by_name(&music_entry(music_file)) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+
+    if let Some(parent) = target.parent() {
+        fs::DirBuilder::new().recursive(true).create(parent)?;
+    }
+
+    let temp = tempfile::NamedTempFile::new_in(target.parent().unwrap())?;
+    io::copy(&mut entry, &mut temp.as_file())?;
+    temp.persist(&target).map_err(|err| err.error)?;
+
+    Ok(true)
+}
+
+/// Merges the bundle read from `reader` into the library.
+pub fn import<R: Read + Seek>(
+    reader: R,
+    config: &BackendConfig,
+    conn: &SqliteConnection,
+) -> io::Result<ImportReport> {
+    let mut archive = ZipArchive::new(reader)?;
+
+    let manifest: Manifest = match read_entry(&mut archive, MANIFEST)? {
+        Some(data) => serde_json::from_slice(&data).map_err(invalid_data)?,
+        None => return Err(invalid_data("bundle.json is missing")),
+    };
+
+    if manifest.version > BUNDLE_VERSION {
+        return Err(invalid_data(format!(
+            "unsupported bundle version {}",
+            manifest.version
+        )));
+    }
+
+    let mut cuecard_paths = placement::cuecard_paths(conn).map_err(database_error)?;
+    let mut placements = Vec::new();
+    for cuecard in &manifest.cuecards {
+        placements.push(place(cuecard, config, &mut cuecard_paths, conn)?);
+    }
+
+    let kept_tips = conn
+        .transaction::<_, diesel::result::Error, _>(|| {
+            for placement in &placements {
+                merge_cuecard(placement, conn)?;
+            }
+
+            let program = merge_program(&manifest, conn)?;
+            let mut kept_tips = Vec::new();
+
+            for tip in &manifest.tips {
+                if !merge_tip(tip, &program, conn)? {
+                    kept_tips.push(tip.tip.name.clone());
+                }
+            }
+
+            Ok(kept_tips)
+        })
+        .map_err(database_error)?;
+
+    let mut report = ImportReport {
+        event: manifest.event.uuid.clone(),
+        ..ImportReport::default()
+    };
+
+    for name in kept_tips {
+        report.warnings.push(format!(
+            "Tip {} not imported, it has been modified more recently in the library",
+            name
+        ));
+    }
+
+    for placement in &placements {
+        let uuid = placement.cuecard.uuid.clone();
+
+        match placement.merge {
+            Merge::Unchanged => {
+                report.unchanged_cuecards.push(uuid);
+                continue;
+            }
+            Merge::Update(_) => report.updated_cuecards.push(uuid),
+            Merge::Create => report.created_cuecards.push(uuid),
+        }
+
+        if config.cuecards_self_managed {
+            continue;
+        }
+
+        let metadata = read_entry(&mut archive, &metadata_entry(&placement.cuecard.uuid))?;
+
+        if let Err(err) = write_cuecard_files(placement, metadata, config) {
+            report.warnings.push(format!(
+                "Cue card file {} not written: {}",
+                placement.file_path, err
+            ));
+        }
+    }
+
+    let music_files: HashSet<&str> = manifest
+        .cuecards
+        .iter()
+        .map(|c| c.music_file.as_str())
+        .filter(|m| !m.is_empty())
+        .collect();
+
+    for music_file in music_files {
+        match copy_music_file(&mut archive, music_file, config) {
+            Ok(true) => report.copied_music_files.push(music_file.to_string()),
+            Ok(false) => (),
+            Err(err) => report.warnings.push(format!(
+                "Music file {} not copied: {}",
+                music_file, err
+            )),
+        }
+    }
+
+    info!(
+        "Imported bundle of event {}: {} cue cards created, {} updated",
+        report.event,
+        report.created_cuecards.len(),
+        report.updated_cuecards.len()
+    );
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_music_entry() {
+        assert_eq!(music_entry("waltz.mp3"), "music/waltz.mp3");
+        assert_eq!(music_entry("w/waltz.mp3"), "music/w/waltz.mp3");
+    }
+}
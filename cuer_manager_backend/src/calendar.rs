@@ -0,0 +1,577 @@
+/**
+
+iCalendar export and import of events.
+
+Events are exported as a feed which can be subscribed to in calendar apps. The program of an
+event is part of the description, so co-cuers can see the planned dances.
+
+Imported calendars only provide the name and the time of events. Times without a time zone
+and times in a named time zone are taken as local times, as time zone definitions are not
+evaluated.
+
+**/
+use crate::programming;
+use crate::programming::EventProgram;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use cuer_database::models::{Event, EventData, ProgramData};
+use diesel::prelude::*;
+use uuidcrate::Uuid;
+
+type DBConnection = SqliteConnection;
+
+const PRODID: &str = "-//sopicki.de//Cuer Manager//EN";
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// An event of a calendar file.
+#[derive(Debug, PartialEq)]
+pub struct CalendarEvent {
+    pub uid: String,
+    pub summary: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub description: Option<String>,
+    pub categories: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct CalendarImport {
+    pub created: Vec<Event>,
+    /// Names of events which already exist
+    pub skipped: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Describes the program of an event: the notes followed by the dances of every tip.
+pub fn program_description(program: &EventProgram) -> String {
+    let mut lines = Vec::new();
+
+    if let Some(notes) = program
+        .program
+        .notes
+        .as_ref()
+        .filter(|n| !n.trim().is_empty())
+    {
+        lines.push(notes.trim().to_string());
+        lines.push(String::new());
+    }
+
+    for (tip, cuecards) in &program.tips {
+        let titles: Vec<String> = cuecards
+            .iter()
+            .map(|c| format!("{} ({})", c.title, c.rhythm))
+            .collect();
+
+        match DateTime::parse_from_rfc3339(&tip.date_start) {
+            Ok(start) => lines.push(format!(
+                "{} {}: {}",
+                start.with_timezone(&Local).format("%H:%M"),
+                tip.name,
+                titles.join(", ")
+            )),
+            Err(_) => lines.push(format!("{}: {}", tip.name, titles.join(", "))),
+        }
+    }
+
+    lines.join("\n").trim().to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') | Some('N') => result.push('\n'),
+            Some(c) => result.push(c),
+            None => (),
+        }
+    }
+
+    result
+}
+
+/// Splits a list value at the commas which are not escaped.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut escaped = false;
+
+    for c in value.chars() {
+        match (c, escaped) {
+            (',', false) => items.push(String::new()),
+            (c, _) => {
+                escaped = c == '\\' && !escaped;
+                if let Some(item) = items.last_mut() {
+                    item.push(c);
+                }
+            }
+        }
+    }
+
+    items.iter().map(|item| unescape(item)).collect()
+}
+
+/// Appends a content line, folded after 75 octets.
+fn push_line(ics: &mut String, line: &str) {
+    let mut length = 0;
+
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            length = 1;
+        }
+
+        ics.push(c);
+        length += c.len_utf8();
+    }
+
+    ics.push_str("\r\n");
+}
+
+/// Writes a calendar of `events`, `stamp` is the time the calendar is created.
+pub fn to_ics(name: &str, events: &[CalendarEvent], stamp: DateTime<Utc>) -> String {
+    let mut ics = String::new();
+
+    push_line(&mut ics, "BEGIN:VCALENDAR");
+    push_line(&mut ics, "VERSION:2.0");
+    push_line(&mut ics, &format!("PRODID:{}", PRODID));
+    push_line(&mut ics, "CALSCALE:GREGORIAN");
+    push_line(&mut ics, &format!("X-WR-CALNAME:{}", escape(name)));
+
+    for event in events {
+        push_line(&mut ics, "BEGIN:VEVENT");
+        push_line(&mut ics, &format!("UID:{}", event.uid));
+        push_line(
+            &mut ics,
+            &format!("DTSTAMP:{}", stamp.format(DATE_TIME_FORMAT)),
+        );
+        push_line(
+            &mut ics,
+            &format!("DTSTART:{}", event.start.format(DATE_TIME_FORMAT)),
+        );
+        push_line(
+            &mut ics,
+            &format!("DTEND:{}", event.end.format(DATE_TIME_FORMAT)),
+        );
+        push_line(&mut ics, &format!("SUMMARY:{}", escape(&event.summary)));
+
+        if let Some(description) = event.description.as_ref().filter(|d| !d.is_empty()) {
+            push_line(&mut ics, &format!("DESCRIPTION:{}", escape(description)));
+        }
+
+        if !event.categories.is_empty() {
+            let categories: Vec<String> = event.categories.iter().map(|c| escape(c)).collect();
+            push_line(&mut ics, &format!("CATEGORIES:{}", categories.join(",")));
+        }
+
+        push_line(&mut ics, "END:VEVENT");
+    }
+
+    push_line(&mut ics, "END:VCALENDAR");
+
+    ics
+}
+
+/// A content line split into its name, parameters and value.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Property> {
+        let mut quoted = false;
+        let colon = line.char_indices().find(|(_, c)| {
+            if *c == '"' {
+                quoted = !quoted;
+            }
+            *c == ':' && !quoted
+        })?;
+
+        let (head, value) = (&line[..colon.0], &line[colon.0 + 1..]);
+        let mut parts = head.split(';');
+        let name = parts.next()?.trim().to_uppercase();
+        let params = parts
+            .filter_map(|p| {
+                let mut pair = p.splitn(2, '=');
+                Some((
+                    pair.next()?.trim().to_uppercase(),
+                    pair.next()?.trim_matches('"').to_string(),
+                ))
+            })
+            .collect();
+
+        Some(Property {
+            name,
+            params,
+            value: value.to_string(),
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parses a date or date and time value, returning whether it is a date only.
+    fn time(&self) -> Option<(DateTime<Utc>, bool)> {
+        let value = self.value.trim();
+
+        if self.param("VALUE") == Some("DATE") || value.len() == 8 {
+            let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+            let start = Local
+                .from_local_datetime(&date.and_hms(0, 0, 0))
+                .earliest()?;
+            return Some((start.with_timezone(&Utc), true));
+        }
+
+        if value.ends_with('Z') {
+            return Utc
+                .datetime_from_str(value, DATE_TIME_FORMAT)
+                .ok()
+                .map(|t| (t, false));
+        }
+
+        let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+        Local
+            .from_local_datetime(&time)
+            .earliest()
+            .map(|t| (t.with_timezone(&Utc), false))
+    }
+}
+
+/// Parses a duration like `PT1H30M` or `-P1W`, `None` if it is invalid or too long.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1, value),
+        None => (1, value.trim_start_matches('+')),
+    };
+
+    let value = value.strip_prefix('P')?;
+    let mut seconds = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+
+    for c in value.chars() {
+        let factor = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'T' => {
+                in_time = true;
+                continue;
+            }
+            'W' => 7 * 24 * 3600,
+            'D' => 24 * 3600,
+            'H' if in_time => 3600,
+            'M' if in_time => 60,
+            'S' if in_time => 1,
+            _ => return None,
+        };
+
+        let part = number.parse::<i64>().ok()?.checked_mul(factor)?;
+        seconds = part.checked_add(seconds)?;
+        number.clear();
+    }
+
+    if number.is_empty() {
+        // milliseconds can't overflow the duration, seconds could
+        Some(Duration::milliseconds(seconds.checked_mul(1000)? * sign))
+    } else {
+        None
+    }
+}
+
+/// Joins folded lines of a calendar file.
+fn unfold(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in input.lines() {
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    lines
+}
+
+/// Reads the events of a calendar file.
+///
+/// Events without a start are left out with a warning. Recurring events are imported with
+/// their first occurrence only.
+pub fn parse_ics(input: &str, warnings: &mut Vec<String>) -> Vec<CalendarEvent> {
+    let mut events = Vec::new();
+    // properties of the current event, and the depth of components nested within it
+    let mut current: Option<Vec<Property>> = None;
+    let mut depth = 0;
+
+    for line in unfold(input) {
+        let property = match Property::parse(&line) {
+            Some(property) => property,
+            None => continue,
+        };
+        let component = property.value.trim().to_uppercase();
+
+        match (property.name.as_ref(), current.as_mut()) {
+            ("BEGIN", None) if component == "VEVENT" => current = Some(Vec::new()),
+            ("BEGIN", Some(_)) => depth += 1,
+            ("END", Some(_)) if depth > 0 => depth -= 1,
+            ("END", Some(_)) => {
+                if let Some(event) = current.take().and_then(|p| to_event(p, warnings)) {
+                    events.push(event);
+                }
+            }
+            (_, Some(properties)) if depth == 0 => properties.push(property),
+            _ => (),
+        }
+    }
+
+    events
+}
+
+fn to_event(properties: Vec<Property>, warnings: &mut Vec<String>) -> Option<CalendarEvent> {
+    let find = |name: &str| properties.iter().find(|p| p.name == name);
+
+    let summary = find("SUMMARY")
+        .map(|p| unescape(&p.value))
+        .unwrap_or_default();
+
+    let (start, all_day) = match find("DTSTART").and_then(Property::time) {
+        Some(start) => start,
+        None => {
+            warnings.push(format!("Event {:?} has no valid start", summary));
+            return None;
+        }
+    };
+
+    let end = match (find("DTEND").and_then(Property::time), find("DURATION")) {
+        (Some((end, _)), _) => end,
+        (None, Some(duration)) => {
+            match parse_duration(&duration.value).and_then(|d| start.checked_add_signed(d)) {
+                Some(end) => end,
+                None => {
+                    warnings.push(format!("Event {:?} has an invalid duration", summary));
+                    return None;
+                }
+            }
+        }
+        (None, None) if all_day => start + Duration::days(1),
+        (None, None) => start,
+    };
+
+    if find("RRULE").is_some() {
+        warnings.push(format!(
+            "Only the first occurrence of {:?} is imported",
+            summary
+        ));
+    }
+
+    let categories = properties
+        .iter()
+        .filter(|p| p.name == "CATEGORIES")
+        .flat_map(|p| split_list(&p.value))
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .collect();
+
+    Some(CalendarEvent {
+        uid: find("UID")
+            .map(|p| p.value.trim().to_string())
+            .unwrap_or_default(),
+        summary,
+        start,
+        end,
+        description: find("DESCRIPTION").map(|p| unescape(&p.value)),
+        categories,
+    })
+}
+
+fn to_timestamp(time: &DateTime<Utc>) -> String {
+    time.format("%FT%T%.3fZ").to_string()
+}
+
+/// Looks for an event imported before, by its uuid or else by its name and start.
+fn existing_event(
+    event: &CalendarEvent,
+    start: &str,
+    conn: &DBConnection,
+) -> QueryResult<Option<Event>> {
+    use cuer_database::schema::events::dsl::*;
+
+    if Uuid::parse_str(&event.uid).is_ok() {
+        if let Some(existing) = programming::event_by_uuid(&event.uid, conn).optional()? {
+            return Ok(Some(existing));
+        }
+    }
+
+    events
+        .filter(name.eq(&event.summary))
+        .filter(date_start.eq(start))
+        .first::<Event>(conn)
+        .optional()
+}
+
+/// Creates an event with an empty program for each calendar event which doesn't exist yet.
+///
+/// The uid of an event is kept if it is a uuid, so importing an exported calendar again
+/// doesn't duplicate events. Categories become tags of the event.
+pub fn import(
+    events: Vec<CalendarEvent>,
+    warnings: Vec<String>,
+    conn: &DBConnection,
+) -> QueryResult<CalendarImport> {
+    let mut report = CalendarImport {
+        warnings,
+        ..CalendarImport::default()
+    };
+
+    conn.transaction::<_, diesel::result::Error, _>(|| {
+        for event in events {
+            let start = to_timestamp(&event.start);
+            let end = to_timestamp(&event.end);
+
+            if existing_event(&event, &start, conn)?.is_some() {
+                report.skipped.push(event.summary);
+                continue;
+            }
+
+            let u = match Uuid::parse_str(&event.uid) {
+                Ok(u) => u.to_hyphenated().to_string(),
+                Err(_) => Uuid::new_v4().to_hyphenated().to_string(),
+            };
+            let now = Local::now().format("%FT%T%.3fZ").to_string();
+
+            let created = EventData {
+                uuid: &u,
+                name: &event.summary,
+                date_start: &start,
+                date_end: &end,
+                schedule: None,
+                date_created: &now,
+                date_modified: &now,
+                series_id: None,
+                recurrence_id: None,
+            }
+            .create(conn)?;
+
+            let u = Uuid::new_v4().to_hyphenated().to_string();
+            let now = Utc::now().format("%FT%T%.3fZ").to_string();
+
+            ProgramData {
+                uuid: &u,
+                notes: None,
+                event_id: created.id,
+                date_created: &now,
+                date_modified: &now,
+            }
+            .create(conn)?;
+
+            for category in &event.categories {
+                programming::add_event_tag(category, &created, conn)?;
+            }
+
+            report.created.push(created);
+        }
+
+        Ok(())
+    })?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let event = CalendarEvent {
+            uid: "e6f4c3a4-8d0b-4a49-9b39-0c7c1d6c2e11".to_string(),
+            summary: "Round Dance Festival, Day 1".to_string(),
+            start: Utc.ymd(2020, 6, 5).and_hms(17, 0, 0),
+            end: Utc.ymd(2020, 6, 5).and_hms(21, 30, 0),
+            description: Some(format!("19:00 Tip 1: {}", "Waltz (Waltz), ".repeat(10))),
+            categories: vec!["festival".to_string(), "phase 4, 5".to_string()],
+        };
+        let stamp = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+
+        let description = event.description.clone();
+        let ics = to_ics("Cuer Manager", &[event], stamp);
+        assert!(ics.contains("\r\nSUMMARY:Round Dance Festival\\, Day 1\r\n"));
+        assert!(ics.contains("\r\nDTSTART:20200605T170000Z\r\n"));
+        assert!(ics.lines().all(|l| l.len() <= 75));
+
+        let mut warnings = Vec::new();
+        let events = parse_ics(&ics, &mut warnings);
+        assert!(warnings.is_empty());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].summary, "Round Dance Festival, Day 1");
+        assert_eq!(events[0].end, Utc.ymd(2020, 6, 5).and_hms(21, 30, 0));
+        assert_eq!(events[0].categories, vec!["festival", "phase 4, 5"]);
+        assert_eq!(events[0].description, description);
+    }
+
+    #[test]
+    fn test_parse_ics() {
+        let ics = "BEGIN:VCALENDAR\n\
+                   BEGIN:VEVENT\n\
+                   UID:workshop@festival.example\n\
+                   DTSTART:20200606T090000Z\n\
+                   DURATION:PT1H30M\n\
+                   SUMMARY:Workshop\n\
+                   BEGIN:VALARM\n\
+                   SUMMARY:Reminder\n\
+                   END:VALARM\n\
+                   RRULE:FREQ=DAILY;COUNT=2\n\
+                   END:VEVENT\n\
+                   BEGIN:VEVENT\n\
+                   SUMMARY:Broken\n\
+                   END:VEVENT\n\
+                   BEGIN:VEVENT\n\
+                   DTSTART:20200606T090000Z\n\
+                   DURATION:P9999999999999W\n\
+                   SUMMARY:Endless\n\
+                   END:VEVENT\n\
+                   END:VCALENDAR\n";
+
+        let mut warnings = Vec::new();
+        let events = parse_ics(ics, &mut warnings);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].summary, "Workshop");
+        assert_eq!(events[0].uid, "workshop@festival.example");
+        assert_eq!(events[0].end, Utc.ymd(2020, 6, 6).and_hms(10, 30, 0));
+        assert_eq!(warnings.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("P1W"), Some(Duration::weeks(1)));
+        assert_eq!(parse_duration("-P1DT2S"), Some(Duration::seconds(-86402)));
+        assert_eq!(parse_duration("P1H"), None);
+        assert_eq!(parse_duration("P99999999999999999W"), None);
+        assert_eq!(parse_duration("P9223372036854775807DT1S"), None);
+    }
+}
@@ -1,6 +1,17 @@
+use crate::revisions;
+use chrono::Utc;
 use cuer_database;
-use cuer_database::models::{Cuecard, CuecardTag, CuecardTagData, Tag, TagData, TipCuecard};
+use cuer_database::models::{
+    Cuecard, CuecardData, CuecardTag, CuecardTagData, Tag, TagData, TipCuecard,
+};
 use diesel::prelude::*;
+use log::error;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
 
 use super::DbConn;
 
@@ -149,3 +160,130 @@ pub fn get_tip_cuecard_to_current_event(
         .select(tip_cuecards::all_columns)
         .load::<TipCuecard>(conn)
 }
+
+/// Returns the time each cue card was cued last according to the cue log, by cue card id.
+pub fn last_cued(conn: &DBConnection) -> QueryResult<HashMap<i32, String>> {
+    use cuer_database::schema::cues::dsl::*;
+
+    let log = cues
+        .select((cuecard_id, started_at))
+        .load::<(i32, String)>(conn)?;
+
+    let mut result: HashMap<i32, String> = HashMap::new();
+
+    for (card_id, time) in log {
+        let last = result.entry(card_id).or_default();
+        if time > *last {
+            *last = time;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Replaces the content of a cue card and writes it to the file of the cue card at `path`. The
+/// metadata is read from the new content like the indexer does. Nothing is changed if the file
+/// can't be written, or if the cue card was modified since `cuecard` was read, which returns
+/// `None`.
+pub fn save_content(
+    cuecard: &Cuecard,
+    content: &str,
+    path: &Path,
+    conn: &DBConnection,
+) -> io::Result<Option<Cuecard>> {
+    let metadata = cuecard_indexer::file_metadata(path, content)?;
+    let meta = serde_json::to_string(&metadata).map_err(io::Error::other)?;
+    let field = |key: &str, default: &'static str| {
+        metadata.get(key).map_or(default, String::as_str)
+    };
+
+    // like the indexer, a music file chosen for the cue card is kept
+    let music_file = if cuecard.music_file.is_empty() {
+        field("music_file", "")
+    } else {
+        &cuecard.music_file
+    };
+
+    let mut write_error = None;
+    let mut modified = false;
+
+    let result = conn.transaction::<_, diesel::result::Error, _>(|| {
+        if cuecard.content != content {
+            revisions::record(cuecard, cuer_database::REVISION_SOURCE_EDITOR, conn)?;
+        }
+
+        let updated = CuecardData {
+            uuid: &cuecard.uuid,
+            phase: field("phase", "unphased"),
+            rhythm: field("rhythm", "unknown"),
+            title: field("title", "unknown"),
+            steplevel: field("steplevel", ""),
+            difficulty: field("difficulty", ""),
+            choreographer: field("choreographer", "unknown"),
+            meta: &meta,
+            content,
+            karaoke_marks: &cuecard.karaoke_marks,
+            music_file,
+            file_path: &cuecard.file_path,
+            date_created: &cuecard.date_created,
+            date_modified: &Utc::now().format("%FT%T%.3fZ").to_string(),
+        }
+        .update_if_unchanged(cuecard, conn)?;
+
+        if updated == 0 {
+            modified = true;
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+
+        if let Err(err) = write_file(path, content) {
+            write_error = Some(err);
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+
+        cuer_database::cuecard_by_uuid(&cuecard.uuid, conn)
+    });
+
+    let saved = match (result, write_error) {
+        (_, Some(err)) => return Err(err),
+        (Ok(saved), None) => saved,
+        (Err(_), None) if modified => return Ok(None),
+        (Err(err), None) => return Err(io::Error::other(err.to_string())),
+    };
+
+    mark_indexed(&saved, path);
+
+    Ok(Some(saved))
+}
+
+/// Replaces the file at `path` by a file with `content`, so the file is never partially written.
+pub fn write_file(path: &Path, content: &str) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) => parent,
+        None => return Err(io::Error::other(format!("invalid file path {:?}", path))),
+    };
+
+    let mut temp = tempfile::NamedTempFile::new_in(parent)?;
+    temp.write_all(content.as_bytes())?;
+
+    // the temporary file is only readable by its owner
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(temp.path(), metadata.permissions())?;
+    }
+
+    temp.persist(path).map_err(|err| err.error)?;
+
+    Ok(())
+}
+
+/// Rewrites the index file of the cue card file at `path`, so the next indexer run doesn't
+/// take the file for modified since it was indexed.
+pub fn mark_indexed(cuecard: &Cuecard, path: &Path) {
+    let result = match cuecard_indexer::index_file_path(path) {
+        Some(index_file) => fs::write(&index_file, &cuecard.uuid),
+        None => Err(io::Error::other(format!("invalid file path {:?}", path))),
+    };
+
+    if let Err(err) = result {
+        error!("Error updating index file of {:?}: {:?}", path, err);
+    }
+}
@@ -0,0 +1,115 @@
+/**
+
+The cue log records every time a cue card is cued.
+
+Cues played from the program of an event reference the tip they belong to. Cues of cue cards
+not in the program are recorded as ad hoc cues, either at an event or, for example at
+practice sessions, without any event.
+
+**/
+use crate::programming;
+use cuer_database::models::{Cue, CueData, Cuecard, Event};
+use diesel::prelude::*;
+use uuidcrate::Uuid;
+
+type DBConnection = SqliteConnection;
+
+pub const SOURCE_PROGRAM: &str = "program";
+pub const SOURCE_ADHOC: &str = "adhoc";
+
+fn event_id_of_tip(t_id: i32, conn: &DBConnection) -> QueryResult<i32> {
+    use cuer_database::schema::*;
+
+    tips::table
+        .inner_join(programs::table)
+        .filter(tips::columns::id.eq(t_id))
+        .select(programs::columns::event_id)
+        .first::<i32>(conn)
+}
+
+/// Adds a cue of `cuecard` started at `started_at` to the log.
+///
+/// The event is taken from the tip if only the tip is known. A tip of another event's program
+/// is not found. The cue counts as played from the program if the cue card is part of the tip.
+pub fn record(
+    cuecard: &Cuecard,
+    event_id: Option<i32>,
+    tip_id: Option<i32>,
+    started_at: &str,
+    conn: &DBConnection,
+) -> QueryResult<Cue> {
+    let event_id = match (event_id, tip_id) {
+        (None, Some(t_id)) => Some(event_id_of_tip(t_id, conn)?),
+        (Some(e_id), Some(t_id)) if event_id_of_tip(t_id, conn)? != e_id => {
+            return Err(diesel::result::Error::NotFound);
+        }
+        (event_id, _) => event_id,
+    };
+
+    let source = match tip_id {
+        Some(t_id) if programming::get_tip_cuecard(t_id, cuecard.id, conn).is_ok() => {
+            SOURCE_PROGRAM
+        }
+        _ => SOURCE_ADHOC,
+    };
+
+    let u = Uuid::new_v4().to_hyphenated().to_string();
+
+    CueData {
+        uuid: &u,
+        cuecard_id: cuecard.id,
+        event_id,
+        tip_id,
+        source,
+        started_at,
+        stopped_at: None,
+    }
+    .create(conn)
+}
+
+pub fn stop(cue: &Cue, time: &str, conn: &DBConnection) -> QueryResult<Cue> {
+    use cuer_database::schema::cues::dsl::*;
+
+    diesel::update(cues.filter(id.eq(cue.id)))
+        .set(stopped_at.eq(time))
+        .execute(conn)?;
+
+    cues.filter(id.eq(cue.id)).first::<Cue>(conn)
+}
+
+pub fn cue_by_uuid(cue_uuid: &str, conn: &DBConnection) -> QueryResult<Cue> {
+    use cuer_database::schema::cues::dsl::*;
+
+    cues.filter(uuid.eq(cue_uuid)).first::<Cue>(conn)
+}
+
+/// Returns the logged cues, latest first, optionally only those of an event or a cue card.
+pub fn cues(
+    event: Option<&Event>,
+    cuecard: Option<&Cuecard>,
+    conn: &DBConnection,
+) -> QueryResult<Vec<Cue>> {
+    use cuer_database::schema::cues::dsl::*;
+
+    let mut query = cues.order(started_at.desc()).into_boxed();
+
+    if let Some(event) = event {
+        query = query.filter(event_id.eq(event.id));
+    }
+
+    if let Some(cuecard) = cuecard {
+        query = query.filter(cuecard_id.eq(cuecard.id));
+    }
+
+    query.load::<Cue>(conn)
+}
+
+/// Returns the events running at `timestamp`.
+pub fn events_at(timestamp: &str, conn: &DBConnection) -> QueryResult<Vec<Event>> {
+    use cuer_database::schema::events::dsl::*;
+
+    events
+        .filter(date_start.le(timestamp))
+        .filter(date_end.gt(timestamp))
+        .load::<Event>(conn)
+}
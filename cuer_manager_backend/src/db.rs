@@ -0,0 +1,58 @@
+/**
+
+Pooled connections to the library database.
+
+SQLite only enforces foreign keys when they are enabled on a connection, so every connection
+of the pool enables them when it is established. Deleting events, tips or cue cards then
+also removes the rows depending on them.
+
+**/
+use rocket_contrib::databases::diesel::r2d2::{ConnectionManager, Error, ManageConnection};
+use rocket_contrib::databases::diesel::SqliteConnection;
+use rocket_contrib::databases::{r2d2, DatabaseConfig, Poolable};
+
+use std::ops::Deref;
+
+pub struct LibraryConnection(SqliteConnection);
+
+impl Deref for LibraryConnection {
+    type Target = SqliteConnection;
+
+    fn deref(&self) -> &SqliteConnection {
+        &self.0
+    }
+}
+
+pub struct LibraryConnectionManager(ConnectionManager<SqliteConnection>);
+
+impl ManageConnection for LibraryConnectionManager {
+    type Connection = LibraryConnection;
+    type Error = Error;
+
+    fn connect(&self) -> Result<LibraryConnection, Error> {
+        let conn = self.0.connect()?;
+        cuer_database::set_foreign_keys(true, &conn).map_err(Error::QueryError)?;
+
+        Ok(LibraryConnection(conn))
+    }
+
+    fn is_valid(&self, conn: &mut LibraryConnection) -> Result<(), Error> {
+        self.0.is_valid(&mut conn.0)
+    }
+
+    fn has_broken(&self, conn: &mut LibraryConnection) -> bool {
+        self.0.has_broken(&mut conn.0)
+    }
+}
+
+impl Poolable for LibraryConnection {
+    type Manager = LibraryConnectionManager;
+    type Error = r2d2::Error;
+
+    fn pool(config: DatabaseConfig) -> Result<r2d2::Pool<Self::Manager>, Self::Error> {
+        let manager = LibraryConnectionManager(ConnectionManager::new(config.url));
+        r2d2::Pool::builder()
+            .max_size(config.pool_size)
+            .build(manager)
+    }
+}
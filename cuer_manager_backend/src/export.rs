@@ -0,0 +1,1432 @@
+/**
+
+Export of cue cards as ODT and DOCX documents.
+
+The Markdown content of a cue card is rendered into a word-processor document that can be edited
+and shared like any other cue sheet. The title becomes the document title, parts become headings,
+cues in block quotes get an indented paragraph style and lists and tables are kept. Metadata of the
+cue card that is not part of its content, like the choreographer or the phase, is added below the
+title as the same `* **Phase**: IV` lines the indexer reads, and it is set as the document
+properties.
+
+**/
+use crate::printing::markdown_options;
+use comrak::nodes::{AstNode, ListType, NodeValue};
+use comrak::{parse_document, Arena};
+use cuer_database::models::Cuecard;
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+use zip::write::{FileOptions, ZipWriter};
+use zip::CompressionMethod;
+
+use std::collections::HashMap;
+use std::io;
+use std::io::{Cursor, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Odt,
+    Docx,
+}
+
+impl Format {
+    /// Returns the format of a name like `odt`, ignoring the case.
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_lowercase().as_str() {
+            "odt" => Some(Format::Odt),
+            "docx" => Some(Format::Docx),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Odt => "odt",
+            Format::Docx => "docx",
+        }
+    }
+
+    /// Returns the top-level type and the subtype of the media type.
+    pub fn media_type(self) -> (&'static str, &'static str) {
+        match self {
+            Format::Odt => ("application", "vnd.oasis.opendocument.text"),
+            Format::Docx => (
+                "application",
+                "vnd.openxmlformats-officedocument.wordprocessingml.document",
+            ),
+        }
+    }
+}
+
+/// Formatting of a text run.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Formatting {
+    bold: bool,
+    italic: bool,
+    link: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Inline {
+    Text(String, Formatting),
+    LineBreak,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ParagraphStyle {
+    Title,
+    Heading(u32),
+    Text,
+    Cue,
+    /// Item of a list, `level` starts at 1. Items of ordered lists keep their numbers as text.
+    Item {
+        level: usize,
+        bullet: bool,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+enum Block {
+    Paragraph(ParagraphStyle, Vec<Inline>),
+    /// Rows of cells, the first row is the header
+    Table(Vec<Vec<Vec<Inline>>>),
+}
+
+/// Document properties of the exported cue card.
+#[derive(Debug, Default)]
+struct Properties {
+    title: String,
+    author: Option<String>,
+    keywords: Vec<String>,
+}
+
+fn is_known(value: &str) -> bool {
+    let value = value.trim();
+
+    !value.is_empty() && value != "unknown" && value != "unphased"
+}
+
+/// Returns the metadata of a cue card with the names used in Markdown, the phase includes the
+/// plus figures.
+fn cuecard_metadata(cuecard: &Cuecard) -> Vec<(&'static str, String)> {
+    let meta: HashMap<String, String> = serde_json::from_str(&cuecard.meta).unwrap_or_default();
+    let meta_value = |name: &str| meta.get(name).cloned().unwrap_or_default();
+
+    vec![
+        ("Choreographer", cuecard.choreographer.clone()),
+        ("Music", meta_value("music")),
+        ("Rhythm", cuecard.rhythm.clone()),
+        (
+            "Phase",
+            format!("{}{}", cuecard.phase, meta_value("plusfigures")),
+        ),
+        ("Steplevel", cuecard.steplevel.clone()),
+        ("Difficulty", cuecard.difficulty.clone()),
+    ]
+    .into_iter()
+    .filter(|(name, value)| is_known(value) && (*name != "Phase" || is_known(&cuecard.phase)))
+    .collect()
+}
+
+/// Escapes the characters of a metadata value that Markdown would take as formatting.
+fn escape_markdown(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(|c| match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+/// Adds the metadata lines that are missing from the content below the title.
+fn with_metadata(cuecard: &Cuecard) -> String {
+    let content_metadata = cuecard_indexer::content_metadata(&cuecard.content);
+    let missing = cuecard_metadata(cuecard)
+        .into_iter()
+        .filter(|(name, _)| {
+            content_metadata
+                .get(&name.to_lowercase())
+                .is_none_or(|value| !is_known(value))
+        })
+        .map(|(name, value)| format!("* **{}**: {}", name, escape_markdown(&value)))
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        return cuecard.content.clone();
+    }
+
+    let mut lines = cuecard
+        .content
+        .lines()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let position = lines
+        .iter()
+        .position(|line| line.starts_with("# "))
+        .map_or(0, |title| title + 1);
+    // the metadata is a list of its own, separated from the title by an empty line
+    lines.splice(
+        position..position,
+        std::iter::once(String::new()).chain(missing),
+    );
+
+    lines.join("\n")
+}
+
+fn properties(cuecard: &Cuecard) -> Properties {
+    let keywords = vec![&cuecard.rhythm, &cuecard.phase, &cuecard.steplevel]
+        .into_iter()
+        .filter(|value| is_known(value))
+        .cloned()
+        .collect();
+
+    Properties {
+        title: cuecard.title.clone(),
+        author: Some(cuecard.choreographer.clone()).filter(|c| is_known(c)),
+        keywords,
+    }
+}
+
+fn inlines<'a>(node: &'a AstNode<'a>, formatting: &Formatting, output: &mut Vec<Inline>) {
+    let mut formatting = formatting.clone();
+
+    match node.data.borrow().value {
+        NodeValue::Text(ref literal) | NodeValue::Code(ref literal) => {
+            let text = String::from_utf8_lossy(literal).into_owned();
+            output.push(Inline::Text(text, formatting));
+            return;
+        }
+        NodeValue::SoftBreak | NodeValue::LineBreak => {
+            output.push(Inline::LineBreak);
+            return;
+        }
+        NodeValue::TaskItem(checked) => {
+            let text = if checked { "[x] " } else { "[ ] " };
+            output.push(Inline::Text(text.to_string(), formatting.clone()));
+        }
+        NodeValue::HtmlInline(_) => return,
+        NodeValue::Strong => formatting.bold = true,
+        NodeValue::Emph => formatting.italic = true,
+        NodeValue::Link(ref link) => {
+            formatting.link = Some(String::from_utf8_lossy(&link.url).into_owned());
+        }
+        _ => (),
+    }
+
+    for child in node.children() {
+        inlines(child, &formatting, output);
+    }
+}
+
+fn paragraph<'a>(node: &'a AstNode<'a>, prefix: &str) -> Vec<Inline> {
+    let mut output = Vec::new();
+
+    if !prefix.is_empty() {
+        output.push(Inline::Text(prefix.to_string(), Formatting::default()));
+    }
+    inlines(node, &Formatting::default(), &mut output);
+
+    output
+}
+
+/// Converts the markdown AST into the blocks of the document. `style` is the style of the
+/// paragraphs and `prefix` the number of an item of an ordered list.
+fn markdown_blocks<'a>(
+    node: &'a AstNode<'a>,
+    style: ParagraphStyle,
+    prefix: &str,
+    blocks: &mut Vec<Block>,
+) {
+    let value = node.data.borrow().value.clone();
+
+    match value {
+        NodeValue::Heading(heading) => {
+            let style = match heading.level {
+                1 => ParagraphStyle::Title,
+                level => ParagraphStyle::Heading(level - 1),
+            };
+            blocks.push(Block::Paragraph(style, paragraph(node, "")));
+        }
+        NodeValue::Paragraph => {
+            blocks.push(Block::Paragraph(style, paragraph(node, prefix)));
+        }
+        NodeValue::CodeBlock(code) => {
+            let literal = String::from_utf8_lossy(&code.literal).into_owned();
+            let mut output = Vec::new();
+
+            for (i, line) in literal.trim_end_matches('\n').lines().enumerate() {
+                if i > 0 {
+                    output.push(Inline::LineBreak);
+                }
+                output.push(Inline::Text(line.to_string(), Formatting::default()));
+            }
+            blocks.push(Block::Paragraph(style, output));
+        }
+        NodeValue::BlockQuote => {
+            for child in node.children() {
+                markdown_blocks(child, ParagraphStyle::Cue, "", blocks);
+            }
+        }
+        NodeValue::List(list) => {
+            let level = match style {
+                ParagraphStyle::Item { level, .. } => level + 1,
+                _ => 1,
+            };
+            let bullet = list.list_type == ListType::Bullet;
+
+            for (i, child) in node.children().enumerate() {
+                let marker = if bullet {
+                    String::new()
+                } else {
+                    format!("{}. ", list.start + i)
+                };
+                markdown_blocks(
+                    child,
+                    ParagraphStyle::Item { level, bullet },
+                    &marker,
+                    blocks,
+                );
+            }
+        }
+        NodeValue::Item(_) => {
+            for (i, child) in node.children().enumerate() {
+                let prefix = if i == 0 { prefix } else { "" };
+                markdown_blocks(child, style, prefix, blocks);
+            }
+        }
+        NodeValue::Table(_) => {
+            let rows = node
+                .children()
+                .map(|row| row.children().map(|cell| paragraph(cell, "")).collect())
+                .collect();
+            blocks.push(Block::Table(rows));
+        }
+        NodeValue::ThematicBreak => {
+            blocks.push(Block::Paragraph(ParagraphStyle::Text, Vec::new()));
+        }
+        NodeValue::HtmlBlock(_) => (),
+        _ => {
+            for child in node.children() {
+                markdown_blocks(child, style, prefix, blocks);
+            }
+        }
+    }
+}
+
+fn blocks(markdown: &str) -> Vec<Block> {
+    let arena = Arena::new();
+    let root = parse_document(&arena, markdown, &markdown_options());
+    let mut blocks = Vec::new();
+    markdown_blocks(root, ParagraphStyle::Text, "", &mut blocks);
+
+    blocks
+}
+
+type XmlWriter = EventWriter<Vec<u8>>;
+
+fn xml_error(err: xml::writer::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err))
+}
+
+fn xml_writer() -> XmlWriter {
+    EmitterConfig::new()
+        .perform_indent(false)
+        .create_writer(Vec::new())
+}
+
+/// Starts an element, attributes named like `xmlns:text` declare namespaces.
+fn start(writer: &mut XmlWriter, name: &str, attributes: &[(&str, &str)]) -> io::Result<()> {
+    let mut element = XmlEvent::start_element(name);
+
+    for (name, value) in attributes {
+        element = match name.strip_prefix("xmlns:") {
+            Some(prefix) => element.ns(prefix.to_string(), value.to_string()),
+            None => element.attr(*name, value),
+        };
+    }
+
+    writer.write(element).map_err(xml_error)
+}
+
+fn end(writer: &mut XmlWriter) -> io::Result<()> {
+    writer.write(XmlEvent::end_element()).map_err(xml_error)
+}
+
+fn characters(writer: &mut XmlWriter, text: &str) -> io::Result<()> {
+    writer.write(XmlEvent::characters(text)).map_err(xml_error)
+}
+
+fn empty_element(
+    writer: &mut XmlWriter,
+    name: &str,
+    attributes: &[(&str, &str)],
+) -> io::Result<()> {
+    start(writer, name, attributes)?;
+    end(writer)
+}
+
+fn text_element(writer: &mut XmlWriter, name: &str, text: &str) -> io::Result<()> {
+    start(writer, name, &[])?;
+    characters(writer, text)?;
+    end(writer)
+}
+
+fn add_file<W>(zip: &mut ZipWriter<W>, name: &str, content: &[u8]) -> io::Result<()>
+where
+    W: Write + io::Seek,
+{
+    zip.start_file(name, FileOptions::default())?;
+    zip.write_all(content)
+}
+
+const ODT_OFFICE: &str = "urn:oasis:names:tc:opendocument:xmlns:office:1.0";
+const ODT_STYLE: &str = "urn:oasis:names:tc:opendocument:xmlns:style:1.0";
+const ODT_TEXT: &str = "urn:oasis:names:tc:opendocument:xmlns:text:1.0";
+const ODT_TABLE: &str = "urn:oasis:names:tc:opendocument:xmlns:table:1.0";
+const ODT_FO: &str = "urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0";
+const ODT_META: &str = "urn:oasis:names:tc:opendocument:xmlns:meta:1.0";
+const XLINK: &str = "http://www.w3.org/1999/xlink";
+const DUBLIN_CORE: &str = "http://purl.org/dc/elements/1.1/";
+
+const ODT_MIMETYPE: &str = "application/vnd.oasis.opendocument.text";
+
+const ODT_MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+ <manifest:file-entry manifest:full-path="/" manifest:version="1.2" manifest:media-type="application/vnd.oasis.opendocument.text"/>
+ <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+ <manifest:file-entry manifest:full-path="styles.xml" manifest:media-type="text/xml"/>
+ <manifest:file-entry manifest:full-path="meta.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"#;
+
+/// Number of list levels with an indentation in the list styles.
+const LIST_LEVELS: usize = 10;
+
+/// Writes a paragraph style of `styles.xml` with its paragraph and text properties.
+fn odt_paragraph_style(
+    writer: &mut XmlWriter,
+    name: &str,
+    display_name: &str,
+    paragraph: &[(&str, &str)],
+    text: &[(&str, &str)],
+) -> io::Result<()> {
+    start(
+        writer,
+        "style:style",
+        &[
+            ("style:name", name),
+            ("style:display-name", display_name),
+            ("style:family", "paragraph"),
+            ("style:parent-style-name", "Standard"),
+        ],
+    )?;
+    if !paragraph.is_empty() {
+        empty_element(writer, "style:paragraph-properties", paragraph)?;
+    }
+    if !text.is_empty() {
+        empty_element(writer, "style:text-properties", text)?;
+    }
+    end(writer)
+}
+
+fn odt_list_style(writer: &mut XmlWriter, name: &str, bullet: bool) -> io::Result<()> {
+    start(writer, "text:list-style", &[("style:name", name)])?;
+
+    for level in 1..=LIST_LEVELS {
+        let level_name = level.to_string();
+        let margin = format!("{:.2}cm", 0.64 * level as f64);
+
+        if bullet {
+            start(
+                writer,
+                "text:list-level-style-bullet",
+                &[
+                    ("text:level", &level_name),
+                    ("text:bullet-char", "\u{2022}"),
+                ],
+            )?;
+        } else {
+            start(
+                writer,
+                "text:list-level-style-number",
+                &[("text:level", &level_name), ("style:num-format", "")],
+            )?;
+        }
+        start(
+            writer,
+            "style:list-level-properties",
+            &[("text:list-level-position-and-space-mode", "label-alignment")],
+        )?;
+        empty_element(
+            writer,
+            "style:list-level-label-alignment",
+            &[
+                ("text:label-followed-by", "listtab"),
+                ("text:list-tab-stop-position", &margin),
+                ("fo:text-indent", "-0.64cm"),
+                ("fo:margin-left", &margin),
+            ],
+        )?;
+        end(writer)?;
+        end(writer)?;
+    }
+
+    end(writer)
+}
+
+fn odt_styles() -> io::Result<Vec<u8>> {
+    let mut writer = xml_writer();
+
+    start(
+        &mut writer,
+        "office:document-styles",
+        &[
+            ("xmlns:office", ODT_OFFICE),
+            ("xmlns:style", ODT_STYLE),
+            ("xmlns:text", ODT_TEXT),
+            ("xmlns:fo", ODT_FO),
+            ("office:version", "1.2"),
+        ],
+    )?;
+    start(&mut writer, "office:styles", &[])?;
+
+    start(
+        &mut writer,
+        "style:style",
+        &[("style:name", "Standard"), ("style:family", "paragraph")],
+    )?;
+    empty_element(
+        &mut writer,
+        "style:paragraph-properties",
+        &[("fo:margin-top", "0cm"), ("fo:margin-bottom", "0.14cm")],
+    )?;
+    empty_element(
+        &mut writer,
+        "style:text-properties",
+        &[
+            ("style:font-name", "Liberation Sans"),
+            ("fo:font-size", "11pt"),
+        ],
+    )?;
+    end(&mut writer)?;
+
+    odt_paragraph_style(&mut writer, "Text_20_body", "Text body", &[], &[])?;
+    odt_paragraph_style(
+        &mut writer,
+        "Title",
+        "Title",
+        &[("fo:margin-bottom", "0.42cm")],
+        &[("fo:font-size", "20pt"), ("fo:font-weight", "bold")],
+    )?;
+
+    for level in 1..=3 {
+        let name = format!("Heading_20_{}", level);
+        let display_name = format!("Heading {}", level);
+        let size = format!("{}pt", 16 - 2 * level);
+        odt_paragraph_style(
+            &mut writer,
+            &name,
+            &display_name,
+            &[("fo:margin-top", "0.42cm"), ("fo:keep-with-next", "always")],
+            &[("fo:font-size", &size), ("fo:font-weight", "bold")],
+        )?;
+    }
+
+    odt_paragraph_style(&mut writer, "Cue", "Cue", &[("fo:margin-left", "1cm")], &[])?;
+    odt_paragraph_style(&mut writer, "List_20_Paragraph", "List Paragraph", &[], &[])?;
+    odt_paragraph_style(&mut writer, "Table_20_Contents", "Table Contents", &[], &[])?;
+    odt_list_style(&mut writer, "Bullets", true)?;
+    odt_list_style(&mut writer, "Numbers", false)?;
+
+    end(&mut writer)?;
+    end(&mut writer)?;
+
+    Ok(writer.into_inner())
+}
+
+/// Writes text, spaces after the first one and tabs are elements in ODT.
+fn odt_text(writer: &mut XmlWriter, text: &str) -> io::Result<()> {
+    let mut plain = String::new();
+    let mut spaces = 0;
+
+    let flush_spaces = |writer: &mut XmlWriter, spaces: usize| -> io::Result<()> {
+        let count = spaces.to_string();
+        empty_element(writer, "text:s", &[("text:c", &count)])
+    };
+
+    for c in text.chars() {
+        if c == ' ' {
+            spaces += 1;
+
+            if spaces == 1 {
+                plain.push(c);
+            }
+            continue;
+        }
+
+        if spaces > 1 || c == '\t' {
+            if !plain.is_empty() {
+                characters(writer, &plain)?;
+                plain.clear();
+            }
+            if spaces > 1 {
+                flush_spaces(writer, spaces - 1)?;
+            }
+        }
+        spaces = 0;
+
+        if c == '\t' {
+            empty_element(writer, "text:tab", &[])?;
+        } else {
+            plain.push(c);
+        }
+    }
+
+    if !plain.is_empty() {
+        characters(writer, &plain)?;
+    }
+    if spaces > 1 {
+        flush_spaces(writer, spaces - 1)?;
+    }
+
+    Ok(())
+}
+
+fn odt_inlines(writer: &mut XmlWriter, inlines: &[Inline]) -> io::Result<()> {
+    for inline in inlines {
+        match inline {
+            Inline::Text(text, formatting) => {
+                if let Some(href) = formatting.link.as_ref() {
+                    start(
+                        writer,
+                        "text:a",
+                        &[("xlink:type", "simple"), ("xlink:href", href)],
+                    )?;
+                }
+
+                let style = match (formatting.bold, formatting.italic) {
+                    (true, false) => Some("Bold"),
+                    (false, true) => Some("Italic"),
+                    (true, true) => Some("Bold_Italic"),
+                    (false, false) => None,
+                };
+
+                match style {
+                    Some(style) => {
+                        start(writer, "text:span", &[("text:style-name", style)])?;
+                        odt_text(writer, text)?;
+                        end(writer)?;
+                    }
+                    None => odt_text(writer, text)?,
+                }
+
+                if formatting.link.is_some() {
+                    end(writer)?;
+                }
+            }
+            Inline::LineBreak => empty_element(writer, "text:line-break", &[])?,
+        }
+    }
+
+    Ok(())
+}
+
+fn odt_paragraph(
+    writer: &mut XmlWriter,
+    style: ParagraphStyle,
+    inlines: &[Inline],
+) -> io::Result<()> {
+    match style {
+        ParagraphStyle::Heading(level) => {
+            let level = level.min(3).to_string();
+            let style_name = format!("Heading_20_{}", level);
+            start(
+                writer,
+                "text:h",
+                &[
+                    ("text:style-name", &style_name),
+                    ("text:outline-level", &level),
+                ],
+            )?;
+        }
+        ParagraphStyle::Item { level, bullet } => {
+            let list_style = if bullet { "Bullets" } else { "Numbers" };
+
+            for _ in 0..level {
+                start(writer, "text:list", &[("text:style-name", list_style)])?;
+                start(writer, "text:list-item", &[])?;
+            }
+            start(
+                writer,
+                "text:p",
+                &[("text:style-name", "List_20_Paragraph")],
+            )?;
+        }
+        style => {
+            let style_name = match style {
+                ParagraphStyle::Title => "Title",
+                ParagraphStyle::Cue => "Cue",
+                _ => "Text_20_body",
+            };
+            start(writer, "text:p", &[("text:style-name", style_name)])?;
+        }
+    }
+
+    odt_inlines(writer, inlines)?;
+    end(writer)?;
+
+    if let ParagraphStyle::Item { level, .. } = style {
+        for _ in 0..(2 * level) {
+            end(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn odt_table(writer: &mut XmlWriter, rows: &[Vec<Vec<Inline>>], number: usize) -> io::Result<()> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let name = format!("Table{}", number);
+    let columns_count = columns.to_string();
+
+    start(
+        writer,
+        "table:table",
+        &[("table:name", &name), ("table:style-name", "Table")],
+    )?;
+    empty_element(
+        writer,
+        "table:table-column",
+        &[("table:number-columns-repeated", &columns_count)],
+    )?;
+
+    for (i, row) in rows.iter().enumerate() {
+        start(writer, "table:table-row", &[])?;
+
+        for column in 0..columns {
+            start(
+                writer,
+                "table:table-cell",
+                &[
+                    ("table:style-name", "Cell"),
+                    ("office:value-type", "string"),
+                ],
+            )?;
+            start(
+                writer,
+                "text:p",
+                &[("text:style-name", "Table_20_Contents")],
+            )?;
+
+            if let Some(cell) = row.get(column) {
+                if i == 0 {
+                    start(writer, "text:span", &[("text:style-name", "Bold")])?;
+                    odt_inlines(writer, cell)?;
+                    end(writer)?;
+                } else {
+                    odt_inlines(writer, cell)?;
+                }
+            }
+            end(writer)?;
+            end(writer)?;
+        }
+        end(writer)?;
+    }
+
+    end(writer)
+}
+
+fn odt_text_style(
+    writer: &mut XmlWriter,
+    name: &str,
+    properties: &[(&str, &str)],
+) -> io::Result<()> {
+    start(
+        writer,
+        "style:style",
+        &[("style:name", name), ("style:family", "text")],
+    )?;
+    empty_element(writer, "style:text-properties", properties)?;
+    end(writer)
+}
+
+fn odt_content(blocks: &[Block]) -> io::Result<Vec<u8>> {
+    let mut writer = xml_writer();
+
+    start(
+        &mut writer,
+        "office:document-content",
+        &[
+            ("xmlns:office", ODT_OFFICE),
+            ("xmlns:style", ODT_STYLE),
+            ("xmlns:text", ODT_TEXT),
+            ("xmlns:table", ODT_TABLE),
+            ("xmlns:fo", ODT_FO),
+            ("xmlns:xlink", XLINK),
+            ("office:version", "1.2"),
+        ],
+    )?;
+
+    start(&mut writer, "office:automatic-styles", &[])?;
+    odt_text_style(&mut writer, "Bold", &[("fo:font-weight", "bold")])?;
+    odt_text_style(&mut writer, "Italic", &[("fo:font-style", "italic")])?;
+    odt_text_style(
+        &mut writer,
+        "Bold_Italic",
+        &[("fo:font-weight", "bold"), ("fo:font-style", "italic")],
+    )?;
+    start(
+        &mut writer,
+        "style:style",
+        &[("style:name", "Table"), ("style:family", "table")],
+    )?;
+    empty_element(
+        &mut writer,
+        "style:table-properties",
+        &[("style:width", "17cm"), ("table:align", "margins")],
+    )?;
+    end(&mut writer)?;
+    start(
+        &mut writer,
+        "style:style",
+        &[("style:name", "Cell"), ("style:family", "table-cell")],
+    )?;
+    empty_element(
+        &mut writer,
+        "style:table-cell-properties",
+        &[
+            ("fo:padding", "0.1cm"),
+            ("fo:border", "0.5pt solid #000000"),
+        ],
+    )?;
+    end(&mut writer)?;
+    end(&mut writer)?;
+
+    start(&mut writer, "office:body", &[])?;
+    start(&mut writer, "office:text", &[])?;
+
+    let mut tables = 0;
+
+    for block in blocks {
+        match block {
+            Block::Paragraph(style, inlines) => odt_paragraph(&mut writer, *style, inlines)?,
+            Block::Table(rows) => {
+                tables += 1;
+                odt_table(&mut writer, rows, tables)?;
+            }
+        }
+    }
+
+    end(&mut writer)?;
+    end(&mut writer)?;
+    end(&mut writer)?;
+
+    Ok(writer.into_inner())
+}
+
+fn odt_meta(properties: &Properties) -> io::Result<Vec<u8>> {
+    let mut writer = xml_writer();
+
+    start(
+        &mut writer,
+        "office:document-meta",
+        &[
+            ("xmlns:office", ODT_OFFICE),
+            ("xmlns:meta", ODT_META),
+            ("xmlns:dc", DUBLIN_CORE),
+            ("office:version", "1.2"),
+        ],
+    )?;
+    start(&mut writer, "office:meta", &[])?;
+    text_element(&mut writer, "meta:generator", "Cuer Manager")?;
+    text_element(&mut writer, "dc:title", &properties.title)?;
+
+    if let Some(author) = properties.author.as_ref() {
+        text_element(&mut writer, "meta:initial-creator", author)?;
+        text_element(&mut writer, "dc:creator", author)?;
+    }
+    for keyword in &properties.keywords {
+        text_element(&mut writer, "meta:keyword", keyword)?;
+    }
+
+    end(&mut writer)?;
+    end(&mut writer)?;
+
+    Ok(writer.into_inner())
+}
+
+fn to_odt(blocks: &[Block], properties: &Properties) -> io::Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    // the media type has to be the first file and uncompressed to identify the document
+    zip.start_file(
+        "mimetype",
+        FileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(ODT_MIMETYPE.as_bytes())?;
+
+    add_file(&mut zip, "META-INF/manifest.xml", ODT_MANIFEST.as_bytes())?;
+    add_file(&mut zip, "content.xml", &odt_content(blocks)?)?;
+    add_file(&mut zip, "styles.xml", &odt_styles()?)?;
+    add_file(&mut zip, "meta.xml", &odt_meta(properties)?)?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+const WORD_MAIN: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const WORD_RELATIONSHIPS: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const PACKAGE_RELATIONSHIPS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+const HYPERLINK_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink";
+const CORE_PROPERTIES: &str =
+    "http://schemas.openxmlformats.org/package/2006/metadata/core-properties";
+
+const DOCX_CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+ <Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+ <Default Extension="xml" ContentType="application/xml"/>
+ <Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>
+ <Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/>
+ <Override PartName="/word/numbering.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml"/>
+ <Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/>
+</Types>
+"#;
+
+const DOCX_RELATIONSHIPS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+ <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+ <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/>
+</Relationships>
+"#;
+
+const DOCX_STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+ <w:docDefaults>
+  <w:rPrDefault><w:rPr><w:rFonts w:ascii="Calibri" w:hAnsi="Calibri" w:cs="Calibri"/><w:sz w:val="22"/></w:rPr></w:rPrDefault>
+  <w:pPrDefault><w:pPr><w:spacing w:after="80"/></w:pPr></w:pPrDefault>
+ </w:docDefaults>
+ <w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:qFormat/></w:style>
+ <w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:after="240"/></w:pPr><w:rPr><w:b/><w:sz w:val="40"/></w:rPr></w:style>
+ <w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="240"/><w:outlineLvl w:val="0"/></w:pPr><w:rPr><w:b/><w:sz w:val="28"/></w:rPr></w:style>
+ <w:style w:type="paragraph" w:styleId="Heading2"><w:name w:val="heading 2"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="200"/><w:outlineLvl w:val="1"/></w:pPr><w:rPr><w:b/><w:sz w:val="24"/></w:rPr></w:style>
+ <w:style w:type="paragraph" w:styleId="Heading3"><w:name w:val="heading 3"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="160"/><w:outlineLvl w:val="2"/></w:pPr><w:rPr><w:b/></w:rPr></w:style>
+ <w:style w:type="paragraph" w:styleId="Cue"><w:name w:val="Cue"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:ind w:left="567"/></w:pPr></w:style>
+ <w:style w:type="paragraph" w:styleId="ListParagraph"><w:name w:val="List Paragraph"/><w:basedOn w:val="Normal"/><w:qFormat/></w:style>
+ <w:style w:type="character" w:styleId="Hyperlink"><w:name w:val="Hyperlink"/><w:rPr><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr></w:style>
+ <w:style w:type="table" w:styleId="TableGrid"><w:name w:val="Table Grid"/><w:tblPr><w:tblBorders><w:top w:val="single" w:sz="4" w:space="0" w:color="000000"/><w:left w:val="single" w:sz="4" w:space="0" w:color="000000"/><w:bottom w:val="single" w:sz="4" w:space="0" w:color="000000"/><w:right w:val="single" w:sz="4" w:space="0" w:color="000000"/><w:insideH w:val="single" w:sz="4" w:space="0" w:color="000000"/><w:insideV w:val="single" w:sz="4" w:space="0" w:color="000000"/></w:tblBorders><w:tblCellMar><w:left w:w="57" w:type="dxa"/><w:right w:w="57" w:type="dxa"/></w:tblCellMar></w:tblPr></w:style>
+</w:styles>
+"#;
+
+/// Width of the text on an A4 page with 2 cm margins, in twentieths of a point.
+const DOCX_TEXT_WIDTH: usize = 10773;
+
+fn docx_numbering() -> io::Result<Vec<u8>> {
+    let mut writer = xml_writer();
+
+    start(&mut writer, "w:numbering", &[("xmlns:w", WORD_MAIN)])?;
+    start(&mut writer, "w:abstractNum", &[("w:abstractNumId", "0")])?;
+
+    // Word supports nine levels
+    for level in 0..9 {
+        let level_name = level.to_string();
+        let indent = (360 * (level + 1)).to_string();
+
+        start(&mut writer, "w:lvl", &[("w:ilvl", &level_name)])?;
+        empty_element(&mut writer, "w:start", &[("w:val", "1")])?;
+        empty_element(&mut writer, "w:numFmt", &[("w:val", "bullet")])?;
+        empty_element(&mut writer, "w:lvlText", &[("w:val", "\u{2022}")])?;
+        empty_element(&mut writer, "w:lvlJc", &[("w:val", "left")])?;
+        start(&mut writer, "w:pPr", &[])?;
+        empty_element(
+            &mut writer,
+            "w:ind",
+            &[("w:left", &indent), ("w:hanging", "360")],
+        )?;
+        end(&mut writer)?;
+        end(&mut writer)?;
+    }
+
+    end(&mut writer)?;
+    start(&mut writer, "w:num", &[("w:numId", "1")])?;
+    empty_element(&mut writer, "w:abstractNumId", &[("w:val", "0")])?;
+    end(&mut writer)?;
+    end(&mut writer)?;
+
+    Ok(writer.into_inner())
+}
+
+/// The body of a DOCX document with the targets of its links, which are relationships of the
+/// document.
+struct DocxBody {
+    writer: XmlWriter,
+    links: Vec<String>,
+}
+
+impl DocxBody {
+    /// Returns the relationship of a link, the first ones are the styles and the numbering.
+    fn link(&mut self, href: &str) -> String {
+        let index = match self.links.iter().position(|link| link == href) {
+            Some(index) => index,
+            None => {
+                self.links.push(href.to_string());
+                self.links.len() - 1
+            }
+        };
+
+        format!("rId{}", index + 3)
+    }
+
+    fn run(&mut self, text: &str, formatting: &Formatting, bold: bool) -> io::Result<()> {
+        let writer = &mut self.writer;
+
+        start(writer, "w:r", &[])?;
+
+        if formatting.bold || formatting.italic || formatting.link.is_some() || bold {
+            start(writer, "w:rPr", &[])?;
+            if formatting.link.is_some() {
+                empty_element(writer, "w:rStyle", &[("w:val", "Hyperlink")])?;
+            }
+            if formatting.bold || bold {
+                empty_element(writer, "w:b", &[])?;
+            }
+            if formatting.italic {
+                empty_element(writer, "w:i", &[])?;
+            }
+            end(writer)?;
+        }
+
+        for (i, part) in text.split('\t').enumerate() {
+            if i > 0 {
+                empty_element(writer, "w:tab", &[])?;
+            }
+            if !part.is_empty() {
+                start(writer, "w:t", &[("xml:space", "preserve")])?;
+                characters(writer, part)?;
+                end(writer)?;
+            }
+        }
+
+        end(writer)
+    }
+
+    fn inlines(&mut self, inlines: &[Inline], bold: bool) -> io::Result<()> {
+        for inline in inlines {
+            match inline {
+                Inline::Text(text, formatting) => match formatting.link.as_ref() {
+                    Some(href) => {
+                        let id = self.link(href);
+                        start(&mut self.writer, "w:hyperlink", &[("r:id", &id)])?;
+                        self.run(text, formatting, bold)?;
+                        end(&mut self.writer)?;
+                    }
+                    None => self.run(text, formatting, bold)?,
+                },
+                Inline::LineBreak => {
+                    start(&mut self.writer, "w:r", &[])?;
+                    empty_element(&mut self.writer, "w:br", &[])?;
+                    end(&mut self.writer)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn paragraph(&mut self, style: ParagraphStyle, inlines: &[Inline]) -> io::Result<()> {
+        let writer = &mut self.writer;
+
+        start(writer, "w:p", &[])?;
+
+        match style {
+            ParagraphStyle::Title => {
+                start(writer, "w:pPr", &[])?;
+                empty_element(writer, "w:pStyle", &[("w:val", "Title")])?;
+                end(writer)?;
+            }
+            ParagraphStyle::Heading(level) => {
+                let style_name = format!("Heading{}", level.min(3));
+                start(writer, "w:pPr", &[])?;
+                empty_element(writer, "w:pStyle", &[("w:val", &style_name)])?;
+                end(writer)?;
+            }
+            ParagraphStyle::Cue => {
+                start(writer, "w:pPr", &[])?;
+                empty_element(writer, "w:pStyle", &[("w:val", "Cue")])?;
+                end(writer)?;
+            }
+            ParagraphStyle::Item { level, bullet } => {
+                start(writer, "w:pPr", &[])?;
+                empty_element(writer, "w:pStyle", &[("w:val", "ListParagraph")])?;
+
+                if bullet {
+                    let level = (level.min(9) - 1).to_string();
+                    start(writer, "w:numPr", &[])?;
+                    empty_element(writer, "w:ilvl", &[("w:val", &level)])?;
+                    empty_element(writer, "w:numId", &[("w:val", "1")])?;
+                    end(writer)?;
+                } else {
+                    let indent = (360 * level).to_string();
+                    empty_element(writer, "w:ind", &[("w:left", &indent)])?;
+                }
+                end(writer)?;
+            }
+            ParagraphStyle::Text => (),
+        }
+
+        self.inlines(inlines, false)?;
+        end(&mut self.writer)
+    }
+
+    fn table(&mut self, rows: &[Vec<Vec<Inline>>]) -> io::Result<()> {
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        let width = (DOCX_TEXT_WIDTH / columns).to_string();
+        let writer = &mut self.writer;
+
+        start(writer, "w:tbl", &[])?;
+        start(writer, "w:tblPr", &[])?;
+        empty_element(writer, "w:tblStyle", &[("w:val", "TableGrid")])?;
+        empty_element(writer, "w:tblW", &[("w:w", "0"), ("w:type", "auto")])?;
+        end(writer)?;
+        start(writer, "w:tblGrid", &[])?;
+        for _ in 0..columns {
+            empty_element(writer, "w:gridCol", &[("w:w", &width)])?;
+        }
+        end(writer)?;
+
+        for (i, row) in rows.iter().enumerate() {
+            start(&mut self.writer, "w:tr", &[])?;
+
+            for column in 0..columns {
+                start(&mut self.writer, "w:tc", &[])?;
+                start(&mut self.writer, "w:tcPr", &[])?;
+                empty_element(
+                    &mut self.writer,
+                    "w:tcW",
+                    &[("w:w", &width), ("w:type", "dxa")],
+                )?;
+                end(&mut self.writer)?;
+                start(&mut self.writer, "w:p", &[])?;
+                if let Some(cell) = row.get(column) {
+                    self.inlines(cell, i == 0)?;
+                }
+                end(&mut self.writer)?;
+                end(&mut self.writer)?;
+            }
+            end(&mut self.writer)?;
+        }
+
+        end(&mut self.writer)?;
+
+        // Word expects a paragraph after every table
+        empty_element(&mut self.writer, "w:p", &[])
+    }
+}
+
+fn docx_document(blocks: &[Block]) -> io::Result<(Vec<u8>, Vec<String>)> {
+    let mut body = DocxBody {
+        writer: xml_writer(),
+        links: Vec::new(),
+    };
+
+    start(
+        &mut body.writer,
+        "w:document",
+        &[("xmlns:w", WORD_MAIN), ("xmlns:r", WORD_RELATIONSHIPS)],
+    )?;
+    start(&mut body.writer, "w:body", &[])?;
+
+    for block in blocks {
+        match block {
+            Block::Paragraph(style, inlines) => body.paragraph(*style, inlines)?,
+            Block::Table(rows) => body.table(rows)?,
+        }
+    }
+
+    // A4 with margins of 2 cm
+    let writer = &mut body.writer;
+    start(writer, "w:sectPr", &[])?;
+    empty_element(writer, "w:pgSz", &[("w:w", "11906"), ("w:h", "16838")])?;
+    empty_element(
+        writer,
+        "w:pgMar",
+        &[
+            ("w:top", "1134"),
+            ("w:right", "1134"),
+            ("w:bottom", "1134"),
+            ("w:left", "1134"),
+            ("w:header", "709"),
+            ("w:footer", "709"),
+            ("w:gutter", "0"),
+        ],
+    )?;
+    end(writer)?;
+    end(writer)?;
+    end(writer)?;
+
+    Ok((body.writer.into_inner(), body.links))
+}
+
+fn docx_document_relationships(links: &[String]) -> io::Result<Vec<u8>> {
+    let mut writer = xml_writer();
+
+    start(
+        &mut writer,
+        "Relationships",
+        &[("xmlns", PACKAGE_RELATIONSHIPS)],
+    )?;
+    empty_element(
+        &mut writer,
+        "Relationship",
+        &[
+            ("Id", "rId1"),
+            (
+                "Type",
+                "http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles",
+            ),
+            ("Target", "styles.xml"),
+        ],
+    )?;
+    empty_element(
+        &mut writer,
+        "Relationship",
+        &[
+            ("Id", "rId2"),
+            (
+                "Type",
+                "http://schemas.openxmlformats.org/officeDocument/2006/relationships/numbering",
+            ),
+            ("Target", "numbering.xml"),
+        ],
+    )?;
+
+    for (i, link) in links.iter().enumerate() {
+        let id = format!("rId{}", i + 3);
+        empty_element(
+            &mut writer,
+            "Relationship",
+            &[
+                ("Id", &id),
+                ("Type", HYPERLINK_RELATIONSHIP),
+                ("Target", link),
+                ("TargetMode", "External"),
+            ],
+        )?;
+    }
+
+    end(&mut writer)?;
+
+    Ok(writer.into_inner())
+}
+
+fn docx_core(properties: &Properties) -> io::Result<Vec<u8>> {
+    let mut writer = xml_writer();
+
+    start(
+        &mut writer,
+        "cp:coreProperties",
+        &[("xmlns:cp", CORE_PROPERTIES), ("xmlns:dc", DUBLIN_CORE)],
+    )?;
+    text_element(&mut writer, "dc:title", &properties.title)?;
+    if let Some(author) = properties.author.as_ref() {
+        text_element(&mut writer, "dc:creator", author)?;
+    }
+    if !properties.keywords.is_empty() {
+        text_element(&mut writer, "cp:keywords", &properties.keywords.join(", "))?;
+    }
+    end(&mut writer)?;
+
+    Ok(writer.into_inner())
+}
+
+fn to_docx(blocks: &[Block], properties: &Properties) -> io::Result<Vec<u8>> {
+    let (document, links) = docx_document(blocks)?;
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    add_file(
+        &mut zip,
+        "[Content_Types].xml",
+        DOCX_CONTENT_TYPES.as_bytes(),
+    )?;
+    add_file(&mut zip, "_rels/.rels", DOCX_RELATIONSHIPS.as_bytes())?;
+    add_file(&mut zip, "word/document.xml", &document)?;
+    add_file(
+        &mut zip,
+        "word/_rels/document.xml.rels",
+        &docx_document_relationships(&links)?,
+    )?;
+    add_file(&mut zip, "word/styles.xml", DOCX_STYLES.as_bytes())?;
+    add_file(&mut zip, "word/numbering.xml", &docx_numbering()?)?;
+    add_file(&mut zip, "docProps/core.xml", &docx_core(properties)?)?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Renders a cue card with its metadata as a document.
+pub fn export(cuecard: &Cuecard, format: Format) -> io::Result<Vec<u8>> {
+    let blocks = blocks(&with_metadata(cuecard));
+    let properties = properties(cuecard);
+
+    match format {
+        Format::Odt => to_odt(&blocks, &properties),
+        Format::Docx => to_docx(&blocks, &properties),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cuecard(content: &str) -> Cuecard {
+        Cuecard {
+            id: 1,
+            uuid: "card".to_string(),
+            phase: "IV".to_string(),
+            rhythm: "Waltz".to_string(),
+            title: "Moon River".to_string(),
+            steplevel: "".to_string(),
+            difficulty: "".to_string(),
+            choreographer: "Jane Doe".to_string(),
+            meta: r#"{"plusfigures":"+1","music":"Moon River"}"#.to_string(),
+            content: content.to_string(),
+            karaoke_marks: "".to_string(),
+            music_file: "".to_string(),
+            file_path: "m/Moon River.md".to_string(),
+            date_created: "2019-11-01T10:00:00.000Z".to_string(),
+            date_modified: "2019-11-01T10:00:00.000Z".to_string(),
+        }
+    }
+
+    fn convert(document: Vec<u8>) -> String {
+        let mut markdown = Vec::new();
+        cuecard_indexer::convert::convert_to_markdown(&mut Cursor::new(document), &mut markdown)
+            .unwrap();
+
+        String::from_utf8(markdown).unwrap()
+    }
+
+    #[test]
+    fn test_with_metadata() {
+        let content = "# Moon River\n\n* **Phase**: IV+1\n\nINTRO\n";
+
+        assert_eq!(
+            with_metadata(&cuecard(content)),
+            "# Moon River\n\n* **Choreographer**: Jane Doe\n* **Music**: Moon River\n\
+             * **Rhythm**: Waltz\n\n* **Phase**: IV+1\n\nINTRO"
+        );
+        assert_eq!(
+            with_metadata(&Cuecard {
+                choreographer: "unknown".to_string(),
+                rhythm: "unknown".to_string(),
+                meta: "{}".to_string(),
+                ..cuecard(content)
+            }),
+            content
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let blocks = blocks("# Title\n\n## Part A\n\n> **1-4** Waltz *away*\n\n* one\n  1. two\n");
+
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Paragraph(
+                    ParagraphStyle::Title,
+                    vec![Inline::Text("Title".to_string(), Formatting::default())]
+                ),
+                Block::Paragraph(
+                    ParagraphStyle::Heading(1),
+                    vec![Inline::Text("Part A".to_string(), Formatting::default())]
+                ),
+                Block::Paragraph(
+                    ParagraphStyle::Cue,
+                    vec![
+                        Inline::Text(
+                            "1-4".to_string(),
+                            Formatting {
+                                bold: true,
+                                ..Formatting::default()
+                            }
+                        ),
+                        Inline::Text(" Waltz ".to_string(), Formatting::default()),
+                        Inline::Text(
+                            "away".to_string(),
+                            Formatting {
+                                italic: true,
+                                ..Formatting::default()
+                            }
+                        ),
+                    ]
+                ),
+                Block::Paragraph(
+                    ParagraphStyle::Item {
+                        level: 1,
+                        bullet: true
+                    },
+                    vec![Inline::Text("one".to_string(), Formatting::default())]
+                ),
+                Block::Paragraph(
+                    ParagraphStyle::Item {
+                        level: 2,
+                        bullet: false
+                    },
+                    vec![
+                        Inline::Text("1. ".to_string(), Formatting::default()),
+                        Inline::Text("two".to_string(), Formatting::default())
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let content = "# Moon River\n\n## Part A\n\n1-4 **Waltz** *away*  and  back\n";
+
+        for format in &[Format::Odt, Format::Docx] {
+            let markdown = convert(export(&cuecard(content), *format).unwrap());
+            let lines = markdown
+                .lines()
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<_>>();
+            // the converter only reads the formatting of ODT documents
+            let cues = match format {
+                Format::Odt => "1-4 **Waltz** *away*  and  back",
+                Format::Docx => "1-4 Waltz away  and  back",
+            };
+
+            assert_eq!(
+                lines,
+                vec![
+                    "# Moon River",
+                    "* **Choreographer**: Jane Doe",
+                    "* **Music**: Moon River",
+                    "* **Rhythm**: Waltz",
+                    "* **Phase**: IV+1",
+                    "# Part A",
+                    cues,
+                ],
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn test_odt_package() {
+        let document = export(&cuecard("# Moon River"), Format::Odt).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(document)).unwrap();
+
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+        drop(mimetype);
+
+        let mut meta = String::new();
+        io::Read::read_to_string(&mut archive.by_name("meta.xml").unwrap(), &mut meta).unwrap();
+        assert!(meta.contains("<dc:title>Moon River</dc:title>"));
+        assert!(meta.contains("<meta:keyword>Waltz</meta:keyword>"));
+    }
+}
@@ -0,0 +1,292 @@
+/**
+
+Generation of program drafts.
+
+Cue cards matching the requested phases and rhythms are distributed over a number of tips.
+Dances which haven't been cued for the longest time are preferred, and the rhythm of a dance
+always differs from the one before if there is any choice. Tips are filled until the music of
+their cue cards no longer fits into the time slot.
+
+**/
+use chrono::{DateTime, Duration, FixedOffset};
+use cuer_database::models::Cuecard;
+
+use std::collections::HashMap;
+
+/// Assumed playing time of a cue card without a measurable music file
+pub const DEFAULT_TRACK_SECONDS: u32 = 180;
+
+/// Number of least recently cued candidates considered for balancing the rhythms
+const WINDOW: usize = 8;
+
+/// Largest number of tips generated for one draft
+pub const MAX_TIPS: u32 = 50;
+
+/// Longest time slot of a generated tip
+pub const MAX_MINUTES_PER_TIP: u32 = 24 * 60;
+
+#[derive(Serialize, Deserialize)]
+pub struct GeneratorOptions {
+    pub min_phase: Option<String>,
+    pub max_phase: Option<String>,
+    /// Only cue cards of these rhythms are used, all rhythms if empty
+    #[serde(default)]
+    pub include_rhythms: Vec<String>,
+    #[serde(default)]
+    pub exclude_rhythms: Vec<String>,
+    /// Number of tips, between 1 and `MAX_TIPS`
+    pub tips: u32,
+    /// Defaults to the configured minutes per tip
+    pub minutes_per_tip: Option<u32>,
+}
+
+/// Checks the number of tips and their length against the limits.
+pub fn validate(options: &GeneratorOptions) -> Result<(), String> {
+    if !(1..=MAX_TIPS).contains(&options.tips) {
+        return Err(format!("Number of tips must be between 1 and {}", MAX_TIPS));
+    }
+
+    if let Some(minutes) = options.minutes_per_tip {
+        if !(1..=MAX_MINUTES_PER_TIP).contains(&minutes) {
+            return Err(format!(
+                "Minutes per tip must be between 1 and {}",
+                MAX_MINUTES_PER_TIP
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// A cue card which may be placed in the draft.
+pub struct Candidate {
+    pub cuecard: Cuecard,
+    pub last_cued: Option<String>,
+    pub duration: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DraftTip {
+    pub name: String,
+    pub date_start: String,
+    pub date_end: String,
+    pub cuecards: Vec<Cuecard>,
+}
+
+/// Returns the numeric level of a phase like `IV` or `IV+2`.
+pub fn phase_level(phase: &str) -> Option<u32> {
+    let phase = phase.trim();
+
+    if let Some(digit) = phase.chars().next().and_then(|c| c.to_digit(10)) {
+        return Some(digit);
+    }
+
+    let numeral: String = phase
+        .chars()
+        .take_while(|c| "IVXivx".contains(*c))
+        .collect();
+
+    match numeral.to_uppercase().as_ref() {
+        "I" => Some(1),
+        "II" => Some(2),
+        "III" => Some(3),
+        "IV" => Some(4),
+        "V" => Some(5),
+        "VI" => Some(6),
+        _ => None,
+    }
+}
+
+fn contains_rhythm(rhythms: &[String], rhythm: &str) -> bool {
+    rhythms.iter().any(|r| r.eq_ignore_ascii_case(rhythm.trim()))
+}
+
+/// Checks the phase and rhythm of a cue card against the options.
+pub fn matches(options: &GeneratorOptions, cuecard: &Cuecard) -> bool {
+    let level = phase_level(&cuecard.phase);
+    let min = options.min_phase.as_ref().and_then(|p| phase_level(p));
+    let max = options.max_phase.as_ref().and_then(|p| phase_level(p));
+
+    let in_range = match (level, min, max) {
+        (_, None, None) => true,
+        (None, _, _) => false,
+        (Some(level), min, max) => {
+            min.is_none_or(|min| level >= min) && max.is_none_or(|max| level <= max)
+        }
+    };
+
+    in_range
+        && (options.include_rhythms.is_empty()
+            || contains_rhythm(&options.include_rhythms, &cuecard.rhythm))
+        && !contains_rhythm(&options.exclude_rhythms, &cuecard.rhythm)
+}
+
+/// Picks the next cue card from the least recently cued `candidates`, preferring rhythms
+/// used least so far and never repeating the previous rhythm if avoidable.
+fn pick(
+    candidates: &[Candidate],
+    previous: Option<&str>,
+    used: &HashMap<String, usize>,
+) -> Option<usize> {
+    let window = std::cmp::min(WINDOW, candidates.len());
+    let rhythm = |i: usize| candidates[i].cuecard.rhythm.trim().to_lowercase();
+    let differs = |i: &usize| previous.is_none_or(|p| rhythm(*i) != p);
+
+    (0..window)
+        .filter(differs)
+        .min_by_key(|i| (used.get(&rhythm(*i)).cloned().unwrap_or(0), *i))
+        .or_else(|| (0..candidates.len()).find(differs))
+        .or(if candidates.is_empty() { None } else { Some(0) })
+}
+
+/// Generates tips starting at `start`, numbered from `first_number`.
+pub fn generate(
+    options: &GeneratorOptions,
+    mut candidates: Vec<Candidate>,
+    start: DateTime<FixedOffset>,
+    first_number: usize,
+    minutes_per_tip: u32,
+    gap_seconds: u32,
+) -> Vec<DraftTip> {
+    candidates.retain(|c| matches(options, &c.cuecard));
+    // never cued cards first, then the ones cued longest ago
+    candidates.sort_by(|a, b| {
+        a.last_cued
+            .cmp(&b.last_cued)
+            .then_with(|| a.cuecard.title.cmp(&b.cuecard.title))
+    });
+
+    let minutes = i64::from(options.minutes_per_tip.unwrap_or(minutes_per_tip));
+    let slot_seconds = minutes * 60;
+
+    let mut used: HashMap<String, usize> = HashMap::new();
+    let mut previous: Option<String> = None;
+    let mut tips = Vec::new();
+
+    for n in 0..options.tips {
+        let tip_start = start + Duration::minutes(i64::from(n) * minutes);
+        let mut cuecards = Vec::new();
+        let mut planned: i64 = 0;
+
+        while let Some(i) = pick(&candidates, previous.as_deref(), &used) {
+            let duration = i64::from(candidates[i].duration.unwrap_or(DEFAULT_TRACK_SECONDS));
+            let needed = if cuecards.is_empty() {
+                duration
+            } else {
+                duration + i64::from(gap_seconds)
+            };
+
+            if !cuecards.is_empty() && planned + needed > slot_seconds {
+                break;
+            }
+
+            let candidate = candidates.remove(i);
+            let rhythm = candidate.cuecard.rhythm.trim().to_lowercase();
+            *used.entry(rhythm.clone()).or_insert(0) += 1;
+            previous = Some(rhythm);
+            planned += needed;
+            cuecards.push(candidate.cuecard);
+        }
+
+        tips.push(DraftTip {
+            name: format!("Tip {}", first_number + n as usize),
+            date_start: tip_start.to_rfc3339(),
+            date_end: (tip_start + Duration::minutes(minutes)).to_rfc3339(),
+            cuecards,
+        });
+    }
+
+    tips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(title: &str, rhythm: &str, phase: &str, last_cued: Option<&str>) -> Candidate {
+        Candidate {
+            cuecard: Cuecard {
+                id: 0,
+                uuid: title.to_string(),
+                phase: phase.to_string(),
+                rhythm: rhythm.to_string(),
+                title: title.to_string(),
+                steplevel: String::new(),
+                difficulty: String::new(),
+                choreographer: String::new(),
+                meta: String::new(),
+                content: String::new(),
+                karaoke_marks: String::new(),
+                music_file: String::new(),
+                file_path: String::new(),
+                date_created: String::new(),
+                date_modified: String::new(),
+            },
+            last_cued: last_cued.map(str::to_string),
+            duration: Some(180),
+        }
+    }
+
+    fn options() -> GeneratorOptions {
+        GeneratorOptions {
+            min_phase: Some("III".to_string()),
+            max_phase: Some("IV".to_string()),
+            include_rhythms: Vec::new(),
+            exclude_rhythms: vec!["Jive".to_string()],
+            tips: 2,
+            minutes_per_tip: Some(10),
+        }
+    }
+
+    #[test]
+    fn test_phase_level() {
+        assert_eq!(phase_level("IV+2"), Some(4));
+        assert_eq!(phase_level("ii"), Some(2));
+        assert_eq!(phase_level("5"), Some(5));
+        assert_eq!(phase_level("unphased"), None);
+    }
+
+    #[test]
+    fn test_generate() {
+        let candidates = vec![
+            candidate("Waltz A", "Waltz", "IV", Some("2019-10-01")),
+            candidate("Waltz B", "Waltz", "III+1", None),
+            candidate("Waltz C", "waltz", "IV", Some("2019-01-01")),
+            candidate("Rumba", "Rumba", "IV", Some("2019-06-01")),
+            candidate("Foxtrot", "Foxtrot", "IV", Some("2019-11-01")),
+            candidate("Jive", "Jive", "IV", None),
+            candidate("Cha", "Cha", "VI", None),
+        ];
+        let start = DateTime::parse_from_rfc3339("2019-11-01T19:00:00+01:00").unwrap();
+
+        let tips = generate(&options(), candidates, start, 3, 15, 30);
+
+        assert_eq!(tips.len(), 2);
+        assert_eq!(tips[0].name, "Tip 3");
+        assert_eq!(tips[1].date_start, "2019-11-01T19:10:00+01:00");
+        assert_eq!(tips[1].date_end, "2019-11-01T19:20:00+01:00");
+
+        let titles: Vec<&str> = tips
+            .iter()
+            .flat_map(|t| t.cuecards.iter().map(|c| c.title.as_str()))
+            .collect();
+        // three dances of 3 minutes plus gaps fit into 10 minutes
+        assert_eq!(tips[0].cuecards.len(), 3);
+        assert_eq!(titles, vec!["Waltz B", "Rumba", "Foxtrot", "Waltz C", "Waltz A"]);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&options()).is_ok());
+
+        let mut options = options();
+        options.tips = 0;
+        assert!(validate(&options).is_err());
+        options.tips = MAX_TIPS + 1;
+        assert!(validate(&options).is_err());
+
+        options.tips = MAX_TIPS;
+        options.minutes_per_tip = Some(u32::max_value());
+        assert!(validate(&options).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use chrono::DateTime;
+use cuecard_indexer::placement::PlacementTemplate;
 use rocket::Request;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome};
@@ -6,9 +8,13 @@ pub struct BackendConfig {
     pub music_files_dir: String,
     pub indexer_path: String,
     pub cuecards_lib_dir: String,
+    pub derived_files_dir: String,
     pub db_url: String,
     pub cuecards_self_managed: bool,
+    /// Path of converted cue sheets in `cuecards_lib_dir`
+    pub cuecards_placement: PlacementTemplate,
     pub minutes_per_tip: u32,
+    pub track_gap_seconds: u32,
 }
 
 #[derive(Debug)]
@@ -26,4 +32,45 @@ impl<'a, 'r> FromRequest<'a, 'r> for FileNameHeader {
         
         Outcome::Success(FileNameHeader(String::from(headers[0])))
     }
-}
\ No newline at end of file
+}
+
+/// Version of a resource a client based its update on, taken from the `If-Match` header.
+///
+/// The version of cue cards and programs is their modification date. With `*` any version
+/// matches, requests without the header are rejected with 428 Precondition Required.
+#[derive(Debug)]
+pub struct IfMatch(pub Option<String>);
+
+impl IfMatch {
+    pub fn matches(&self, date_modified: &str) -> bool {
+        let expected = match &self.0 {
+            Some(expected) => expected,
+            None => return true,
+        };
+
+        match (
+            DateTime::parse_from_rfc3339(expected),
+            DateTime::parse_from_rfc3339(date_modified),
+        ) {
+            (Ok(expected), Ok(current)) => expected == current,
+            _ => expected == date_modified,
+        }
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfMatch {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let version = match request.headers().get_one("If-Match") {
+            Some(value) => value.trim().trim_start_matches("W/").trim_matches('"'),
+            None => return Outcome::Failure((Status::PreconditionRequired, ())),
+        };
+
+        if version == "*" {
+            return Outcome::Success(IfMatch(None));
+        }
+
+        Outcome::Success(IfMatch(Some(String::from(version))))
+    }
+}
@@ -0,0 +1,187 @@
+/**
+
+Import of converted cue sheets into the library.
+
+Converted cue sheets are stored below `cuecards_lib_dir` at the path of the configured placement
+template, by default in the directory of the first letter of their file name. A path that is
+already taken gets a number, so existing cue cards are never replaced. Before a cue sheet is
+stored, a preview shows its Markdown, the metadata the indexer will read from it and the
+problems found, so the user can decide whether to import it.
+Conversions that are not reliable, like those of PDF documents without recognizable parts, are
+marked for review.
+
+**/
+use crate::sandbox;
+use cuecard_indexer::convert::Conversion;
+use cuecard_indexer::placement::{self, PlacementTemplate};
+use cuer_database::models::Cuecard;
+use diesel::prelude::*;
+use log::error;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+type DBConnection = SqliteConnection;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConversionPreview {
+    pub markdown: String,
+    /// Metadata as read by the indexer, like `title`, `phase` and `plusfigures`
+    pub metadata: HashMap<String, String>,
+    /// Path relative to the library, if the cue sheet is stored in the library
+    pub target_path: Option<String>,
+    pub warnings: Vec<String>,
+    /// The conversion is not reliable and the Markdown should be checked before it is imported
+    pub needs_review: bool,
+}
+
+/// Returns the cue cards with the same title and choreographer.
+fn duplicates(title: &str, choreographer: &str, conn: &DBConnection) -> QueryResult<Vec<Cuecard>> {
+    use cuer_database::schema::cuecards::dsl;
+
+    dsl::cuecards
+        .filter(dsl::title.eq(title))
+        .filter(dsl::choreographer.eq(choreographer))
+        .load::<Cuecard>(conn)
+}
+
+/// Returns the path relative to `lib_dir` at which a converted cue sheet is stored, the path of
+/// the placement template or, if it is taken by a file or one of `cuecard_paths`, the path with a
+/// number.
+pub fn library_path(
+    markdown: &str,
+    filename: &str,
+    lib_dir: &Path,
+    template: &PlacementTemplate,
+    cuecard_paths: &HashSet<PathBuf>,
+) -> PathBuf {
+    let metadata = cuecard_indexer::content_metadata(markdown);
+
+    placement::unique_path(lib_dir, &template.path(&metadata, filename), None, cuecard_paths)
+}
+
+/// A converted cue sheet stored in the library.
+pub struct Stored {
+    /// Path relative to the library
+    pub path: PathBuf,
+    /// Path of the placement template, which differs from `path` if it was taken
+    pub planned: PathBuf,
+}
+
+/// Stores a converted cue sheet below `lib_dir` at the path of the placement template or, if it
+/// is taken by a file or one of `cuecard_paths`, at the path with the first free number. A file
+/// created meanwhile at that path is never replaced, the next number is used instead.
+pub fn store(
+    markdown: &str,
+    filename: &str,
+    lib_dir: &Path,
+    template: &PlacementTemplate,
+    cuecard_paths: &HashSet<PathBuf>,
+) -> io::Result<Stored> {
+    let metadata = cuecard_indexer::content_metadata(markdown);
+    let planned = template.path(&metadata, filename);
+    let mut number = 1;
+
+    loop {
+        let path = if number == 1 {
+            planned.clone()
+        } else {
+            placement::numbered_path(&planned, number)
+        };
+        number += 1;
+
+        if cuecard_paths.contains(&path) {
+            continue;
+        }
+
+        let target = sandbox::resolve(lib_dir, &path)?;
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&target) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        };
+
+        if let Err(err) = file.write_all(markdown.as_bytes()) {
+            // a partially written cue card must not be indexed
+            if let Err(err) = fs::remove_file(&target) {
+                error!("Error removing {:?}: {:?}", target, err);
+            }
+            return Err(err);
+        }
+
+        return Ok(Stored { path, planned });
+    }
+}
+
+/// Checks a converted cue sheet. Without `lib_dir` the cue sheet is not stored in the library
+/// and the target path is not checked.
+pub fn preview(
+    markdown: String,
+    conversion: Conversion,
+    filename: &str,
+    lib_dir: Option<&Path>,
+    template: &PlacementTemplate,
+    conn: &DBConnection,
+) -> QueryResult<ConversionPreview> {
+    let metadata = cuecard_indexer::content_metadata(&markdown);
+    let needs_review = conversion.needs_review();
+    let mut warnings = conversion.warnings;
+
+    match metadata.get("title").map(|t| t.trim()) {
+        Some(title) if !title.is_empty() => {
+            // the indexer stores cue cards without a choreographer as unknown
+            let choreographer = metadata
+                .get("choreographer")
+                .map_or("unknown", String::as_str);
+
+            for cuecard in duplicates(title, choreographer, conn)? {
+                warnings.push(format!(
+                    "{} by {} is already in the library as {}",
+                    title, choreographer, cuecard.file_path
+                ));
+            }
+        }
+        _ => warnings.push("No title was found".to_string()),
+    }
+
+    if metadata.get("phase").map(String::as_str) == Some("unphased") {
+        warnings.push("No phase was recognized, the cue card will be unphased".to_string());
+    }
+
+    let mut target_path = None;
+
+    if let Some(lib_dir) = lib_dir {
+        let planned = template.path(&metadata, filename);
+        let cuecard_paths = placement::cuecard_paths(conn)?;
+        let path = library_path(&markdown, filename, lib_dir, template, &cuecard_paths);
+
+        match sandbox::resolve(lib_dir, &path) {
+            Ok(_) => {
+                if path != planned {
+                    warnings.push(format!(
+                        "{} is already taken, the cue card will be stored as {}",
+                        planned.display(),
+                        path.display()
+                    ));
+                }
+                target_path = Some(path.to_string_lossy().into_owned());
+            }
+            Err(_) => warnings.push(format!("{} is not a valid path", path.display())),
+        }
+    }
+
+    Ok(ConversionPreview {
+        markdown,
+        metadata,
+        target_path,
+        warnings,
+        needs_review,
+    })
+}
@@ -12,18 +12,22 @@ extern crate serde_derive;
 extern crate base64;
 extern crate chrono;
 extern crate comrak;
+extern crate cuecard_indexer;
 extern crate cuer_database;
 extern crate dirs;
 extern crate duct;
+extern crate hound;
 extern crate log;
+extern crate printpdf;
 extern crate serde;
 extern crate serde_json;
+extern crate symphonia;
 extern crate tempfile;
 extern crate unescape;
 extern crate uuid as uuidcrate;
-extern crate xml;
 extern crate zip;
 extern crate walkdir;
+extern crate xml;
 
 #[macro_use]
 extern crate diesel_migrations;
@@ -31,25 +35,48 @@ extern crate diesel_migrations;
 #[cfg(test)]
 mod tests;
 
-mod convert;
+mod audio;
+mod bundle;
+mod calendar;
+mod generator;
 mod cuecards;
+mod cuelog;
+mod db;
+mod export;
 mod guards;
+mod importing;
+mod planning;
+mod printing;
 mod programming;
+mod recurrence;
+mod revisions;
 mod routes;
-
+mod sandbox;
+mod schedule;
+mod stats;
+mod tempo;
+mod templates;
+mod versions;
+
+use cuecard_indexer::placement;
+use log::error;
 use rocket::fairing::AdHoc;
-use rocket_contrib::databases::diesel;
 
 use guards::BackendConfig;
 
 #[database("sqlite_db")]
-pub struct DbConn(diesel::SqliteConnection);
+pub struct DbConn(db::LibraryConnection);
 
 embed_migrations!("../migrations");
 
 fn rocket() -> rocket::Rocket {
-    rocket::ignite()
+    build(rocket::ignite())
+}
+
+fn build(rocket: rocket::Rocket) -> rocket::Rocket {
+    rocket
         .attach(DbConn::fairing())
+        .manage(audio::DurationCache::default())
         .mount(
             "/",
             routes![
@@ -58,7 +85,14 @@ fn rocket() -> rocket::Rocket {
                 routes::get_all_cuecards,
                 routes::search_cuecards,
                 routes::get_cuecard_by_uuid,
+                routes::export_cuecard,
+                routes::get_cuecard_revisions,
+                routes::get_cuecard_revision_diff,
+                routes::restore_cuecard_revision,
                 routes::cued_at,
+                routes::start_cue,
+                routes::stop_cue,
+                routes::get_cues,
                 routes::cuecard_content_by_uuid,
                 routes::post_cuecard_content_by_uuid,
                 routes::get_cuecard_metadata,
@@ -66,29 +100,66 @@ fn rocket() -> rocket::Rocket {
                 routes::refresh_cuecards_library,
                 routes::favicon,
                 routes::get_events,
+                routes::events_calendar,
+                routes::import_calendar,
+                routes::get_event_tags,
+                routes::add_event_tag,
+                routes::remove_event_tag,
+                routes::program_html,
+                routes::program_pdf,
+                routes::export_bundle,
+                routes::import_bundle,
                 routes::event_by_uuid,
                 routes::delete_event,
                 routes::create_event,
+                routes::get_event_series,
+                routes::create_event_series,
+                routes::update_event_series,
+                routes::delete_event_series,
+                routes::materialize_occurrence,
                 routes::get_program,
                 routes::get_program_notes,
                 routes::update_program_notes,
                 routes::get_tips,
+                routes::get_event_schedule,
+                routes::set_event_schedule,
+                routes::schedule_problems,
+                routes::create_scheduled_tips,
+                routes::copy_program,
+                routes::create_program_template,
+                routes::get_program_templates,
+                routes::get_program_template,
+                routes::delete_program_template,
+                routes::apply_program_template,
                 routes::create_tip,
+                routes::generate_program_draft,
+                routes::accept_program_draft,
                 routes::update_tip,
                 routes::remove_tip,
                 routes::create_tip_cuecard,
                 routes::update_tip_cuecard,
+                routes::order_tip_cuecards,
                 routes::remove_tip_cuecard,
                 routes::catchall,
                 routes::audio_file,
+                routes::stream_audio_file,
                 routes::set_marks,
                 routes::check_migrations,
                 routes::run_migrations,
+                routes::cuecard_stats,
+                routes::event_stats,
+                routes::rhythm_stats,
+                routes::phase_stats,
+                routes::never_cued,
+                routes::rotation,
                 routes::get_all_tags,
                 routes::get_tags,
                 routes::add_tag,
                 routes::remove_tag,
                 routes::convert_odt_file,
+                routes::convert_any_file,
+                routes::preview_conversion,
+                routes::import_cue_sheets,
                 routes::list_music_files,
                 routes::get_settings
             ],
@@ -112,6 +183,12 @@ fn rocket() -> rocket::Rocket {
                 .unwrap_or("cuecard_indexer")
                 .to_string();
 
+            let derived_files_dir = rocket
+                .config()
+                .get_str("derived_files_dir")
+                .unwrap_or("derived_files")
+                .to_string();
+
             let db_url = rocket
                 .config()
                 .get_str("library_db")
@@ -123,15 +200,33 @@ fn rocket() -> rocket::Rocket {
                 .get_bool("cuecards_self_managed")
                 .unwrap_or(false);
 
+            let cuecards_placement = rocket
+                .config()
+                .get_str("cuecards_placement")
+                .unwrap_or(placement::DEFAULT_TEMPLATE);
+
+            let cuecards_placement = match placement::PlacementTemplate::new(cuecards_placement) {
+                Ok(template) => template,
+                Err(err) => {
+                    error!("{}", err);
+                    return Err(rocket);
+                }
+            };
+
             let minutes_per_tip: u32 = rocket.config().get_int("minutes_per_tip").unwrap_or(15) as u32;
 
+            let track_gap_seconds: u32 = rocket.config().get_int("track_gap_seconds").unwrap_or(30) as u32;
+
             Ok(rocket.manage(BackendConfig {
                 music_files_dir,
                 cuecards_lib_dir,
                 indexer_path,
+                derived_files_dir,
                 db_url,
                 cuecards_self_managed,
-                minutes_per_tip
+                cuecards_placement,
+                minutes_per_tip,
+                track_gap_seconds
             }))
         }))
 }
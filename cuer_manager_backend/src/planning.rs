@@ -0,0 +1,89 @@
+/**
+
+Time planning of tips.
+
+The planned duration of a tip is the playing time of the music of all its cue cards plus a
+gap between two dances. A tip overruns if this is longer than its time slot.
+
+**/
+use chrono::DateTime;
+use cuer_database::models::Tip;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TipPlan {
+    /// Playing time of every cue card's music in seconds, `None` if it is unknown
+    pub track_durations: Vec<Option<u32>>,
+    pub planned_seconds: u32,
+    pub slot_seconds: u32,
+    pub overrun: bool,
+    /// Set if the playing time of a cue card is unknown, so the planned duration is too short
+    pub incomplete: bool,
+}
+
+/// Returns the length of the time slot of `tip` in seconds.
+///
+/// Tips without a valid start and end fall back to the configured minutes per tip.
+pub fn slot_seconds(tip: &Tip, minutes_per_tip: u32) -> u32 {
+    let start = DateTime::parse_from_rfc3339(&tip.date_start);
+    let end = DateTime::parse_from_rfc3339(&tip.date_end);
+
+    match (start, end) {
+        (Ok(start), Ok(end)) if end > start => (end - start).num_seconds() as u32,
+        _ => minutes_per_tip * 60,
+    }
+}
+
+pub fn plan(track_durations: Vec<Option<u32>>, gap_seconds: u32, slot_seconds: u32) -> TipPlan {
+    let music: u32 = track_durations.iter().filter_map(|d| *d).sum();
+    let gaps = gap_seconds * (track_durations.len().saturating_sub(1) as u32);
+    let planned_seconds = music + gaps;
+
+    TipPlan {
+        incomplete: track_durations.iter().any(Option::is_none),
+        track_durations,
+        planned_seconds,
+        slot_seconds,
+        overrun: planned_seconds > slot_seconds,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tip(date_start: &str, date_end: &str) -> Tip {
+        Tip {
+            id: 1,
+            uuid: "tip".to_string(),
+            name: "Tip".to_string(),
+            program_id: 1,
+            date_start: date_start.to_string(),
+            date_end: date_end.to_string(),
+            date_modified: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_slot_seconds() {
+        let t = tip("2019-11-01T19:00:00.000Z", "2019-11-01T19:15:00.000Z");
+        assert_eq!(slot_seconds(&t, 20), 900);
+
+        let t = tip("2019-11-01T19:00:00.000Z", "");
+        assert_eq!(slot_seconds(&t, 20), 1200);
+    }
+
+    #[test]
+    fn test_plan() {
+        let p = plan(vec![Some(180), Some(200), Some(190)], 30, 900);
+        assert_eq!(p.planned_seconds, 630);
+        assert!(!p.overrun);
+        assert!(!p.incomplete);
+
+        let p = plan(vec![Some(400), None, Some(400)], 60, 900);
+        assert_eq!(p.planned_seconds, 920);
+        assert!(p.overrun);
+        assert!(p.incomplete);
+
+        assert_eq!(plan(Vec::new(), 30, 900).planned_seconds, 0);
+    }
+}
@@ -0,0 +1,553 @@
+/**
+
+Printable versions of an event's program.
+
+The program is rendered either as a single HTML page meant to be printed from the browser or
+as a PDF document. Both contain the program notes, a table of contents and every cue card of
+the program's tips, each starting on a new page.
+
+**/
+use crate::programming::EventProgram;
+use chrono::DateTime;
+use comrak::nodes::{AstNode, ListType, NodeValue};
+use comrak::{markdown_to_html, parse_document, Arena, ComrakOptions};
+use printpdf::{BuiltinFont, IndirectFontRef, Mm, PdfDocument};
+
+use std::io;
+use std::io::BufWriter;
+
+pub fn markdown_options() -> ComrakOptions {
+    ComrakOptions {
+        ext_tasklist: true,
+        ext_footnotes: true,
+        ext_table: true,
+        hardbreaks: true,
+        ext_tagfilter: true,
+        unsafe_: true,
+        ..ComrakOptions::default()
+    }
+}
+
+/// Formats the time of day of an ISO 8601 timestamp, returns the input if it can't be parsed.
+fn format_time(timestamp: &str) -> String {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(time) => time.format("%H:%M").to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+fn format_date(timestamp: &str) -> String {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const PRINT_STYLE: &str = "
+body { font-family: sans-serif; font-size: 11pt; margin: 2em; }
+.cuecard, .notes { page-break-before: always; break-before: page; }
+.tip-name { color: #555; font-size: 9pt; }
+.toc ul { list-style: none; }
+.toc .page-ref { color: #555; }
+blockquote { margin-left: 1em; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #999; padding: 0.2em 0.5em; }
+@media screen { .cuecard, .notes { border-top: 1px dashed #999; margin-top: 2em; } }
+";
+
+/// Renders the program as a single HTML document for printing.
+pub fn to_html(program: &EventProgram) -> String {
+    let options = markdown_options();
+    let event = &program.event;
+    let mut html = String::new();
+
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape_html(&event.name)));
+    html.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", PRINT_STYLE));
+    html.push_str(&format!(
+        "<h1>{}</h1>\n<p>{} &ndash; {}</p>\n",
+        escape_html(&event.name),
+        format_date(&event.date_start),
+        format_date(&event.date_end)
+    ));
+
+    html.push_str("<nav class=\"toc\">\n<h2>Contents</h2>\n<ul>\n");
+    for (tip_index, (tip, cuecards)) in program.tips.iter().enumerate() {
+        html.push_str(&format!(
+            "<li><strong>{} &ndash; {} {}</strong>\n<ul>\n",
+            format_time(&tip.date_start),
+            format_time(&tip.date_end),
+            escape_html(&tip.name)
+        ));
+        for (card_index, cuecard) in cuecards.iter().enumerate() {
+            html.push_str(&format!(
+                "<li><a href=\"#card-{}-{}\">{}</a> <span class=\"page-ref\">({}, {})</span></li>\n",
+                tip_index,
+                card_index,
+                escape_html(&cuecard.title),
+                escape_html(&cuecard.rhythm),
+                escape_html(&cuecard.phase)
+            ));
+        }
+        html.push_str("</ul>\n</li>\n");
+    }
+    html.push_str("</ul>\n</nav>\n");
+
+    if let Some(notes) = program.program.notes.as_ref().filter(|n| !n.trim().is_empty()) {
+        html.push_str("<section class=\"notes\">\n<h2>Program notes</h2>\n");
+        html.push_str(&markdown_to_html(notes, &options));
+        html.push_str("</section>\n");
+    }
+
+    for (tip_index, (tip, cuecards)) in program.tips.iter().enumerate() {
+        for (card_index, cuecard) in cuecards.iter().enumerate() {
+            html.push_str(&format!(
+                "<section class=\"cuecard\" id=\"card-{}-{}\">\n<p class=\"tip-name\">{} &middot; {}</p>\n",
+                tip_index,
+                card_index,
+                escape_html(&tip.name),
+                format_time(&tip.date_start)
+            ));
+            html.push_str(&markdown_to_html(&cuecard.content, &options));
+            html.push_str("</section>\n");
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+const PAGE_WIDTH: f64 = 210.0;
+const PAGE_HEIGHT: f64 = 297.0;
+const MARGIN: f64 = 20.0;
+const FOOTER_HEIGHT: f64 = 10.0;
+const TEXT_SIZE: f64 = 10.0;
+const PT_IN_MM: f64 = 0.3528;
+/// Width reserved for the page numbers in the table of contents
+const PAGE_REF_WIDTH: f64 = 12.0;
+
+/// Character widths of Helvetica in 1/1000 em for the printable ASCII characters.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+fn text_width(text: &str, size: f64, bold: bool) -> f64 {
+    let units: u32 = text
+        .chars()
+        .map(|c| match c as u32 {
+            code @ 32..=126 => u32::from(HELVETICA_WIDTHS[(code - 32) as usize]),
+            _ => 556,
+        })
+        .sum();
+
+    // Helvetica-Bold is slightly wider, the approximation is good enough for wrapping
+    let factor = if bold { 1.08 } else { 1.0 };
+
+    f64::from(units) / 1000.0 * size * PT_IN_MM * factor
+}
+
+/// Splits `text` into lines fitting into `width` millimeters.
+fn wrap(text: &str, size: f64, bold: bool, width: f64) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+
+        if text_width(&candidate, size, bold) <= width || line.is_empty() {
+            line = candidate;
+        } else {
+            lines.push(line);
+            line = word.to_string();
+        }
+    }
+
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[derive(Clone, Debug)]
+enum Block {
+    Text {
+        text: String,
+        size: f64,
+        bold: bool,
+        indent: f64,
+        /// Page reference printed right aligned, used in the table of contents
+        page_ref: Option<usize>,
+    },
+    Space(f64),
+    PageBreak,
+    /// Marks the start of a cue card for the table of contents and the bookmarks
+    Anchor(usize),
+}
+
+fn text(text: &str, size: f64, bold: bool, indent: f64) -> Block {
+    Block::Text {
+        text: text.to_string(),
+        size,
+        bold,
+        indent,
+        page_ref: None,
+    }
+}
+
+struct Line {
+    text: String,
+    size: f64,
+    bold: bool,
+    x: f64,
+    y: f64,
+}
+
+struct Layout {
+    pages: Vec<Vec<Line>>,
+    /// Page index of every anchor
+    anchors: Vec<(usize, usize)>,
+}
+
+fn layout(blocks: &[Block]) -> Layout {
+    let top = PAGE_HEIGHT - MARGIN;
+    let bottom = MARGIN + FOOTER_HEIGHT;
+    let width = PAGE_WIDTH - 2.0 * MARGIN;
+
+    let mut pages: Vec<Vec<Line>> = vec![Vec::new()];
+    let mut anchors = Vec::new();
+    let mut y = top;
+
+    for block in blocks {
+        match block {
+            Block::Text {
+                text,
+                size,
+                bold,
+                indent,
+                page_ref,
+            } => {
+                let line_height = size * PT_IN_MM * 1.35;
+                let available = width - indent - page_ref.map_or(0.0, |_| PAGE_REF_WIDTH);
+
+                for (i, part) in wrap(text, *size, *bold, available).into_iter().enumerate() {
+                    if y - line_height < bottom {
+                        pages.push(Vec::new());
+                        y = top;
+                    }
+                    y -= line_height;
+
+                    if let (0, Some(page)) = (i, page_ref) {
+                        let number = page.to_string();
+                        pages.last_mut().unwrap().push(Line {
+                            x: PAGE_WIDTH - MARGIN - text_width(&number, *size, *bold),
+                            text: number,
+                            size: *size,
+                            bold: *bold,
+                            y,
+                        });
+                    }
+
+                    pages.last_mut().unwrap().push(Line {
+                        text: part,
+                        size: *size,
+                        bold: *bold,
+                        x: MARGIN + indent,
+                        y,
+                    });
+                }
+            }
+            Block::Space(space) => {
+                y -= space;
+            }
+            Block::PageBreak => {
+                if !pages.last().unwrap().is_empty() {
+                    pages.push(Vec::new());
+                }
+                y = top;
+            }
+            Block::Anchor(id) => anchors.push((*id, pages.len() - 1)),
+        }
+    }
+
+    Layout { pages, anchors }
+}
+
+fn node_text<'a>(node: &'a AstNode<'a>, output: &mut String) {
+    match node.data.borrow().value {
+        NodeValue::Text(ref literal) | NodeValue::Code(ref literal) => {
+            output.push_str(&String::from_utf8_lossy(literal))
+        }
+        NodeValue::SoftBreak | NodeValue::LineBreak => output.push('\n'),
+        NodeValue::TaskItem(checked) => output.push_str(if checked { "[x] " } else { "[ ] " }),
+        _ => (),
+    }
+
+    for child in node.children() {
+        node_text(child, output);
+    }
+}
+
+/// Converts the markdown AST into text blocks for the PDF layout.
+fn markdown_blocks<'a>(node: &'a AstNode<'a>, indent: f64, prefix: &str, blocks: &mut Vec<Block>) {
+    let value = node.data.borrow().value.clone();
+
+    match value {
+        NodeValue::Heading(heading) => {
+            let size = match heading.level {
+                1 => 14.0,
+                2 => 12.0,
+                _ => 11.0,
+            };
+            let mut s = String::new();
+            node_text(node, &mut s);
+            blocks.push(Block::Space(2.0));
+            for line in s.lines() {
+                blocks.push(text(line, size, true, indent));
+            }
+            blocks.push(Block::Space(1.0));
+        }
+        NodeValue::Paragraph | NodeValue::TableCell => {
+            let mut s = String::new();
+            node_text(node, &mut s);
+            for (i, line) in s.lines().enumerate() {
+                let line = if i == 0 {
+                    format!("{}{}", prefix, line)
+                } else {
+                    line.to_string()
+                };
+                blocks.push(text(&line, TEXT_SIZE, false, indent));
+            }
+            blocks.push(Block::Space(1.5));
+        }
+        NodeValue::CodeBlock(code) => {
+            for line in String::from_utf8_lossy(&code.literal).lines() {
+                blocks.push(text(line, TEXT_SIZE, false, indent + 4.0));
+            }
+            blocks.push(Block::Space(1.5));
+        }
+        NodeValue::BlockQuote => {
+            for child in node.children() {
+                markdown_blocks(child, indent + 5.0, "", blocks);
+            }
+        }
+        NodeValue::List(list) => {
+            for (i, child) in node.children().enumerate() {
+                let marker = match list.list_type {
+                    ListType::Bullet => "\u{2022} ".to_string(),
+                    ListType::Ordered => format!("{}. ", list.start + i),
+                };
+                markdown_blocks(child, indent, &marker, blocks);
+            }
+        }
+        NodeValue::Item(_) => {
+            for (i, child) in node.children().enumerate() {
+                let prefix = if i == 0 { prefix } else { "" };
+                markdown_blocks(child, indent + 5.0, prefix, blocks);
+            }
+        }
+        NodeValue::TableRow(header) => {
+            let cells = node
+                .children()
+                .map(|cell| {
+                    let mut s = String::new();
+                    node_text(cell, &mut s);
+                    s.replace('\n', " ")
+                })
+                .collect::<Vec<_>>();
+            blocks.push(text(&cells.join(" | "), TEXT_SIZE, header, indent));
+        }
+        NodeValue::ThematicBreak => blocks.push(Block::Space(4.0)),
+        NodeValue::HtmlBlock(_) => (),
+        _ => {
+            for child in node.children() {
+                markdown_blocks(child, indent, prefix, blocks);
+            }
+        }
+    }
+}
+
+fn add_markdown(markdown: &str, blocks: &mut Vec<Block>) {
+    let arena = Arena::new();
+    let root = parse_document(&arena, markdown, &markdown_options());
+    markdown_blocks(root, 0.0, "", blocks);
+}
+
+/// Title page and table of contents. `pages` maps the cue card anchors to page numbers.
+fn front_blocks(program: &EventProgram, pages: &[usize]) -> Vec<Block> {
+    let event = &program.event;
+    let mut blocks = vec![
+        text(&event.name, 18.0, true, 0.0),
+        Block::Space(2.0),
+        text(
+            &format!(
+                "{} \u{2013} {}",
+                format_date(&event.date_start),
+                format_date(&event.date_end)
+            ),
+            TEXT_SIZE,
+            false,
+            0.0,
+        ),
+        Block::Space(8.0),
+        text("Contents", 14.0, true, 0.0),
+        Block::Space(2.0),
+    ];
+
+    let mut anchor = 0;
+    for (tip, cuecards) in program.tips.iter() {
+        blocks.push(Block::Space(2.0));
+        blocks.push(text(
+            &format!(
+                "{} \u{2013} {}  {}",
+                format_time(&tip.date_start),
+                format_time(&tip.date_end),
+                tip.name
+            ),
+            TEXT_SIZE,
+            true,
+            0.0,
+        ));
+
+        for cuecard in cuecards {
+            blocks.push(Block::Text {
+                text: format!("{} ({}, {})", cuecard.title, cuecard.rhythm, cuecard.phase),
+                size: TEXT_SIZE,
+                bold: false,
+                indent: 5.0,
+                page_ref: Some(pages.get(anchor).cloned().unwrap_or(0)),
+            });
+            anchor += 1;
+        }
+    }
+
+    blocks
+}
+
+fn body_blocks(program: &EventProgram) -> Vec<Block> {
+    let mut blocks = Vec::new();
+
+    if let Some(notes) = program.program.notes.as_ref().filter(|n| !n.trim().is_empty()) {
+        blocks.push(Block::PageBreak);
+        blocks.push(text("Program notes", 14.0, true, 0.0));
+        blocks.push(Block::Space(2.0));
+        add_markdown(notes, &mut blocks);
+    }
+
+    let mut anchor = 0;
+    for (tip, cuecards) in program.tips.iter() {
+        for cuecard in cuecards {
+            blocks.push(Block::PageBreak);
+            blocks.push(Block::Anchor(anchor));
+            blocks.push(text(
+                &format!("{} \u{00b7} {}", tip.name, format_time(&tip.date_start)),
+                8.0,
+                false,
+                0.0,
+            ));
+            add_markdown(&cuecard.content, &mut blocks);
+            anchor += 1;
+        }
+    }
+
+    blocks
+}
+
+fn pdf_error<E: std::fmt::Debug>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err))
+}
+
+/// Renders the program as a PDF document.
+pub fn to_pdf(program: &EventProgram) -> io::Result<Vec<u8>> {
+    let body = layout(&body_blocks(program));
+
+    // the size of the table of contents doesn't depend on the page numbers in it
+    let front_pages = layout(&front_blocks(program, &[])).pages.len();
+    let page_numbers = body
+        .anchors
+        .iter()
+        .map(|(_, page)| front_pages + page + 1)
+        .collect::<Vec<_>>();
+    let front = layout(&front_blocks(program, &page_numbers));
+
+    let pages = front
+        .pages
+        .into_iter()
+        .chain(body.pages.into_iter().filter(|p| !p.is_empty()))
+        .collect::<Vec<_>>();
+
+    let (doc, first_page, first_layer) = PdfDocument::new(
+        program.event.name.as_str(),
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "Program",
+    );
+    let regular = doc
+        .add_builtin_font(BuiltinFont::Helvetica)
+        .map_err(pdf_error)?;
+    let bold = doc
+        .add_builtin_font(BuiltinFont::HelveticaBold)
+        .map_err(pdf_error)?;
+    let font = |is_bold: bool| -> &IndirectFontRef {
+        if is_bold {
+            &bold
+        } else {
+            &regular
+        }
+    };
+
+    let page_count = pages.len();
+    let mut page_indices = Vec::with_capacity(page_count);
+
+    for (number, lines) in pages.iter().enumerate() {
+        let (page, layer) = if number == 0 {
+            (first_page, first_layer)
+        } else {
+            doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Program")
+        };
+        page_indices.push(page);
+
+        let layer = doc.get_page(page).get_layer(layer);
+
+        for line in lines {
+            layer.use_text(
+                line.text.as_str(),
+                line.size,
+                Mm(line.x),
+                Mm(line.y),
+                font(line.bold),
+            );
+        }
+
+        let footer = format!("{} \u{2013} {}/{}", program.event.name, number + 1, page_count);
+        layer.use_text(footer, 8.0, Mm(MARGIN), Mm(MARGIN), &regular);
+    }
+
+    let mut anchor = 0;
+    for (_, cuecards) in program.tips.iter() {
+        for cuecard in cuecards {
+            let page = page_numbers[anchor] - 1;
+            doc.add_bookmark(cuecard.title.as_str(), page_indices[page]);
+            anchor += 1;
+        }
+    }
+
+    let mut writer = BufWriter::new(Vec::new());
+    doc.save(&mut writer).map_err(pdf_error)?;
+    writer.into_inner().map_err(pdf_error)
+}
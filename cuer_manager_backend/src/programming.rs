@@ -1,5 +1,11 @@
+use crate::recurrence;
+use crate::recurrence::{Occurrence, Rule, DATE_FORMAT};
+use crate::schedule;
+use crate::schedule::Problem;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, SecondsFormat, TimeZone, Utc};
 use cuer_database::models::{
-    Cuecard, Event, EventData, Program, Tip, TipCuecard, TipCuecardData, TipData,
+    Cuecard, Event, EventData, EventSeries, EventSeriesData, EventTag, EventTagData, Program,
+    ProgramData, Tag, TagData, Tip, TipCuecard, TipCuecardData, TipData,
 };
 /**
 
@@ -8,6 +14,8 @@ This means the module is responsible for handling events, programs, tips and the
 
 **/
 use diesel::prelude::*;
+use log::error;
+use uuidcrate::Uuid;
 
 /*pub enum EventError {
     DuplicateEvent,
@@ -16,12 +24,69 @@ use diesel::prelude::*;
 
 type DBConnection = SqliteConnection;
 
-pub fn delete_event(uuid: &str, conn: &DBConnection) -> QueryResult<Event> {
-    let e = event_by_uuid(uuid, conn).unwrap();
+/// Everything removed together with an event.
+#[derive(Serialize, Deserialize)]
+pub struct EventDeletion {
+    pub event: Event,
+    pub programs: Vec<Program>,
+    pub tips: Vec<Tip>,
+    pub tip_cuecards: Vec<TipCuecard>,
+    /// Only shows what would be removed, nothing was deleted
+    pub dry_run: bool,
+}
+
+/// Deletes an event with its program, tips and their cue cards in one transaction.
+///
+/// With `dry_run` nothing is deleted, but the result shows what would be removed.
+pub fn delete_event(uuid: &str, dry_run: bool, conn: &DBConnection) -> QueryResult<EventDeletion> {
+    use cuer_database::schema::{programs, tip_cuecards, tips};
+
+    conn.transaction(|| {
+        let event = event_by_uuid(uuid, conn)?;
+
+        let event_programs = programs::table
+            .filter(programs::columns::event_id.eq(event.id))
+            .load::<Program>(conn)?;
+        let program_ids: Vec<i32> = event_programs.iter().map(|p| p.id).collect();
 
-    e.delete(conn).unwrap();
+        let event_tips = tips::table
+            .filter(tips::columns::program_id.eq_any(&program_ids))
+            .load::<Tip>(conn)?;
+        let tip_ids: Vec<i32> = event_tips.iter().map(|t| t.id).collect();
 
-    Ok(e)
+        let event_tip_cuecards = tip_cuecards::table
+            .filter(tip_cuecards::columns::tip_id.eq_any(&tip_ids))
+            .order((
+                tip_cuecards::columns::tip_id,
+                tip_cuecards::columns::sort_order,
+            ))
+            .load::<TipCuecard>(conn)?;
+
+        if !dry_run {
+            // a deleted occurrence of a series is cancelled instead of showing up again
+            if let (Some(s_id), Some(date)) = (event.series_id, &event.recurrence_id) {
+                add_series_exception(s_id, date, conn)?;
+            }
+
+            // the rows are removed explicitly, as not every connection enforces foreign keys
+            diesel::delete(
+                tip_cuecards::table.filter(tip_cuecards::columns::tip_id.eq_any(&tip_ids)),
+            )
+            .execute(conn)?;
+            diesel::delete(tips::table.filter(tips::columns::id.eq_any(&tip_ids))).execute(conn)?;
+            diesel::delete(programs::table.filter(programs::columns::id.eq_any(&program_ids)))
+                .execute(conn)?;
+            event.delete(conn)?;
+        }
+
+        Ok(EventDeletion {
+            event,
+            programs: event_programs,
+            tips: event_tips,
+            tip_cuecards: event_tip_cuecards,
+            dry_run,
+        })
+    })
 }
 
 pub fn event_by_uuid(entry_uuid: &str, conn: &DBConnection) -> QueryResult<Event> {
@@ -29,17 +94,341 @@ pub fn event_by_uuid(entry_uuid: &str, conn: &DBConnection) -> QueryResult<Event
     events.filter(uuid.eq(entry_uuid)).first::<Event>(conn)
 }
 
+pub fn event_by_id(event_id: i32, conn: &DBConnection) -> QueryResult<Event> {
+    use cuer_database::schema::events::dsl::*;
+    events.filter(id.eq(event_id)).first::<Event>(conn)
+}
+
+/// Stores the schedule of `event` as JSON.
+pub fn set_event_schedule(
+    event: &Event,
+    event_schedule: Option<&schedule::Schedule>,
+    conn: &DBConnection,
+) -> QueryResult<Event> {
+    use cuer_database::schema::events::dsl::*;
+
+    let json = match event_schedule.map(serde_json::to_string) {
+        Some(Ok(json)) => Some(json),
+        Some(Err(err)) => return Err(diesel::result::Error::SerializationError(Box::new(err))),
+        None => None,
+    };
+    let now = Local::now().format("%FT%T%.3fZ").to_string();
+
+    diesel::update(events.filter(id.eq(event.id)))
+        .set((schedule.eq(json), date_modified.eq(now)))
+        .execute(conn)?;
+
+    event_by_id(event.id, conn)
+}
+
+/// Returns the problems of the tips of `program`, see `schedule::check_tips`.
+pub fn tip_problems(program: &Program, conn: &DBConnection) -> QueryResult<Vec<Problem<i32>>> {
+    let event = event_by_id(program.event_id, conn)?;
+    let tips = tips_by_program_id(program.id, conn)?;
+
+    Ok(schedule::check_tips(&event, &tips))
+}
+
+/// An occurrence of an event series which is not an event yet. It has neither an id nor a
+/// uuid, so it has to be turned into an event by `materialize_occurrence` before it can be
+/// programmed, cued or deleted.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SeriesOccurrence {
+    pub series_id: i32,
+    pub series_uuid: String,
+    /// Date of the occurrence, which becomes the `recurrence_id` of its event
+    pub recurrence_id: String,
+    pub name: String,
+    pub date_start: String,
+    pub date_end: String,
+}
+
+/// An entry of the event list.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ListedEvent {
+    Event(Event),
+    Occurrence(SeriesOccurrence),
+}
+
+impl ListedEvent {
+    pub fn date_start(&self) -> &str {
+        match self {
+            ListedEvent::Event(event) => &event.date_start,
+            ListedEvent::Occurrence(occurrence) => &occurrence.date_start,
+        }
+    }
+}
+
+/// Returns the events starting within `min_date..max_date`, including the occurrences of
+/// event series which are not concrete events yet.
 pub fn get_events(
     conn: &DBConnection,
-    min_date: String,
-    max_date: String,
-) -> QueryResult<Vec<Event>> {
+    min_date: DateTime<FixedOffset>,
+    max_date: DateTime<FixedOffset>,
+) -> QueryResult<Vec<ListedEvent>> {
+    use cuer_database::schema::events::dsl::*;
+    let mut result: Vec<ListedEvent> = events
+        .filter(date_start.ge(min_date.to_rfc3339()))
+        .filter(date_start.lt(max_date.to_rfc3339()))
+        .order(date_start.asc())
+        .load::<Event>(conn)?
+        .into_iter()
+        .map(ListedEvent::Event)
+        .collect();
+
+    let min_date = min_date.with_timezone(&Local);
+    let max_date = max_date.with_timezone(&Local);
+
+    for series in get_event_series(conn)? {
+        let materialized = series_recurrence_ids(series.id, conn)?;
+
+        let occurrences = match occurrences(&series, min_date, max_date) {
+            Ok(occurrences) => occurrences,
+            Err(err) => {
+                error!("Invalid event series {}: {}", series.uuid, err);
+                continue;
+            }
+        };
+
+        for occurrence in occurrences {
+            let recurrence = occurrence.date.format(DATE_FORMAT).to_string();
+
+            if !materialized.contains(&recurrence) {
+                result.push(ListedEvent::Occurrence(SeriesOccurrence {
+                    series_id: series.id,
+                    series_uuid: series.uuid.clone(),
+                    recurrence_id: recurrence,
+                    name: series.name.clone(),
+                    date_start: format_time(&occurrence.start),
+                    date_end: format_time(&occurrence.end),
+                }));
+            }
+        }
+    }
+
+    result.sort_by(|a, b| a.date_start().cmp(b.date_start()));
+
+    Ok(result)
+}
+
+/// Formats a time in UTC like the times sent by the frontend.
+pub fn format_time<Tz: TimeZone>(time: &DateTime<Tz>) -> String {
+    time.with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Expands `series` into its occurrences starting within `min..max` in the local time zone.
+pub fn occurrences(
+    series: &EventSeries,
+    min: DateTime<Local>,
+    max: DateTime<Local>,
+) -> Result<Vec<Occurrence<Local>>, String> {
+    let rule = series.rrule.parse::<Rule>()?;
+    let exceptions = recurrence::parse_exceptions(&series.exceptions)?;
+
+    let parse = |time: &str| {
+        DateTime::parse_from_rfc3339(time)
+            .map(|time| time.with_timezone(&Local))
+            .map_err(|err| format!("Invalid time {:?}: {}", time, err))
+    };
+
+    let start = parse(&series.date_start)?;
+    let end = parse(&series.date_end)?;
+
+    Ok(recurrence::occurrences(
+        &rule,
+        &exceptions,
+        start,
+        end,
+        min,
+        max,
+    ))
+}
+
+/// Returns the dates of the occurrences of a series which are concrete events.
+fn series_recurrence_ids(s_id: i32, conn: &DBConnection) -> QueryResult<Vec<String>> {
     use cuer_database::schema::events::dsl::*;
+
     events
-        .filter(date_start.ge(min_date))
-        .filter(date_start.lt(max_date))
+        .filter(series_id.eq(s_id))
+        .filter(recurrence_id.is_not_null())
+        .select(recurrence_id)
+        .load::<Option<String>>(conn)
+        .map(|ids| ids.into_iter().flatten().collect())
+}
+
+pub fn get_event_series(conn: &DBConnection) -> QueryResult<Vec<EventSeries>> {
+    use cuer_database::schema::event_series::dsl::*;
+
+    event_series
         .order(date_start.asc())
-        .load::<Event>(conn)
+        .load::<EventSeries>(conn)
+}
+
+pub fn event_series_by_uuid(series_uuid: &str, conn: &DBConnection) -> QueryResult<EventSeries> {
+    use cuer_database::schema::event_series::dsl::*;
+
+    event_series
+        .filter(uuid.eq(series_uuid))
+        .first::<EventSeries>(conn)
+}
+
+pub fn event_series_by_id(series_id: i32, conn: &DBConnection) -> QueryResult<EventSeries> {
+    use cuer_database::schema::event_series::dsl::*;
+
+    event_series
+        .filter(id.eq(series_id))
+        .first::<EventSeries>(conn)
+}
+
+fn add_series_exception(s_id: i32, date: &str, conn: &DBConnection) -> QueryResult<()> {
+    let series = event_series_by_id(s_id, conn)?;
+
+    if series.exceptions.split(',').any(|e| e.trim() == date) {
+        return Ok(());
+    }
+
+    let exceptions = if series.exceptions.trim().is_empty() {
+        date.to_string()
+    } else {
+        format!("{},{}", series.exceptions, date)
+    };
+    let now = Local::now().format("%FT%T%.3fZ").to_string();
+
+    EventSeriesData {
+        uuid: &series.uuid,
+        name: &series.name,
+        date_start: &series.date_start,
+        date_end: &series.date_end,
+        rrule: &series.rrule,
+        exceptions: &exceptions,
+        date_created: &series.date_created,
+        date_modified: &now,
+    }
+    .update(conn)
+    .map(|_| ())
+}
+
+/// Turns the occurrence of `series` on `date` into an event with an empty program, so it can
+/// be programmed. Returns the existing event if this already happened.
+pub fn materialize_occurrence(
+    series: &EventSeries,
+    date: NaiveDate,
+    conn: &DBConnection,
+) -> QueryResult<Event> {
+    use cuer_database::schema::events::dsl::*;
+
+    let recurrence = date.format(DATE_FORMAT).to_string();
+
+    conn.transaction(|| {
+        let existing = events
+            .filter(series_id.eq(series.id))
+            .filter(recurrence_id.eq(&recurrence))
+            .first::<Event>(conn)
+            .optional()?;
+
+        if let Some(event) = existing {
+            return Ok(event);
+        }
+
+        // the occurrences around the date, as the time zones of the date and the series differ
+        let min = Local
+            .from_local_datetime(&date.pred().and_hms(0, 0, 0))
+            .earliest();
+        let max = Local
+            .from_local_datetime(&date.succ().succ().and_hms(0, 0, 0))
+            .earliest();
+
+        let occurrence = match (min, max) {
+            (Some(min), Some(max)) => occurrences(series, min, max)
+                .map_err(|err| error!("Invalid event series {}: {}", series.uuid, err))
+                .ok()
+                .and_then(|o| o.into_iter().find(|o| o.date == date)),
+            _ => None,
+        };
+
+        let occurrence = match occurrence {
+            Some(occurrence) => occurrence,
+            None => return Err(diesel::result::Error::NotFound),
+        };
+
+        let now = Local::now().format("%FT%T%.3fZ").to_string();
+        let u = Uuid::new_v4().to_hyphenated().to_string();
+
+        let event = EventData {
+            uuid: &u,
+            name: &series.name,
+            date_start: &format_time(&occurrence.start),
+            date_end: &format_time(&occurrence.end),
+            schedule: None,
+            date_created: &now,
+            date_modified: &now,
+            series_id: Some(series.id),
+            recurrence_id: Some(&recurrence),
+        }
+        .create(conn)?;
+
+        let u = Uuid::new_v4().to_hyphenated().to_string();
+        let now = Utc::now().format("%FT%T%.3fZ").to_string();
+
+        ProgramData {
+            uuid: &u,
+            notes: None,
+            event_id: event.id,
+            date_created: &now,
+            date_modified: &now,
+        }
+        .create(conn)?;
+
+        Ok(event)
+    })
+}
+
+pub fn get_event_tags(event: &Event, conn: &DBConnection) -> QueryResult<Vec<Tag>> {
+    cuer_database::schema::event_tags::table
+        .inner_join(cuer_database::schema::tags::table)
+        .filter(cuer_database::schema::event_tags::columns::event_id.eq(event.id))
+        .select(cuer_database::schema::tags::all_columns)
+        .load(conn)
+}
+
+/// Adds the tag `name` to `event`, creating the tag if it doesn't exist yet.
+pub fn add_event_tag(name: &str, event: &Event, conn: &DBConnection) -> QueryResult<Tag> {
+    use cuer_database::schema::event_tags::dsl::*;
+
+    let t = match cuer_database::schema::tags::table
+        .filter(cuer_database::schema::tags::columns::tag.eq(name))
+        .first::<Tag>(conn)
+        .optional()?
+    {
+        Some(t) => t,
+        None => TagData { tag: name }.create(conn)?,
+    };
+
+    let associated = event_tags
+        .filter(event_id.eq(event.id))
+        .filter(tag_id.eq(t.id))
+        .first::<EventTag>(conn)
+        .optional()?;
+
+    if associated.is_none() {
+        EventTagData {
+            event_id: event.id,
+            tag_id: t.id,
+        }
+        .create(conn)?;
+    }
+
+    Ok(t)
+}
+
+pub fn remove_event_tag(tag: &Tag, event: &Event, conn: &DBConnection) -> QueryResult<usize> {
+    EventTagData {
+        event_id: event.id,
+        tag_id: tag.id,
+    }
+    .delete(conn)
 }
 
 pub fn create_event(event: &EventData, conn: &DBConnection) -> QueryResult<Event> {
@@ -85,6 +474,32 @@ pub fn create_tip_cuecard(tip_cuecard: &TipCuecardData, conn: &DBConnection) ->
     tip_cuecard.create(conn)
 }
 
+/// Adds `cuecard` after the last cue card of `tip`, the order is computed in the same
+/// transaction as the insert so cue cards added at the same time get distinct orders.
+pub fn append_tip_cuecard(tip: &Tip, cuecard: &Cuecard, conn: &DBConnection) -> QueryResult<i32> {
+    use cuer_database::schema::tip_cuecards::dsl::*;
+
+    conn.transaction(|| {
+        let last = tip_cuecards
+            .filter(tip_id.eq(tip.id))
+            .select(diesel::dsl::max(sort_order))
+            .first::<Option<i32>>(conn)?;
+        let order = last.unwrap_or(0) + 1;
+
+        create_tip_cuecard(
+            &TipCuecardData {
+                tip_id: &tip.id,
+                cuecard_id: &cuecard.id,
+                sort_order: &order,
+                cued_at: None,
+            },
+            conn,
+        )?;
+
+        Ok(order)
+    })
+}
+
 pub fn update_tip_cuecard(tip_cuecard: &TipCuecardData, conn: &DBConnection) -> QueryResult<usize> {
     use cuer_database::schema::tip_cuecards::dsl::*;
 
@@ -99,6 +514,21 @@ pub fn remove_tip_cuecard(tip_cuecard: &TipCuecardData, conn: &DBConnection) ->
     tip_cuecard.delete(conn)
 }
 
+/// Sets the version of `tip`, which is otherwise changed by the database on every change.
+pub fn set_tip_modified(tip: &Tip, modified: &str, conn: &DBConnection) -> QueryResult<usize> {
+    use cuer_database::schema::tips::dsl::*;
+
+    diesel::update(tips.filter(id.eq(tip.id)))
+        .set(date_modified.eq(modified))
+        .execute(conn)
+}
+
+pub fn clear_tip_cuecards(tip: &Tip, conn: &DBConnection) -> QueryResult<usize> {
+    use cuer_database::schema::tip_cuecards::dsl::*;
+
+    diesel::delete(tip_cuecards.filter(tip_id.eq(tip.id))).execute(conn)
+}
+
 pub fn get_tip_cuecard(t_id: i32, c_id: i32, conn: &DBConnection) -> QueryResult<TipCuecard> {
     use cuer_database::schema::tip_cuecards::dsl::*;
 
@@ -113,7 +543,10 @@ pub fn get_cuecards(tip: &Tip, conn: &DBConnection) -> QueryResult<Vec<Cuecard>>
         .inner_join(cuer_database::schema::cuecards::table)
         .filter(cuer_database::schema::tip_cuecards::columns::tip_id.eq(tip.id))
         .select(cuer_database::schema::cuecards::all_columns)
-        .order(cuer_database::schema::tip_cuecards::columns::sort_order)
+        .order((
+            cuer_database::schema::tip_cuecards::columns::sort_order,
+            cuer_database::schema::tip_cuecards::columns::id,
+        ))
         .load::<Cuecard>(conn)
 }
 
@@ -121,10 +554,130 @@ pub fn get_tip_cuecards(tip: &Tip, conn: &DBConnection) -> QueryResult<Vec<TipCu
     cuer_database::schema::tip_cuecards::table
         .filter(cuer_database::schema::tip_cuecards::columns::tip_id.eq(tip.id))
         .select(cuer_database::schema::tip_cuecards::all_columns)
-        .order(cuer_database::schema::tip_cuecards::columns::sort_order)
+        .order((
+            cuer_database::schema::tip_cuecards::columns::sort_order,
+            cuer_database::schema::tip_cuecards::columns::id,
+        ))
         .load::<TipCuecard>(conn)
 }
 
+/// Returns the cue cards of `tip` in program order, each with its entry in the tip.
+pub fn get_tip_cuecards_with_cuecards(
+    tip: &Tip,
+    conn: &DBConnection,
+) -> QueryResult<Vec<(TipCuecard, Cuecard)>> {
+    use cuer_database::schema::{cuecards, tip_cuecards};
+
+    tip_cuecards::table
+        .inner_join(cuecards::table)
+        .filter(tip_cuecards::columns::tip_id.eq(tip.id))
+        .select((tip_cuecards::all_columns, cuecards::all_columns))
+        .order((tip_cuecards::columns::sort_order, tip_cuecards::columns::id))
+        .load::<(TipCuecard, Cuecard)>(conn)
+}
+
+/// Result of ordering the cue cards of a tip.
+#[derive(Debug)]
+pub enum TipOrder {
+    Ordered(Vec<TipCuecard>),
+    /// Cue cards of the tip are missing in the order, nothing was changed
+    Incomplete,
+    /// Cue cards are neither in the tip nor in the tip they are moved from, nothing was changed
+    Unknown,
+}
+
+/// Sets the order of the cue cards of `tip` to the order of `cuecards` in one transaction.
+///
+/// Cue cards which are not in the tip yet are moved from the tip `from`, keeping the time they
+/// were cued. The remaining cue cards of `from` are renumbered.
+pub fn order_tip_cuecards(
+    tip: &Tip,
+    cuecards: &[Cuecard],
+    from: Option<&Tip>,
+    conn: &DBConnection,
+) -> QueryResult<TipOrder> {
+    conn.transaction(|| {
+        let existing = get_tip_cuecards(tip, conn)?;
+
+        if existing
+            .iter()
+            .any(|e| !cuecards.iter().any(|c| c.id == e.cuecard_id))
+        {
+            return Ok(TipOrder::Incomplete);
+        }
+
+        let movable = match from {
+            Some(from) => get_tip_cuecards(from, conn)?,
+            None => Vec::new(),
+        };
+
+        if cuecards.iter().any(|c| {
+            !existing.iter().any(|e| e.cuecard_id == c.id)
+                && !movable.iter().any(|m| m.cuecard_id == c.id)
+        }) {
+            return Ok(TipOrder::Unknown);
+        }
+
+        for (i, cuecard) in cuecards.iter().enumerate() {
+            let sort_order = i as i32 + 1;
+
+            if let Some(existing) = get_tip_cuecard(tip.id, cuecard.id, conn).optional()? {
+                update_tip_cuecard(
+                    &TipCuecardData {
+                        tip_id: &tip.id,
+                        cuecard_id: &cuecard.id,
+                        sort_order: &sort_order,
+                        cued_at: existing.cued_at,
+                    },
+                    conn,
+                )?;
+                continue;
+            }
+
+            let moved = match from {
+                Some(from) => get_tip_cuecard(from.id, cuecard.id, conn)?,
+                None => return Err(diesel::result::Error::NotFound),
+            };
+
+            remove_tip_cuecard(
+                &TipCuecardData {
+                    tip_id: &moved.tip_id,
+                    cuecard_id: &moved.cuecard_id,
+                    sort_order: &moved.sort_order,
+                    cued_at: None,
+                },
+                conn,
+            )?;
+
+            create_tip_cuecard(
+                &TipCuecardData {
+                    tip_id: &tip.id,
+                    cuecard_id: &cuecard.id,
+                    sort_order: &sort_order,
+                    cued_at: moved.cued_at,
+                },
+                conn,
+            )?;
+        }
+
+        if let Some(from) = from {
+            for (i, remaining) in get_tip_cuecards(from, conn)?.into_iter().enumerate() {
+                update_tip_cuecard(
+                    &TipCuecardData {
+                        tip_id: &remaining.tip_id,
+                        cuecard_id: &remaining.cuecard_id,
+                        sort_order: &(i as i32 + 1),
+                        cued_at: remaining.cued_at,
+                    },
+                    conn,
+                )?;
+            }
+        }
+
+        get_tip_cuecards(tip, conn).map(TipOrder::Ordered)
+    })
+}
+
 pub fn set_marks(c_id: i32, marks: &str, conn: &DBConnection) -> QueryResult<usize> {
     use cuer_database::schema::cuecards::dsl::*;
 
@@ -133,10 +686,31 @@ pub fn set_marks(c_id: i32, marks: &str, conn: &DBConnection) -> QueryResult<usi
         .execute(conn)
 }
 
-pub fn set_content(c_id: i32, markdown: &str, conn: &DBConnection) -> QueryResult<usize> {
-    use cuer_database::schema::cuecards::dsl::*;
+/// An event with its program, the tips of the program and their cue cards in program order.
+pub struct EventProgram {
+    pub event: Event,
+    pub program: Program,
+    pub tips: Vec<(Tip, Vec<Cuecard>)>,
+}
 
-    diesel::update(cuecards.filter(id.eq(c_id)))
-        .set(content.eq(markdown))
-        .execute(conn)
-}
\ No newline at end of file
+pub fn event_program(event_uuid: &str, conn: &DBConnection) -> QueryResult<EventProgram> {
+    let event = event_by_uuid(event_uuid, conn)?;
+
+    let program = match program_by_event_id(event.id, conn)? {
+        Some(program) => program,
+        None => return Err(diesel::result::Error::NotFound),
+    };
+
+    let mut tips = Vec::new();
+
+    for tip in tips_by_program_id(program.id, conn)? {
+        let cuecards = get_cuecards(&tip, conn)?;
+        tips.push((tip, cuecards));
+    }
+
+    Ok(EventProgram {
+        event,
+        program,
+        tips,
+    })
+}
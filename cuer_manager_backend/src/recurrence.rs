@@ -0,0 +1,440 @@
+/**
+
+Recurrence rules of event series.
+
+A subset of the iCalendar RRULE syntax is supported: weekly and monthly rules with an
+interval, weekdays (`BYDAY=TU`, or with their position in the month like `BYDAY=2TU,-1FR` for
+monthly rules), days of the month (`BYMONTHDAY=1,-1`), `COUNT` and `UNTIL`.
+
+Occurrences are identified by their date in the time zone of the series, which is also used to
+list the cancelled occurrences of a series.
+
+**/
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Weekday};
+
+use std::str::FromStr;
+
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Largest number of weeks or months between two periods of a series
+pub const MAX_INTERVAL: u32 = 52;
+
+/// Largest number of occurrences of a series with a `COUNT`
+pub const MAX_COUNT: u32 = 1000;
+
+#[derive(Debug, PartialEq)]
+pub enum Frequency {
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Rule {
+    pub frequency: Frequency,
+    /// Number of weeks or months between two periods of the rule
+    pub interval: u32,
+    /// Weekdays, with their position within the month for monthly rules
+    pub by_day: Vec<(Option<i32>, Weekday)>,
+    /// Days of the month, negative values count from the end of the month
+    pub by_month_day: Vec<i32>,
+    pub count: Option<u32>,
+    pub until: Option<NaiveDate>,
+}
+
+/// A single occurrence of an event series.
+#[derive(Debug, PartialEq)]
+pub struct Occurrence<Tz: TimeZone> {
+    pub date: NaiveDate,
+    pub start: DateTime<Tz>,
+    pub end: DateTime<Tz>,
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    match day {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Parses a weekday with an optional position like `TU`, `2TU` or `-1FR`.
+fn parse_by_day(value: &str) -> Option<(Option<i32>, Weekday)> {
+    if value.len() < 2 || !value.is_char_boundary(value.len() - 2) {
+        return None;
+    }
+
+    let (position, day) = value.split_at(value.len() - 2);
+    let day = parse_weekday(day)?;
+
+    match position {
+        "" => Some((None, day)),
+        position => match position.trim_start_matches('+').parse::<i32>() {
+            Ok(n) if n != 0 && n.abs() <= 5 => Some((Some(n), day)),
+            _ => None,
+        },
+    }
+}
+
+fn parse_until(value: &str) -> Option<NaiveDate> {
+    value
+        .get(0..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+}
+
+fn parse_list<T, F>(name: &str, value: &str, parse: F) -> Result<Vec<T>, String>
+where
+    F: Fn(&str) -> Option<T>,
+{
+    value
+        .split(',')
+        .map(|v| parse(v.trim()).ok_or_else(|| format!("Invalid {} value {:?}", name, v)))
+        .collect()
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(rrule: &str) -> Result<Self, Self::Err> {
+        let rrule = rrule.trim();
+        let rrule = rrule.strip_prefix("RRULE:").unwrap_or(rrule);
+
+        let mut frequency = None;
+        let mut rule = Rule {
+            frequency: Frequency::Weekly,
+            interval: 1,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            count: None,
+            until: None,
+        };
+
+        for part in rrule.split(';').filter(|p| !p.is_empty()) {
+            let mut pair = part.splitn(2, '=');
+            let name = pair.next().unwrap_or("").trim().to_uppercase();
+            let value = pair.next().unwrap_or("").trim().to_uppercase();
+
+            match name.as_ref() {
+                "FREQ" => {
+                    frequency = match value.as_ref() {
+                        "WEEKLY" => Some(Frequency::Weekly),
+                        "MONTHLY" => Some(Frequency::Monthly),
+                        _ => return Err(format!("Unsupported frequency {:?}", value)),
+                    }
+                }
+                "INTERVAL" => {
+                    rule.interval = match value.parse::<u32>() {
+                        Ok(interval) if interval > 0 => interval,
+                        _ => return Err(format!("Invalid interval {:?}", value)),
+                    }
+                }
+                "BYDAY" => rule.by_day = parse_list(&name, &value, parse_by_day)?,
+                "BYMONTHDAY" => {
+                    rule.by_month_day = parse_list(&name, &value, |v| {
+                        v.parse::<i32>()
+                            .ok()
+                            .filter(|day| *day != 0 && day.abs() <= 31)
+                    })?
+                }
+                "COUNT" => {
+                    rule.count = match value.parse::<u32>() {
+                        Ok(count) => Some(count),
+                        Err(_) => return Err(format!("Invalid count {:?}", value)),
+                    }
+                }
+                "UNTIL" => {
+                    rule.until = match parse_until(&value) {
+                        Some(until) => Some(until),
+                        None => return Err(format!("Invalid end date {:?}", value)),
+                    }
+                }
+                "WKST" if value == "MO" => {}
+                _ => return Err(format!("Unsupported rule part {:?}", part)),
+            }
+        }
+
+        rule.frequency = match frequency {
+            Some(frequency) => frequency,
+            None => return Err("Missing frequency".to_string()),
+        };
+
+        if rule.frequency == Frequency::Weekly
+            && (!rule.by_month_day.is_empty() || rule.by_day.iter().any(|(n, _)| n.is_some()))
+        {
+            return Err("Weekly rules only support plain weekdays".to_string());
+        }
+
+        Ok(rule)
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> Option<u32> {
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year.checked_add(1)?, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+
+    Some(next.pred().day())
+}
+
+impl Rule {
+    /// Returns the first day of period `n` and the dates of the rule within it, or `None` if the
+    /// period lies beyond the supported dates.
+    fn period(&self, first: NaiveDate, n: u32) -> Option<(NaiveDate, Vec<NaiveDate>)> {
+        let mut dates = Vec::new();
+        let periods = self.interval.checked_mul(n)?;
+
+        let start = match self.frequency {
+            Frequency::Weekly => {
+                let offset = |day: Weekday| Duration::days(i64::from(day.num_days_from_monday()));
+                let monday = first
+                    .checked_sub_signed(offset(first.weekday()))?
+                    .checked_add_signed(Duration::weeks(i64::from(periods)))?;
+
+                if self.by_day.is_empty() {
+                    dates.push(monday.checked_add_signed(offset(first.weekday()))?);
+                }
+
+                for (_, day) in &self.by_day {
+                    dates.push(monday.checked_add_signed(offset(*day))?);
+                }
+
+                monday
+            }
+            Frequency::Monthly => {
+                let months = first.month0().checked_add(periods)?;
+                let year = first.year().checked_add((months / 12) as i32)?;
+                let month = months % 12 + 1;
+                let length = days_in_month(year, month)? as i32;
+                let date = |day: i32| NaiveDate::from_ymd(year, month, day as u32);
+
+                let mut month_days = self.by_month_day.clone();
+                if month_days.is_empty() && self.by_day.is_empty() {
+                    month_days.push(first.day() as i32);
+                }
+
+                for day in month_days {
+                    let day = if day > 0 { day } else { length + day + 1 };
+                    if day >= 1 && day <= length {
+                        dates.push(date(day));
+                    }
+                }
+
+                for (position, weekday) in &self.by_day {
+                    let matching: Vec<NaiveDate> = (1..=length)
+                        .map(date)
+                        .filter(|d| d.weekday() == *weekday)
+                        .collect();
+
+                    match position {
+                        None => dates.extend(matching),
+                        Some(n) if *n > 0 => dates.extend(matching.get(*n as usize - 1)),
+                        Some(n) => {
+                            if let Some(i) = matching.len().checked_sub(n.unsigned_abs() as usize) {
+                                dates.push(matching[i]);
+                            }
+                        }
+                    }
+                }
+
+                date(1)
+            }
+        };
+
+        dates.sort();
+        dates.dedup();
+
+        Some((start, dates))
+    }
+
+    /// Returns the dates of all occurrences of a series starting on `first` before `before`.
+    pub fn dates(&self, first: NaiveDate, before: NaiveDate) -> Vec<NaiveDate> {
+        let mut result = Vec::new();
+        let mut count = 0;
+        let mut n = 0;
+
+        loop {
+            let (start, dates) = match self.period(first, n) {
+                Some((start, _)) if start >= before => return result,
+                Some(period) => period,
+                None => return result,
+            };
+
+            for date in dates.into_iter().filter(|d| *d >= first) {
+                if date >= before
+                    || self.until.is_some_and(|until| date > until)
+                    || self.count.is_some_and(|max| count >= max)
+                {
+                    return result;
+                }
+
+                count += 1;
+                result.push(date);
+            }
+
+            n += 1;
+        }
+    }
+}
+
+/// Parses a comma separated list of dates.
+pub fn parse_exceptions(exceptions: &str) -> Result<Vec<NaiveDate>, String> {
+    exceptions
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(|e| {
+            NaiveDate::parse_from_str(e, DATE_FORMAT).map_err(|_| format!("Invalid date {:?}", e))
+        })
+        .collect()
+}
+
+/// Returns the occurrences of a series starting at `start` and ending at `end` which start
+/// within `min..max`, leaving out the dates in `exceptions`.
+///
+/// The start time is kept in the time zone `tz`, so occurrences keep their local time across
+/// changes of the daylight saving time.
+pub fn occurrences<Tz: TimeZone>(
+    rule: &Rule,
+    exceptions: &[NaiveDate],
+    start: DateTime<Tz>,
+    end: DateTime<Tz>,
+    min: DateTime<Tz>,
+    max: DateTime<Tz>,
+) -> Vec<Occurrence<Tz>> {
+    let duration = end - start.clone();
+    let time = start.naive_local().time();
+    let tz = start.timezone();
+    // the day after `max` covers occurrences starting in its last hours in other time zones
+    let before = max.naive_local().date().succ();
+
+    rule.dates(start.naive_local().date(), before)
+        .into_iter()
+        .filter(|date| !exceptions.contains(date))
+        .filter_map(|date| {
+            tz.from_local_datetime(&date.and_time(time))
+                .earliest()
+                .map(|start| Occurrence {
+                    date,
+                    end: start.clone() + duration,
+                    start,
+                })
+        })
+        .filter(|o| o.start >= min && o.start < max)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn dates(rrule: &str, first: &str, before: &str) -> Vec<String> {
+        let rule = rrule.parse::<Rule>().unwrap();
+        let first = NaiveDate::parse_from_str(first, DATE_FORMAT).unwrap();
+        let before = NaiveDate::parse_from_str(before, DATE_FORMAT).unwrap();
+
+        rule.dates(first, before)
+            .iter()
+            .map(|d| d.format(DATE_FORMAT).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let rule = "RRULE:FREQ=MONTHLY;INTERVAL=2;BYDAY=2TU,-1FR;UNTIL=20201231T000000Z"
+            .parse::<Rule>()
+            .unwrap();
+        assert_eq!(rule.frequency, Frequency::Monthly);
+        assert_eq!(rule.interval, 2);
+        assert_eq!(
+            rule.by_day,
+            vec![(Some(2), Weekday::Tue), (Some(-1), Weekday::Fri)]
+        );
+        assert_eq!(rule.until, Some(NaiveDate::from_ymd(2020, 12, 31)));
+
+        assert!("FREQ=DAILY".parse::<Rule>().is_err());
+        assert!("BYDAY=TU".parse::<Rule>().is_err());
+        assert!("FREQ=WEEKLY;BYDAY=2TU".parse::<Rule>().is_err());
+        assert!("FREQ=WEEKLY;BYDAY=XX".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_weekly() {
+        assert_eq!(
+            dates("FREQ=WEEKLY", "2019-11-05", "2019-11-27"),
+            vec!["2019-11-05", "2019-11-12", "2019-11-19", "2019-11-26"]
+        );
+        assert_eq!(
+            dates(
+                "FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,FR;COUNT=3",
+                "2019-11-08",
+                "2020-01-01"
+            ),
+            vec!["2019-11-08", "2019-11-19", "2019-11-22"]
+        );
+        assert_eq!(
+            dates("FREQ=WEEKLY;UNTIL=20191112", "2019-11-05", "2020-01-01"),
+            vec!["2019-11-05", "2019-11-12"]
+        );
+    }
+
+    #[test]
+    fn test_monthly() {
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYDAY=2TU,-1FR", "2019-11-01", "2020-01-01"),
+            vec!["2019-11-12", "2019-11-29", "2019-12-10", "2019-12-27"]
+        );
+        assert_eq!(
+            dates("FREQ=MONTHLY", "2020-01-31", "2020-06-01"),
+            vec!["2020-01-31", "2020-03-31", "2020-05-31"]
+        );
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYMONTHDAY=-1", "2020-01-31", "2020-04-01"),
+            vec!["2020-01-31", "2020-02-29", "2020-03-31"]
+        );
+    }
+
+    #[test]
+    fn test_large_interval() {
+        assert_eq!(
+            dates("FREQ=WEEKLY;INTERVAL=4294967295", "2019-11-05", "9999-01-01"),
+            vec!["2019-11-05"]
+        );
+        assert_eq!(
+            dates("FREQ=MONTHLY;INTERVAL=4294967295", "2019-11-05", "9999-01-01"),
+            vec!["2019-11-05"]
+        );
+    }
+
+    #[test]
+    fn test_occurrences() {
+        let parse = |d: &str| DateTime::parse_from_rfc3339(d).unwrap();
+        let rule = "FREQ=WEEKLY".parse::<Rule>().unwrap();
+        let exceptions = parse_exceptions("2019-11-12, 2019-11-26").unwrap();
+
+        let result: Vec<Occurrence<FixedOffset>> = occurrences(
+            &rule,
+            &exceptions,
+            parse("2019-11-05T19:00:00+01:00"),
+            parse("2019-11-05T22:00:00+01:00"),
+            parse("2019-11-10T00:00:00+01:00"),
+            parse("2019-12-04T00:00:00+01:00"),
+        );
+
+        let starts: Vec<String> = result.iter().map(|o| o.start.to_rfc3339()).collect();
+        assert_eq!(
+            starts,
+            vec!["2019-11-19T19:00:00+01:00", "2019-12-03T19:00:00+01:00"]
+        );
+        assert_eq!(result[0].end.to_rfc3339(), "2019-11-19T22:00:00+01:00");
+        assert_eq!(result[1].date, NaiveDate::from_ymd(2019, 12, 3));
+
+        assert!(parse_exceptions("2019-13-01").is_err());
+    }
+}
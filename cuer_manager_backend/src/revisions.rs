@@ -0,0 +1,296 @@
+/**
+
+Revisions of cue cards.
+
+Before the content or the metadata of a cue card is replaced, by the web editor, the indexer or
+an import, the previous version is kept as a revision. A revision can be compared with the
+current version of its cue card and restored. Restoring a revision keeps the version it replaces
+as a revision as well, so a restore can be undone like any other change. The metadata file of
+the cue card is rewritten with the restored metadata, so the indexer keeps it.
+
+**/
+use crate::cuecards;
+use chrono::Utc;
+use cuer_database::models::{Cuecard, CuecardData, CuecardRevision, CuecardRevisionData};
+use diesel::prelude::*;
+use log::error;
+use uuidcrate::Uuid;
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+type DBConnection = SqliteConnection;
+
+/// Keeps the current version of a cue card as a revision, `source` is what replaces it.
+pub fn record(
+    cuecard: &Cuecard,
+    source: &str,
+    conn: &DBConnection,
+) -> QueryResult<CuecardRevision> {
+    let uuid = Uuid::new_v4().to_string();
+    let date_created = Utc::now().format("%FT%T%.3fZ").to_string();
+
+    CuecardRevisionData::of(cuecard, &uuid, source, &date_created).create(conn)
+}
+
+/// Returns the revisions of a cue card, the latest first.
+pub fn revisions_of(cuecard: &Cuecard, conn: &DBConnection) -> QueryResult<Vec<CuecardRevision>> {
+    use cuer_database::schema::cuecard_revisions::dsl::*;
+
+    cuecard_revisions
+        .filter(cuecard_id.eq(cuecard.id))
+        .order((date_created.desc(), id.desc()))
+        .load::<CuecardRevision>(conn)
+}
+
+pub fn revision_by_uuid(
+    cuecard: &Cuecard,
+    revision_uuid: &str,
+    conn: &DBConnection,
+) -> QueryResult<CuecardRevision> {
+    use cuer_database::schema::cuecard_revisions::dsl::*;
+
+    cuecard_revisions
+        .filter(cuecard_id.eq(cuecard.id))
+        .filter(uuid.eq(revision_uuid))
+        .first::<CuecardRevision>(conn)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Unchanged,
+    /// The line is only part of the current version
+    Added,
+    /// The line is only part of the revision
+    Removed,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct DiffLine {
+    pub change: Change,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct FieldChange {
+    pub name: String,
+    pub revision: String,
+    pub current: String,
+}
+
+/// Changes from a revision to the current version of its cue card.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RevisionDiff {
+    pub revision: String,
+    pub metadata: Vec<FieldChange>,
+    pub content: Vec<DiffLine>,
+}
+
+/// Compares two texts line by line, using their longest common subsequence of lines.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let line = |change, text: &str| DiffLine {
+        change,
+        text: text.to_string(),
+    };
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(line(Change::Unchanged, old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(line(Change::Removed, old[i]));
+            i += 1;
+        } else {
+            lines.push(line(Change::Added, new[j]));
+            j += 1;
+        }
+    }
+
+    lines.extend(old[i..].iter().map(|text| line(Change::Removed, text)));
+    lines.extend(new[j..].iter().map(|text| line(Change::Added, text)));
+
+    lines
+}
+
+pub fn diff(revision: &CuecardRevision, cuecard: &Cuecard) -> RevisionDiff {
+    let fields = [
+        ("title", &revision.title, &cuecard.title),
+        (
+            "choreographer",
+            &revision.choreographer,
+            &cuecard.choreographer,
+        ),
+        ("phase", &revision.phase, &cuecard.phase),
+        ("rhythm", &revision.rhythm, &cuecard.rhythm),
+        ("steplevel", &revision.steplevel, &cuecard.steplevel),
+        ("difficulty", &revision.difficulty, &cuecard.difficulty),
+        ("meta", &revision.meta, &cuecard.meta),
+    ];
+
+    let metadata = fields
+        .iter()
+        .filter(|(_, revision, current)| revision != current)
+        .map(|(name, revision, current)| FieldChange {
+            name: name.to_string(),
+            revision: revision.to_string(),
+            current: current.to_string(),
+        })
+        .collect();
+
+    RevisionDiff {
+        revision: revision.uuid.clone(),
+        metadata,
+        content: diff_lines(&revision.content, &cuecard.content),
+    }
+}
+
+/// Returns the content of the metadata file with the metadata of `revision`, the music file of
+/// `cuecard` is kept.
+fn metadata_file_content(revision: &CuecardRevision, cuecard: &Cuecard) -> String {
+    let mut metadata: HashMap<String, String> =
+        serde_json::from_str::<HashMap<String, serde_json::Value>>(&revision.meta)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(key, value)| value.as_str().map(|value| (key, value.to_string())))
+            .collect();
+
+    // the columns are what the cue card was listed with
+    let columns = [
+        ("phase", &revision.phase),
+        ("rhythm", &revision.rhythm),
+        ("choreographer", &revision.choreographer),
+        ("difficulty", &revision.difficulty),
+        ("steplevel", &revision.steplevel),
+    ];
+
+    for (key, value) in columns.iter() {
+        metadata.insert(key.to_string(), value.to_string());
+    }
+
+    metadata.insert("music_file".to_string(), cuecard.music_file.clone());
+
+    cuecard_indexer::metadata_file_content(&metadata)
+}
+
+/// Restores the content and metadata of a revision and writes the content to the file of the
+/// cue card at `path`. Nothing is changed if the file can't be written.
+pub fn restore(
+    revision: &CuecardRevision,
+    cuecard: &Cuecard,
+    path: &Path,
+    conn: &DBConnection,
+) -> io::Result<Cuecard> {
+    let mut write_error = None;
+
+    let result = conn.transaction::<_, diesel::result::Error, _>(|| {
+        record(cuecard, cuer_database::REVISION_SOURCE_RESTORE, conn)?;
+
+        CuecardData {
+            uuid: &cuecard.uuid,
+            phase: &revision.phase,
+            rhythm: &revision.rhythm,
+            title: &revision.title,
+            steplevel: &revision.steplevel,
+            difficulty: &revision.difficulty,
+            choreographer: &revision.choreographer,
+            meta: &revision.meta,
+            content: &revision.content,
+            karaoke_marks: &cuecard.karaoke_marks,
+            music_file: &cuecard.music_file,
+            file_path: &cuecard.file_path,
+            date_created: &cuecard.date_created,
+            date_modified: &Utc::now().format("%FT%T%.3fZ").to_string(),
+        }
+        .update(cuecard, conn)?;
+
+        if let Err(err) = cuecards::write_file(path, &revision.content) {
+            write_error = Some(err);
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+
+        let metadata_file = cuecard_indexer::metadata_file_path(path);
+        let metadata = metadata_file_content(revision, cuecard);
+
+        if let Err(err) = cuecards::write_file(&metadata_file, &metadata) {
+            // the content must match the metadata kept in the database
+            if let Err(err) = cuecards::write_file(path, &cuecard.content) {
+                error!("Error resetting the content of {:?}: {:?}", path, err);
+            }
+            write_error = Some(err);
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+
+        cuer_database::cuecard_by_uuid(&cuecard.uuid, conn)
+    });
+
+    match (result, write_error) {
+        (_, Some(err)) => Err(err),
+        (Ok(cuecard), None) => {
+            cuecards::mark_indexed(&cuecard, path);
+            Ok(cuecard)
+        }
+        (Err(err), None) => Err(io::Error::other(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(lines: &[DiffLine]) -> Vec<(&Change, &str)> {
+        lines
+            .iter()
+            .map(|line| (&line.change, line.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let lines = diff_lines(
+            "# Waltz\nINTRO\n1-4 Wait\nPART A\n",
+            "# Waltz\nINTRO\n1-4 Wait 2\nPART A\n5-8 Box\n",
+        );
+
+        assert_eq!(
+            changes(&lines),
+            vec![
+                (&Change::Unchanged, "# Waltz"),
+                (&Change::Unchanged, "INTRO"),
+                (&Change::Removed, "1-4 Wait"),
+                (&Change::Added, "1-4 Wait 2"),
+                (&Change::Unchanged, "PART A"),
+                (&Change::Added, "5-8 Box"),
+            ]
+        );
+        assert_eq!(
+            changes(&diff_lines("", "# Waltz")),
+            vec![(&Change::Added, "# Waltz")]
+        );
+        assert_eq!(
+            changes(&diff_lines("# Waltz", "")),
+            vec![(&Change::Removed, "# Waltz")]
+        );
+    }
+}
@@ -1,25 +1,56 @@
-use crate::convert;
+use crate::audio::{AudioFile, DurationCache};
+use crate::bundle;
+use crate::calendar;
+use crate::calendar::{CalendarEvent, CalendarImport};
 use crate::cuecards;
-use crate::guards::{BackendConfig, FileNameHeader};
+use crate::cuelog;
+use crate::export;
+use crate::generator;
+use crate::generator::{Candidate, DraftTip, GeneratorOptions};
+use crate::guards::{BackendConfig, FileNameHeader, IfMatch};
+use crate::importing;
+use crate::importing::ConversionPreview;
+use crate::planning;
+use crate::planning::TipPlan;
+use crate::printing;
 use crate::programming;
+use crate::programming::{EventDeletion, ListedEvent, TipOrder};
+use crate::recurrence;
+use crate::recurrence::Rule;
+use crate::revisions;
+use crate::revisions::RevisionDiff;
+use crate::sandbox;
+use crate::schedule;
+use crate::schedule::Schedule;
+use crate::stats;
+use crate::stats::{CuecardStats, GroupStats};
+use crate::tempo;
+use crate::templates;
+use crate::templates::{CopiedTips, TemplateTip};
+use crate::versions::{Rejection, Versioned};
 use comrak::{markdown_to_html, ComrakOptions};
+use cuecard_indexer::convert;
+use cuecard_indexer::import;
+use cuecard_indexer::import::ImportedFile;
+use cuecard_indexer::placement;
 use cuer_database;
-use cuer_database::models::Cuecard;
+use cuer_database::models::{Cuecard, CuecardRevision};
 use cuer_database::models::{
-    CuecardData, Event, EventData, Program, ProgramData, Tag, Tip, TipCuecard, TipCuecardData,
-    TipData,
+    Cue, CuecardData, Event, EventData, EventSeries, EventSeriesData, Program, ProgramData,
+    ProgramTemplate, Tag, Tip, TipCuecard, TipCuecardData, TipData,
 };
 use log::{error, info};
 use uuidcrate::Uuid;
 
 use std::convert::From;
 use std::io;
-use std::io::Write;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use tempfile;
 
 use rocket::data::Data;
-use rocket::http::Status;
+use rocket::http::{ContentType, Status};
 use rocket::response::{content, NamedFile};
 use rocket::State;
 use rocket_contrib::json::Json;
@@ -31,7 +62,7 @@ use duct::cmd;
 use base64::decode;
 
 use super::DbConn;
-use diesel::QueryResult;
+use diesel::{Connection, QueryResult};
 
 use diesel_migrations::{any_pending_migrations, run_pending_migrations};
 use walkdir::WalkDir;
@@ -46,6 +77,17 @@ pub struct FormEvent<'a> {
     date_modified: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct FormEventSeries {
+    name: String,
+    date_start: String,
+    date_end: String,
+    rrule: String,
+    /// Comma separated dates of cancelled occurrences
+    #[serde(default)]
+    exceptions: String,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct FullTip {
     //Tip including cue cards
@@ -57,6 +99,7 @@ pub struct FullTip {
     date_end: String,
     cuecards: Vec<Cuecard>,
     tip_cuecards: Vec<TipCuecard>,
+    plan: Option<TipPlan>,
 }
 
 impl From<Tip> for FullTip {
@@ -70,6 +113,7 @@ impl From<Tip> for FullTip {
             date_end: tip.date_end,
             cuecards: Vec::new(),
             tip_cuecards: Vec::new(),
+            plan: None,
         }
     }
 }
@@ -91,14 +135,39 @@ pub struct FormTip {
     date_end: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct FormDraftTip {
+    name: String,
+    date_start: String,
+    date_end: String,
+    cuecards: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FormCue {
+    cuecard_uuid: String,
+    event_uuid: Option<String>,
+    tip_uuid: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct FormTipCuecard {
     tip_uuid: String,
     cuecard_uuid: String,
+    /// Ignored when a cue card is added, it is appended to the tip
+    #[serde(default)]
     sort_order: i32,
     cued_at: Option<String>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct FormTipOrder {
+    cuecards: Vec<String>,
+    /// Tip from which cue cards are moved
+    #[serde(default)]
+    from_tip: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct FormCuecardMarks {
     karaoke_marks: String,
@@ -112,7 +181,6 @@ pub struct FormTag {
 #[derive(Serialize, Deserialize)]
 pub struct FormNotes {
     notes: String,
-    date_modified: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -134,12 +202,16 @@ pub struct FormCuecardContent {
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Settings {
-   pub minutes_per_tip: u32
+   pub minutes_per_tip: u32,
+   pub track_gap_seconds: u32
 }
 
 #[get("/v2/settings")]
 pub fn get_settings(config: State<BackendConfig>) -> Result<Json<Settings>, Status> {
-    let settings = Settings { minutes_per_tip: config.minutes_per_tip};
+    let settings = Settings {
+        minutes_per_tip: config.minutes_per_tip,
+        track_gap_seconds: config.track_gap_seconds,
+    };
     
     Ok(Json(settings))
 }
@@ -169,28 +241,43 @@ pub fn cuecard_content_by_uuid(
 }
 
 #[post("/v2/cuecards/<uuid>/content", format="application/json", data="<content>")]
-pub fn post_cuecard_content_by_uuid(uuid: String, content: Json<FormCuecardContent>, conn: DbConn, config: State<BackendConfig>) -> Result<(), Status> {
+pub fn post_cuecard_content_by_uuid(
+    uuid: String,
+    content: Json<FormCuecardContent>,
+    if_match: IfMatch,
+    conn: DbConn,
+    config: State<BackendConfig>,
+) -> Result<Versioned<()>, Rejection<Json<Cuecard>>> {
     let data = content.into_inner();
 
     let cuecard = match cuer_database::cuecard_by_uuid(&uuid, &conn) {
         Ok(cuecard) => cuecard,
-        Err(_) => return Err(Status::NotFound),
+        Err(_) => return Err(Status::NotFound.into()),
     };
 
-    match programming::set_content(cuecard.id, &data.content, &conn) {
-        Ok(_) => (),
-        Err(_) => return Err(Status::BadRequest),
+    if !if_match.matches(&cuecard.date_modified) {
+        return Err(Rejection::Conflict(Json(cuecard)));
     }
 
-    let mut path = PathBuf::from(&config.cuecards_lib_dir);
-    path.push(&cuecard.file_path);
+    let path = match sandbox::resolve(&config.cuecards_lib_dir, &cuecard.file_path) {
+        Ok(path) => path,
+        Err(err) => {
+            error!("Invalid cuecard path {:?}: {:?}", cuecard.file_path, err);
+            return Err(sandbox::error_status(&err).into());
+        }
+    };
 
-    match std::fs::write(path, data.content) {
-        Ok(_) => (),
-        Err(_) => return Err(Status::BadRequest)
+    match cuecards::save_content(&cuecard, &data.content, &path, &conn) {
+        Ok(Some(cuecard)) => Ok(Versioned((), cuecard.date_modified)),
+        Ok(None) => match cuer_database::cuecard_by_uuid(&uuid, &conn) {
+            Ok(cuecard) => Err(Rejection::Conflict(Json(cuecard))),
+            Err(_) => Err(Status::NotFound.into()),
+        },
+        Err(err) => {
+            error!("Error saving content of cuecard {}: {:?}", uuid, err);
+            Err(Status::InternalServerError.into())
+        }
     }
-
-    Ok(())
 }
 
 #[get("/v2/cuecards/<uuid>")]
@@ -201,6 +288,119 @@ pub fn get_cuecard_by_uuid(uuid: String, conn: DbConn) -> Result<Json<Cuecard>,
     }
 }
 
+/// Renders a cue card as an ODT or DOCX document, depending on `format`.
+#[get("/v2/cuecards/<uuid>/export?<format>")]
+pub fn export_cuecard<'r>(
+    uuid: String,
+    format: String,
+    conn: DbConn,
+) -> Result<rocket::Response<'r>, Status> {
+    let format = match export::Format::from_name(&format) {
+        Some(format) => format,
+        None => return Err(Status::BadRequest),
+    };
+
+    let cuecard = match cuer_database::cuecard_by_uuid(&uuid, &conn) {
+        Ok(cuecard) => cuecard,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let document = match export::export(&cuecard, format) {
+        Ok(document) => document,
+        Err(err) => {
+            error!("Error exporting cue card {}: {:?}", uuid, err);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    let (top, sub) = format.media_type();
+
+    Ok(rocket::Response::build()
+        .header(ContentType::new(top, sub))
+        .raw_header(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"{}.{}\"",
+                file_name_of(&cuecard.title),
+                format.extension()
+            ),
+        )
+        .sized_body(io::Cursor::new(document))
+        .finalize())
+}
+
+#[get("/v2/cuecards/<uuid>/revisions")]
+pub fn get_cuecard_revisions(
+    uuid: String,
+    conn: DbConn,
+) -> Result<Json<Vec<CuecardRevision>>, Status> {
+    let cuecard = match cuer_database::cuecard_by_uuid(&uuid, &conn) {
+        Ok(cuecard) => cuecard,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    match revisions::revisions_of(&cuecard, &conn) {
+        Ok(revisions) => Ok(Json(revisions)),
+        Err(err) => {
+            error!("Error loading revisions of cuecard {}: {:?}", uuid, err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// Compares a revision with the current version of the cue card.
+#[get("/v2/cuecards/<uuid>/revisions/<revision>/diff")]
+pub fn get_cuecard_revision_diff(
+    uuid: String,
+    revision: String,
+    conn: DbConn,
+) -> Result<Json<RevisionDiff>, Status> {
+    let cuecard = match cuer_database::cuecard_by_uuid(&uuid, &conn) {
+        Ok(cuecard) => cuecard,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    match revisions::revision_by_uuid(&cuecard, &revision, &conn) {
+        Ok(revision) => Ok(Json(revisions::diff(&revision, &cuecard))),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+/// Restores a revision, the replaced version is kept as a revision.
+#[post("/v2/cuecards/<uuid>/revisions/<revision>/restore")]
+pub fn restore_cuecard_revision(
+    uuid: String,
+    revision: String,
+    conn: DbConn,
+    config: State<BackendConfig>,
+) -> Result<Json<Cuecard>, Status> {
+    let cuecard = match cuer_database::cuecard_by_uuid(&uuid, &conn) {
+        Ok(cuecard) => cuecard,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let revision = match revisions::revision_by_uuid(&cuecard, &revision, &conn) {
+        Ok(revision) => revision,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let path = match sandbox::resolve(&config.cuecards_lib_dir, &cuecard.file_path) {
+        Ok(path) => path,
+        Err(err) => {
+            error!("Invalid cuecard path {:?}: {:?}", cuecard.file_path, err);
+            return Err(sandbox::error_status(&err));
+        }
+    };
+
+    match revisions::restore(&revision, &cuecard, &path, &conn) {
+        Ok(cuecard) => Ok(Json(cuecard)),
+        Err(err) => {
+            error!("Error restoring revision {} of cuecard {}: {:?}", revision.uuid, uuid, err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
 #[post(
     "/v2/cuecards/<uuid>/marks",
     format = "application/json",
@@ -231,11 +431,13 @@ pub fn get_cuecard_metadata(
         Err(_) => return Err(Status::NotFound),
     };
 
-    let base_path = PathBuf::from(&config.cuecards_lib_dir);
-
-    let path = base_path
-        .join(PathBuf::from(cuecard.file_path))
-        .with_extension(".meta.json");
+    let path = match sandbox::resolve(&config.cuecards_lib_dir, &cuecard.file_path) {
+        Ok(path) => path.with_extension(".meta.json"),
+        Err(err) => {
+            error!("Invalid cuecard path {:?}: {:?}", cuecard.file_path, err);
+            return Err(sandbox::error_status(&err));
+        }
+    };
 
     if path.exists() {
         match serde_json::from_str::<FormMetaData>(&std::fs::read_to_string(path).unwrap()) {
@@ -258,39 +460,38 @@ pub fn get_cuecard_metadata(
 pub fn set_cuecard_metadata(
     uuid: String,
     metadata: Json<FormMetaData>,
+    if_match: IfMatch,
     conn: DbConn,
     config: State<BackendConfig>,
-) -> Result<(), Status> {
+) -> Result<Versioned<()>, Rejection<Json<Cuecard>>> {
     let data = metadata.into_inner();
 
     let cuecard = match cuer_database::cuecard_by_uuid(&uuid, &conn) {
         Ok(cuecard) => cuecard,
-        Err(_) => return Err(Status::NotFound),
+        Err(_) => return Err(Status::NotFound.into()),
     };
 
-    let base_path = PathBuf::from(&config.cuecards_lib_dir);
+    if !if_match.matches(&cuecard.date_modified) {
+        return Err(Rejection::Conflict(Json(cuecard)));
+    }
 
-    let path = base_path
-        .join(PathBuf::from(&cuecard.file_path))
-        .with_extension(".meta.json");
+    let path = match sandbox::resolve(&config.cuecards_lib_dir, &cuecard.file_path) {
+        Ok(path) => path.with_extension(".meta.json"),
+        Err(err) => {
+            error!("Invalid cuecard path {:?}: {:?}", cuecard.file_path, err);
+            return Err(sandbox::error_status(&err).into());
+        }
+    };
 
     let serialized_data = match serde_json::to_string(&data) {
         Ok(m) => m,
         Err(err) => {
             error!("Error converting metadata: {:?}", err);
-            return Err(Status::BadRequest);
-        }
-    };
-
-    match std::fs::write(&path, &serialized_data) {
-        Ok(_) => (),
-        Err(error) => {
-            error!("Error writing metadata to file {:?}: {:?}", path, error);
-            return Err(Status::BadRequest);
+            return Err(Status::BadRequest.into());
         }
     };
 
-    let time = Utc::now();
+    let date_modified = Utc::now().format("%FT%T%.3fZ").to_string();
 
     let cuecard_data = CuecardData {
         uuid: &cuecard.uuid,
@@ -306,14 +507,50 @@ pub fn set_cuecard_metadata(
         music_file: &data.music_file.unwrap_or_default(),
         file_path: &cuecard.file_path,
         date_created: &cuecard.date_created,
-        date_modified: &time.format("%FT%T%.3fZ").to_string(),
+        date_modified: &date_modified,
     };
 
-    match cuecard_data.update(&cuecard, &conn) {
-        Ok(_) => Ok(()),
-        Err(err) => {
+    let changed = cuecard_data.phase != cuecard.phase
+        || cuecard_data.rhythm != cuecard.rhythm
+        || cuecard_data.steplevel != cuecard.steplevel
+        || cuecard_data.difficulty != cuecard.difficulty
+        || cuecard_data.choreographer != cuecard.choreographer
+        || cuecard_data.meta != cuecard.meta;
+
+    let mut modified = false;
+    let mut write_error = None;
+
+    let result = conn.transaction::<_, diesel::result::Error, _>(|| {
+        if changed {
+            revisions::record(&cuecard, cuer_database::REVISION_SOURCE_EDITOR, &conn)?;
+        }
+
+        if cuecard_data.update_if_unchanged(&cuecard, &conn)? == 0 {
+            modified = true;
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+
+        if let Err(err) = std::fs::write(&path, &serialized_data) {
+            write_error = Some(err);
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+
+        Ok(())
+    });
+
+    match (result, write_error) {
+        (Ok(_), _) => Ok(Versioned((), date_modified)),
+        (Err(_), Some(err)) => {
+            error!("Error writing metadata to file {:?}: {:?}", path, err);
+            Err(Status::BadRequest.into())
+        }
+        (Err(_), None) if modified => match cuer_database::cuecard_by_uuid(&uuid, &conn) {
+            Ok(cuecard) => Err(Rejection::Conflict(Json(cuecard))),
+            Err(_) => Err(Status::NotFound.into()),
+        },
+        (Err(err), None) => {
             error!("Error saving metadata to database: {:?}", err);
-            Err(Status::BadRequest)
+            Err(Status::BadRequest.into())
         }
     }
 }
@@ -328,28 +565,126 @@ pub fn cued_at(uuid: String, conn: DbConn) -> Result<(), Status> {
     let time = Local::now();
 
     let timestamp = time.format("%FT%T").to_string();
+    let cued_at = time.format("%FT%T%.3fZ").to_string();
 
-    match cuecards::get_tip_cuecard_to_current_event(&cuecard, &timestamp, &conn) {
-        Ok(tip_cuecards) => {
-            for tip_cuecard in tip_cuecards.iter() {
-                let tip_cuecard_data = TipCuecardData {
-                    tip_id: &tip_cuecard.tip_id,
-                    cuecard_id: &cuecard.id,
-                    sort_order: &tip_cuecard.sort_order,
-                    cued_at: Some(time.format("%FT%T%.3fZ").to_string()),
-                };
+    let tip_cuecards = match cuecards::get_tip_cuecard_to_current_event(&cuecard, &timestamp, &conn) {
+        Ok(tip_cuecards) => tip_cuecards,
+        Err(_) => return Err(Status::BadRequest),
+    };
 
-                match programming::update_tip_cuecard(&tip_cuecard_data, &conn) {
-                    Ok(_) => (),
-                    Err(_) => return Err(Status::BadRequest),
-                }
-            }
-            Ok(())
+    for tip_cuecard in tip_cuecards.iter() {
+        let tip_cuecard_data = TipCuecardData {
+            tip_id: &tip_cuecard.tip_id,
+            cuecard_id: &cuecard.id,
+            sort_order: &tip_cuecard.sort_order,
+            cued_at: Some(cued_at.clone()),
+        };
+
+        match programming::update_tip_cuecard(&tip_cuecard_data, &conn) {
+            Ok(_) => (),
+            Err(_) => return Err(Status::BadRequest),
+        }
+
+        if let Err(err) = cuelog::record(&cuecard, None, Some(tip_cuecard.tip_id), &cued_at, &conn) {
+            error!("Error logging cue of {}: {:?}", uuid, err);
+            return Err(Status::BadRequest);
+        }
+    }
+
+    if tip_cuecards.is_empty() {
+        // not in the program, but possibly played at a running event
+        let event = match cuelog::events_at(&timestamp, &conn) {
+            Ok(events) => events.into_iter().next(),
+            Err(_) => return Err(Status::BadRequest),
+        };
+
+        if let Err(err) = cuelog::record(&cuecard, event.map(|e| e.id), None, &cued_at, &conn) {
+            error!("Error logging cue of {}: {:?}", uuid, err);
+            return Err(Status::BadRequest);
+        }
+    }
+
+    Ok(())
+}
+
+#[post("/v2/cues", format = "application/json", data = "<cue>")]
+pub fn start_cue(cue: Json<FormCue>, conn: DbConn) -> Result<Json<Cue>, Status> {
+    let data = cue.into_inner();
+
+    let cuecard = match cuer_database::cuecard_by_uuid(&data.cuecard_uuid, &conn) {
+        Ok(cuecard) => cuecard,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let event_id = match data.event_uuid {
+        Some(event_uuid) => match programming::event_by_uuid(&event_uuid, &conn) {
+            Ok(event) => Some(event.id),
+            Err(_) => return Err(Status::NotFound),
+        },
+        None => None,
+    };
+
+    let tip_id = match data.tip_uuid {
+        Some(tip_uuid) => match programming::get_tip_by_uuid(&tip_uuid, &conn) {
+            Ok(tip) => Some(tip.id),
+            Err(_) => return Err(Status::NotFound),
+        },
+        None => None,
+    };
+
+    let started_at = Local::now().format("%FT%T%.3fZ").to_string();
+
+    match cuelog::record(&cuecard, event_id, tip_id, &started_at, &conn) {
+        Ok(cue) => Ok(Json(cue)),
+        Err(diesel::result::Error::NotFound) => Err(Status::NotFound),
+        Err(err) => {
+            error!("Error logging cue of {}: {:?}", cuecard.uuid, err);
+            Err(Status::BadRequest)
         }
-        Err(_) => Err(Status::BadRequest),
     }
 }
 
+#[post("/v2/cues/<uuid>/stop")]
+pub fn stop_cue(uuid: String, conn: DbConn) -> Result<Json<Cue>, Status> {
+    let cue = match cuelog::cue_by_uuid(&uuid, &conn) {
+        Ok(cue) => cue,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let stopped_at = Local::now().format("%FT%T%.3fZ").to_string();
+
+    cuelog::stop(&cue, &stopped_at, &conn)
+        .map(Json)
+        .or_else(|_| Err(Status::BadRequest))
+}
+
+#[get("/v2/cues?<event>&<cuecard>")]
+pub fn get_cues(
+    event: Option<String>,
+    cuecard: Option<String>,
+    conn: DbConn,
+) -> Result<Json<Vec<Cue>>, Status> {
+    let event = match event {
+        Some(uuid) => match programming::event_by_uuid(&uuid, &conn) {
+            Ok(event) => Some(event),
+            Err(_) => return Err(Status::NotFound),
+        },
+        None => None,
+    };
+
+    let cuecard = match cuecard {
+        Some(uuid) => match cuer_database::cuecard_by_uuid(&uuid, &conn) {
+            Ok(cuecard) => Some(cuecard),
+            Err(_) => return Err(Status::NotFound),
+        },
+        None => None,
+    };
+
+    cuelog::cues(event.as_ref(), cuecard.as_ref(), &conn)
+        .map(Json)
+        .or_else(|_| Err(Status::BadRequest))
+}
+
 #[get("/v2/cuecards")]
 pub fn get_all_cuecards(conn: DbConn) -> Result<Json<Vec<Cuecard>>, Status> {
     match cuecards::get_all(&conn) {
@@ -363,11 +698,22 @@ pub fn search_cuecards(query: String, conn: DbConn) -> QueryResult<Json<Vec<Cuec
     cuecards::search_cuecards(&query, &conn).map(Json)
 }
 
-#[delete("/v2/events/<uuid>")]
-pub fn delete_event(uuid: String, conn: DbConn) -> Result<Json<Event>, Status> {
-    programming::delete_event(&uuid, &conn)
-        .map(Json)
-        .or_else(|_| Err(Status::NotFound))
+/// Deletes an event with its program. With `dry_run` only the rows which would be removed are
+/// returned.
+#[delete("/v2/events/<uuid>?<dry_run>")]
+pub fn delete_event(
+    uuid: String,
+    dry_run: Option<bool>,
+    conn: DbConn,
+) -> Result<Json<EventDeletion>, Status> {
+    match programming::delete_event(&uuid, dry_run.unwrap_or(false), &conn) {
+        Ok(deletion) => Ok(Json(deletion)),
+        Err(diesel::result::Error::NotFound) => Err(Status::NotFound),
+        Err(err) => {
+            error!("Error deleting event {}: {:?}", uuid, err);
+            Err(Status::InternalServerError)
+        }
+    }
 }
 
 #[get("/v2/events/<uuid>")]
@@ -377,12 +723,12 @@ pub fn event_by_uuid(uuid: String, conn: DbConn) -> Result<Json<Event>, Status>
         .or_else(|_| Err(Status::NotFound))
 }
 
-#[get("/v2/events/<min_date>/<max_date>")]
+#[get("/v2/events/<min_date>/<max_date>", rank = 1)]
 pub fn get_events(
     conn: DbConn,
     min_date: String,
     max_date: String,
-) -> Result<Json<Vec<Event>>, Status> {
+) -> Result<Json<Vec<ListedEvent>>, Status> {
     let start_date = DateTime::parse_from_rfc3339(min_date.as_str());
     let end_date = DateTime::parse_from_rfc3339(max_date.as_str());
 
@@ -401,84 +747,503 @@ pub fn get_events(
         return Err(Status::BadRequest);
     }
 
-    programming::get_events(&conn, start_date.to_rfc3339(), end_date.to_rfc3339())
+    programming::get_events(&conn, start_date, end_date)
         .map(Json)
         .or_else(|_| Err(Status::BadRequest))
 }
 
-#[put("/v2/event", format = "application/json", data = "<event>")]
-pub fn create_event(event: Json<FormEvent>, conn: DbConn) -> Result<Json<Event>, Status> {
-    let data = event.into_inner();
-    let u = Uuid::new_v4().to_hyphenated().to_string();
-
-    let e = EventData {
-        uuid: &u,
-        name: &data.name,
-        date_start: &data.date_start,
-        date_end: &data.date_end,
-        schedule: data.schedule,
-        date_created: &data.date_created,
-        date_modified: &data.date_modified,
+#[get("/v2/events.ics?<from>&<to>&<tag>")]
+pub fn events_calendar(
+    from: Option<String>,
+    to: Option<String>,
+    tag: Option<String>,
+    conn: DbConn,
+) -> Result<content::Content<String>, Status> {
+    // without a range the calendar covers the last three months and the next year
+    let now = Local::now();
+    let range = |time: Option<String>, default: DateTime<Local>| match time {
+        Some(time) => DateTime::parse_from_rfc3339(&time).map_err(|_| Status::BadRequest),
+        None => Ok(default.with_timezone(default.offset())),
     };
 
-    let event = programming::create_event(&e, &conn);
+    let from = range(from, now - chrono::Duration::days(90))?;
+    let to = range(to, now + chrono::Duration::days(365))?;
 
-    match event {
-        Ok(event) => {
-            let u = Uuid::new_v4().to_hyphenated().to_string();
+    let events = match programming::get_events(&conn, from, to) {
+        Ok(events) => events,
+        Err(_) => return Err(Status::BadRequest),
+    };
 
-            {
-                let p = ProgramData {
-                    uuid: &u,
-                    notes: None,
-                    event_id: event.id,
-                    date_created: &data.date_created,
-                    date_modified: &data.date_modified,
+    let series: HashMap<i32, String> = programming::get_event_series(&conn)
+        .unwrap_or_else(|_| Vec::new())
+        .into_iter()
+        .map(|s| (s.id, s.uuid))
+        .collect();
+
+    let mut entries = Vec::with_capacity(events.len());
+
+    for entry in events {
+        let (uid, name, date_start, date_end, description, categories) = match entry {
+            ListedEvent::Event(event) => {
+                let categories: Vec<String> = programming::get_event_tags(&event, &conn)
+                    .unwrap_or_else(|_| Vec::new())
+                    .into_iter()
+                    .map(|t| t.tag)
+                    .collect();
+
+                let description = programming::event_program(&event.uuid, &conn)
+                    .ok()
+                    .map(|program| calendar::program_description(&program));
+
+                // occurrences keep their uid when they become events
+                let series_uuid = event.series_id.and_then(|id| series.get(&id));
+                let uid = match (series_uuid, &event.recurrence_id) {
+                    (Some(series_uuid), Some(date)) => format!("{}-{}", series_uuid, date),
+                    _ => event.uuid.clone(),
                 };
 
-                p.create(&conn).unwrap();
+                (uid, event.name, event.date_start, event.date_end, description, categories)
+            }
+            // occurrences of a series don't have tags or a program before they are events
+            ListedEvent::Occurrence(occurrence) => (
+                format!("{}-{}", occurrence.series_uuid, occurrence.recurrence_id),
+                occurrence.name,
+                occurrence.date_start,
+                occurrence.date_end,
+                None,
+                Vec::new(),
+            ),
+        };
+
+        if let Some(tag) = &tag {
+            if !categories.contains(tag) {
+                continue;
             }
+        }
 
-            Ok(Json(event))
+        let start = DateTime::parse_from_rfc3339(&date_start);
+        let end = DateTime::parse_from_rfc3339(&date_end);
+
+        match (start, end) {
+            (Ok(start), Ok(end)) => entries.push(CalendarEvent {
+                uid,
+                summary: name,
+                start: start.with_timezone(&Utc),
+                end: end.with_timezone(&Utc),
+                description,
+                categories,
+            }),
+            _ => error!("Invalid time of event {}, not added to the calendar", uid),
         }
-        Err(_) => Err(Status::BadRequest),
     }
-}
 
-#[get("/v2/event/program/<event_id>")]
-pub fn get_program(event_id: i32, conn: DbConn) -> Result<Json<Option<Program>>, Status> {
-    programming::program_by_event_id(event_id, &conn)
-        .map(|p| match p {
-            Some(p) => {
-                let options = ComrakOptions {
-                    ext_tasklist: true,
-                    ..ComrakOptions::default()
-                };
-                let markdown =
-                    markdown_to_html(p.notes.unwrap_or_else(|| "".to_string()).as_str(), &options);
-                let program = Program {
-                    id: p.id,
-                    uuid: p.uuid,
-                    notes: Some(markdown),
-                    event_id: p.event_id,
-                    date_created: p.date_created,
-                    date_modified: p.date_modified,
-                };
-                Json(Some(program))
-            }
-            None => Json(None),
-        })
-        .or_else(|_| Err(Status::NotFound))
+    Ok(content::Content(
+        ContentType::Calendar,
+        calendar::to_ics("Cuer Manager", &entries, Utc::now()),
+    ))
 }
 
-#[get("/v2/event/<event_id>/program/notes")]
-pub fn get_program_notes(event_id: i32, conn: DbConn) -> Result<String, Status> {
-    match programming::program_by_event_id(event_id, &conn) {
-        Ok(p) => match p {
-            Some(p) => Ok(p.notes.unwrap_or_else(|| "".to_owned())),
-            None => Ok("".to_owned()),
-        },
-        Err(_) => Err(Status::NotFound),
+#[post("/v2/events.ics", format = "text/calendar", data = "<data>")]
+pub fn import_calendar(data: Data, conn: DbConn) -> Result<Json<CalendarImport>, Status> {
+    let mut input = String::new();
+
+    if let Err(err) = data.open().take(10 * 1024 * 1024).read_to_string(&mut input) {
+        error!("Error reading calendar: {:?}", err);
+        return Err(Status::BadRequest);
+    }
+
+    let mut warnings = Vec::new();
+    let events = calendar::parse_ics(&input, &mut warnings);
+
+    match calendar::import(events, warnings, &conn) {
+        Ok(report) => Ok(Json(report)),
+        Err(err) => {
+            error!("Error importing calendar: {:?}", err);
+            Err(Status::BadRequest)
+        }
+    }
+}
+
+#[get("/v2/events/<uuid>/tags")]
+pub fn get_event_tags(uuid: String, conn: DbConn) -> Result<Json<Vec<Tag>>, Status> {
+    let event = match programming::event_by_uuid(&uuid, &conn) {
+        Ok(event) => event,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    match programming::get_event_tags(&event, &conn) {
+        Ok(tags) => Ok(Json(tags)),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+#[post(
+    "/v2/events/<uuid>/tags",
+    format = "application/json",
+    data = "<tagdata>"
+)]
+pub fn add_event_tag(uuid: String, tagdata: Json<FormTag>, conn: DbConn) -> Result<(), Status> {
+    let event = match programming::event_by_uuid(&uuid, &conn) {
+        Ok(event) => event,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    match programming::add_event_tag(&tagdata.tag, &event, &conn) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(Status::BadRequest),
+    }
+}
+
+#[delete("/v2/events/<uuid>/tag/<tag>")]
+pub fn remove_event_tag(uuid: String, tag: String, conn: DbConn) -> Result<(), Status> {
+    let event = match programming::event_by_uuid(&uuid, &conn) {
+        Ok(event) => event,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    match cuecards::get_tag_by_name(&tag, &conn) {
+        Ok(tag) => match programming::remove_event_tag(&tag, &event, &conn) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(Status::BadRequest),
+        },
+        Err(_) => Err(Status::BadRequest),
+    }
+}
+
+#[get("/v2/events/<uuid>/program.html")]
+pub fn program_html(uuid: String, conn: DbConn) -> Result<content::Html<String>, Status> {
+    match programming::event_program(&uuid, &conn) {
+        Ok(program) => Ok(content::Html(printing::to_html(&program))),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+#[get("/v2/events/<uuid>/program.pdf")]
+pub fn program_pdf(uuid: String, conn: DbConn) -> Result<content::Content<Vec<u8>>, Status> {
+    let program = match programming::event_program(&uuid, &conn) {
+        Ok(program) => program,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    match printing::to_pdf(&program) {
+        Ok(pdf) => Ok(content::Content(ContentType::PDF, pdf)),
+        Err(err) => {
+            error!("Error rendering program of event {}: {:?}", uuid, err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// Replaces everything but letters, digits, `-` and `_` so the name can be used as file name.
+fn file_name_of(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+#[get("/v2/events/<uuid>/bundle.zip")]
+pub fn export_bundle<'r>(
+    uuid: String,
+    conn: DbConn,
+    config: State<BackendConfig>,
+) -> Result<rocket::Response<'r>, Status> {
+    let program = match programming::event_program(&uuid, &conn) {
+        Ok(program) => program,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    // music files make bundles too large to be kept in memory
+    let mut file = match tempfile::tempfile() {
+        Ok(file) => file,
+        Err(err) => {
+            error!("Error creating temporary file: {:?}", err);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    let result = bundle::export(&program, &config, &conn, &mut file)
+        .and_then(|_| file.seek(SeekFrom::Start(0)));
+
+    if let Err(err) = result {
+        error!("Error exporting bundle of event {}: {:?}", uuid, err);
+        return Err(Status::InternalServerError);
+    }
+
+    Ok(rocket::Response::build()
+        .header(ContentType::ZIP)
+        .raw_header(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"{}.zip\"",
+                file_name_of(&program.event.name)
+            ),
+        )
+        .sized_body(file)
+        .finalize())
+}
+
+#[post("/v2/bundles", format = "application/octet-stream", data = "<data>")]
+pub fn import_bundle(
+    data: Data,
+    conn: DbConn,
+    config: State<BackendConfig>,
+) -> Result<Json<bundle::ImportReport>, Status> {
+    let mut file = match tempfile::tempfile() {
+        Ok(file) => file,
+        Err(err) => {
+            error!("Error creating temporary file: {:?}", err);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    match data.stream_to(&mut file).and_then(|_| file.seek(SeekFrom::Start(0))) {
+        Ok(_) => (),
+        Err(err) => {
+            error!("Error writing to temporary file: {:?}", err);
+            return Err(Status::BadRequest);
+        }
+    }
+
+    match bundle::import(file, &config, &conn) {
+        Ok(report) => Ok(Json(report)),
+        Err(err) => {
+            error!("Error importing bundle: {:?}", err);
+            Err(Status::BadRequest)
+        }
+    }
+}
+
+#[put("/v2/event", format = "application/json", data = "<event>")]
+pub fn create_event(event: Json<FormEvent>, conn: DbConn) -> Result<Json<Event>, Status> {
+    let data = event.into_inner();
+    let u = Uuid::new_v4().to_hyphenated().to_string();
+
+    if let Some(Err(err)) = data.schedule.map(serde_json::from_str::<Schedule>) {
+        error!("Invalid schedule of event {:?}: {}", data.name, err);
+        return Err(Status::BadRequest);
+    }
+
+    let e = EventData {
+        uuid: &u,
+        name: &data.name,
+        date_start: &data.date_start,
+        date_end: &data.date_end,
+        schedule: data.schedule,
+        date_created: &data.date_created,
+        date_modified: &data.date_modified,
+        series_id: None,
+        recurrence_id: None,
+    };
+
+    let event = programming::create_event(&e, &conn);
+
+    match event {
+        Ok(event) => {
+            let u = Uuid::new_v4().to_hyphenated().to_string();
+
+            {
+                let p = ProgramData {
+                    uuid: &u,
+                    notes: None,
+                    event_id: event.id,
+                    date_created: &data.date_created,
+                    date_modified: &data.date_modified,
+                };
+
+                p.create(&conn).unwrap();
+            }
+
+            Ok(Json(event))
+        }
+        Err(_) => Err(Status::BadRequest),
+    }
+}
+
+/// Checks the recurrence rule, exceptions and times of an event series.
+fn validate_series(series: &FormEventSeries) -> Result<(), String> {
+    let rule = series.rrule.parse::<Rule>()?;
+    recurrence::parse_exceptions(&series.exceptions)?;
+
+    if rule.interval > recurrence::MAX_INTERVAL {
+        return Err(format!("The interval is larger than {}", recurrence::MAX_INTERVAL));
+    }
+
+    if rule.count.is_some_and(|count| count > recurrence::MAX_COUNT) {
+        return Err(format!("The count is larger than {}", recurrence::MAX_COUNT));
+    }
+
+    let start = DateTime::parse_from_rfc3339(&series.date_start).map_err(|e| e.to_string())?;
+    let end = DateTime::parse_from_rfc3339(&series.date_end).map_err(|e| e.to_string())?;
+
+    if end < start {
+        return Err("The series ends before it starts".to_string());
+    }
+
+    Ok(())
+}
+
+#[get("/v2/event_series")]
+pub fn get_event_series(conn: DbConn) -> Result<Json<Vec<EventSeries>>, Status> {
+    programming::get_event_series(&conn)
+        .map(Json)
+        .or_else(|_| Err(Status::BadRequest))
+}
+
+#[put("/v2/event_series", format = "application/json", data = "<series>")]
+pub fn create_event_series(
+    series: Json<FormEventSeries>,
+    conn: DbConn,
+) -> Result<Json<EventSeries>, Status> {
+    let data = series.into_inner();
+
+    if let Err(err) = validate_series(&data) {
+        error!("Invalid event series {:?}: {}", data.name, err);
+        return Err(Status::BadRequest);
+    }
+
+    let u = Uuid::new_v4().to_hyphenated().to_string();
+    let now = Local::now().format("%FT%T%.3fZ").to_string();
+
+    let series_data = EventSeriesData {
+        uuid: &u,
+        name: &data.name,
+        date_start: &data.date_start,
+        date_end: &data.date_end,
+        rrule: &data.rrule,
+        exceptions: &data.exceptions,
+        date_created: &now,
+        date_modified: &now,
+    };
+
+    match series_data.create(&conn) {
+        Ok(series) => Ok(Json(series)),
+        Err(_) => Err(Status::BadRequest),
+    }
+}
+
+#[post("/v2/event_series/<uuid>", format = "application/json", data = "<series>")]
+pub fn update_event_series(
+    uuid: String,
+    series: Json<FormEventSeries>,
+    conn: DbConn,
+) -> Result<Json<EventSeries>, Status> {
+    let data = series.into_inner();
+
+    let series = match programming::event_series_by_uuid(&uuid, &conn) {
+        Ok(series) => series,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    if let Err(err) = validate_series(&data) {
+        error!("Invalid event series {:?}: {}", data.name, err);
+        return Err(Status::BadRequest);
+    }
+
+    let now = Local::now().format("%FT%T%.3fZ").to_string();
+
+    let series_data = EventSeriesData {
+        uuid: &series.uuid,
+        name: &data.name,
+        date_start: &data.date_start,
+        date_end: &data.date_end,
+        rrule: &data.rrule,
+        exceptions: &data.exceptions,
+        date_created: &series.date_created,
+        date_modified: &now,
+    };
+
+    match series_data.update(&conn) {
+        Ok(series) => Ok(Json(series)),
+        Err(_) => Err(Status::BadRequest),
+    }
+}
+
+/// Deletes an event series. Occurrences which already are events are kept.
+#[delete("/v2/event_series/<uuid>")]
+pub fn delete_event_series(uuid: String, conn: DbConn) -> Result<Json<EventSeries>, Status> {
+    let series = match programming::event_series_by_uuid(&uuid, &conn) {
+        Ok(series) => series,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    match series.delete(&conn) {
+        Ok(_) => Ok(Json(series)),
+        Err(_) => Err(Status::BadRequest),
+    }
+}
+
+/// Turns the occurrence of a series on `date` into an event, which is required before it can
+/// be programmed.
+#[put("/v2/event_series/<series_id>/occurrences/<date>")]
+pub fn materialize_occurrence(
+    series_id: i32,
+    date: String,
+    conn: DbConn,
+) -> Result<Json<Event>, Status> {
+    let date = match NaiveDate::parse_from_str(&date, recurrence::DATE_FORMAT) {
+        Ok(date) => date,
+        Err(_) => return Err(Status::BadRequest),
+    };
+
+    let series = match programming::event_series_by_id(series_id, &conn) {
+        Ok(series) => series,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    match programming::materialize_occurrence(&series, date, &conn) {
+        Ok(event) => Ok(Json(event)),
+        Err(diesel::result::Error::NotFound) => Err(Status::NotFound),
+        Err(err) => {
+            error!("Error creating the event of series {} on {}: {:?}", series.uuid, date, err);
+            Err(Status::BadRequest)
+        }
+    }
+}
+
+/// Returns the program of an event. Occurrences of a series which are not events yet have no id
+/// and are not found.
+#[get("/v2/event/program/<event_id>")]
+pub fn get_program(event_id: i32, conn: DbConn) -> Result<Json<Option<Program>>, Status> {
+    if programming::event_by_id(event_id, &conn).is_err() {
+        return Err(Status::NotFound);
+    }
+
+    programming::program_by_event_id(event_id, &conn)
+        .map(|p| match p {
+            Some(p) => {
+                let options = ComrakOptions {
+                    ext_tasklist: true,
+                    ..ComrakOptions::default()
+                };
+                let markdown =
+                    markdown_to_html(p.notes.unwrap_or_else(|| "".to_string()).as_str(), &options);
+                let program = Program {
+                    id: p.id,
+                    uuid: p.uuid,
+                    notes: Some(markdown),
+                    event_id: p.event_id,
+                    date_created: p.date_created,
+                    date_modified: p.date_modified,
+                };
+                Json(Some(program))
+            }
+            None => Json(None),
+        })
+        .or_else(|_| Err(Status::NotFound))
+}
+
+#[get("/v2/event/<event_id>/program/notes")]
+pub fn get_program_notes(event_id: i32, conn: DbConn) -> Result<String, Status> {
+    if programming::event_by_id(event_id, &conn).is_err() {
+        return Err(Status::NotFound);
+    }
+
+    match programming::program_by_event_id(event_id, &conn) {
+        Ok(p) => match p {
+            Some(p) => Ok(p.notes.unwrap_or_else(|| "".to_owned())),
+            None => Ok("".to_owned()),
+        },
+        Err(_) => Err(Status::NotFound),
     }
 }
 
@@ -490,57 +1255,565 @@ pub fn get_program_notes(event_id: i32, conn: DbConn) -> Result<String, Status>
 pub fn update_program_notes(
     program_id: i32,
     notes: Json<FormNotes>,
+    if_match: IfMatch,
     conn: DbConn,
-) -> Result<String, Status> {
+) -> Result<Versioned<String>, Rejection<Json<Program>>> {
     let program = programming::get_program_by_id(program_id, &conn);
 
     let data = notes.into_inner();
 
     match program {
         Ok(p) => {
+            if !if_match.matches(&p.date_modified) {
+                return Err(Rejection::Conflict(Json(p)));
+            }
+
+            let date_modified = Utc::now().format("%FT%T%.3fZ").to_string();
+
             let program_data = ProgramData {
                 uuid: &p.uuid,
                 notes: Some(&data.notes),
                 event_id: p.event_id,
                 date_created: &p.date_created,
-                date_modified: &data.date_modified,
+                date_modified: &date_modified,
             };
 
-            match program_data.update(&conn) {
+            match program_data.update_if_unchanged(&p, &conn) {
+                // the program was modified since it was read
+                Ok(0) => match programming::get_program_by_id(program_id, &conn) {
+                    Ok(current) => Err(Rejection::Conflict(Json(current))),
+                    Err(_) => Err(Status::NotFound.into()),
+                },
                 Ok(_) => {
                     let options = ComrakOptions {
                         ext_tasklist: true,
                         ..ComrakOptions::default()
                     };
-                    Ok(markdown_to_html(&data.notes.as_str(), &options))
+                    let notes = markdown_to_html(&data.notes.as_str(), &options);
+
+                    Ok(Versioned(notes, date_modified))
                 }
-                Err(_) => Err(Status::BadRequest),
+                Err(_) => Err(Status::BadRequest.into()),
+            }
+        }
+        Err(_) => Err(Status::NotFound.into()),
+    }
+}
+
+/// Returns the playing time of the music of `cuecard` in whole seconds.
+fn track_duration(cuecard: &Cuecard, config: &BackendConfig, durations: &DurationCache) -> Option<u32> {
+    if cuecard.music_file.is_empty() {
+        return None;
+    }
+
+    sandbox::resolve_existing(&config.music_files_dir, &cuecard.music_file)
+        .and_then(|path| durations.duration(&path))
+        .map(|seconds| seconds.round() as u32)
+        .map_err(|err| info!("No duration for music file {:?}: {}", cuecard.music_file, err))
+        .ok()
+}
+
+#[get("/v2/tips/<program_id>")]
+pub fn get_tips(
+    program_id: i32,
+    conn: DbConn,
+    config: State<BackendConfig>,
+    durations: State<DurationCache>,
+) -> Result<Json<Vec<FullTip>>, Status> {
+    programming::tips_by_program_id(program_id, &conn)
+        .map(|tips| {
+            let mut result: Vec<FullTip> = Vec::with_capacity(tips.len());
+            for tip in tips.into_iter() {
+                let cuecards =
+                    programming::get_cuecards(&tip, &conn).unwrap_or_else(|_| Vec::new());
+
+                let tip_cuecards =
+                    programming::get_tip_cuecards(&tip, &conn).unwrap_or_else(|_| Vec::new());
+
+                let track_durations = cuecards
+                    .iter()
+                    .map(|c| track_duration(c, &config, &durations))
+                    .collect();
+                let plan = planning::plan(
+                    track_durations,
+                    config.track_gap_seconds,
+                    planning::slot_seconds(&tip, config.minutes_per_tip),
+                );
+
+                let mut full_tip = FullTip::from(tip);
+                full_tip.plan = Some(plan);
+                full_tip.cuecards = cuecards;
+                full_tip.tip_cuecards = tip_cuecards;
+                result.push(full_tip)
             }
+            Json(result)
+        })
+        .or_else(|_| Err(Status::BadRequest))
+}
+
+/// Collects the tip and cue card data of `tip` for the response.
+fn full_tip(tip: Tip, conn: &DbConn) -> FullTip {
+    let cuecards = programming::get_cuecards(&tip, conn).unwrap_or_else(|_| Vec::new());
+    let tip_cuecards = programming::get_tip_cuecards(&tip, conn).unwrap_or_else(|_| Vec::new());
+
+    let mut full_tip = FullTip::from(tip);
+    full_tip.cuecards = cuecards;
+    full_tip.tip_cuecards = tip_cuecards;
+    full_tip
+}
+
+#[post(
+    "/v2/events/<uuid>/program/draft",
+    format = "application/json",
+    data = "<options>"
+)]
+pub fn generate_program_draft(
+    uuid: String,
+    options: Json<GeneratorOptions>,
+    conn: DbConn,
+    config: State<BackendConfig>,
+    durations: State<DurationCache>,
+) -> Result<Json<Vec<DraftTip>>, Status> {
+    let options = options.into_inner();
+
+    if let Err(err) = generator::validate(&options) {
+        error!("Invalid draft options for event {}: {}", uuid, err);
+        return Err(Status::BadRequest);
+    }
+
+    let event = match programming::event_by_uuid(&uuid, &conn) {
+        Ok(event) => event,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let program = match programming::program_by_event_id(event.id, &conn) {
+        Ok(Some(program)) => program,
+        _ => return Err(Status::NotFound),
+    };
+
+    let tips = match programming::tips_by_program_id(program.id, &conn) {
+        Ok(tips) => tips,
+        Err(_) => return Err(Status::BadRequest),
+    };
+
+    // the draft continues after the last tip already planned
+    let mut start = match DateTime::parse_from_rfc3339(&event.date_start) {
+        Ok(start) => start,
+        Err(err) => {
+            error!("Invalid start of event {}: {:?}", uuid, err);
+            return Err(Status::BadRequest);
+        }
+    };
+
+    let mut programmed = std::collections::HashSet::new();
+
+    for tip in &tips {
+        if let Ok(end) = DateTime::parse_from_rfc3339(&tip.date_end) {
+            start = std::cmp::max(start, end);
+        }
+
+        for cuecard in programming::get_cuecards(tip, &conn).unwrap_or_else(|_| Vec::new()) {
+            programmed.insert(cuecard.id);
+        }
+    }
+
+    let (cuecards, mut last_cued) = match (cuecards::get_all(&conn), cuecards::last_cued(&conn)) {
+        (Ok(cuecards), Ok(last_cued)) => (cuecards, last_cued),
+        _ => return Err(Status::BadRequest),
+    };
+
+    let candidates = cuecards
+        .into_iter()
+        .filter(|c| !programmed.contains(&c.id))
+        .map(|cuecard| Candidate {
+            last_cued: last_cued.remove(&cuecard.id),
+            duration: track_duration(&cuecard, &config, &durations),
+            cuecard,
+        })
+        .collect();
+
+    Ok(Json(generator::generate(
+        &options,
+        candidates,
+        start,
+        tips.len() + 1,
+        config.minutes_per_tip,
+        config.track_gap_seconds,
+    )))
+}
+
+#[put(
+    "/v2/events/<uuid>/program/draft",
+    format = "application/json",
+    data = "<draft>"
+)]
+pub fn accept_program_draft(
+    uuid: String,
+    draft: Json<Vec<FormDraftTip>>,
+    conn: DbConn,
+) -> Result<Json<Vec<FullTip>>, Status> {
+    let draft = draft.into_inner();
+
+    let event = match programming::event_by_uuid(&uuid, &conn) {
+        Ok(event) => event,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let program = match programming::program_by_event_id(event.id, &conn) {
+        Ok(Some(program)) => program,
+        _ => return Err(Status::NotFound),
+    };
+
+    let result = change_tips(program.id, &conn, || {
+        let mut tips = Vec::with_capacity(draft.len());
+
+        for draft_tip in &draft {
+            let u = Uuid::new_v4().to_hyphenated().to_string();
+
+            let tip = programming::create_tip(
+                &TipData {
+                    name: &draft_tip.name,
+                    uuid: &u,
+                    program_id: &program.id,
+                    date_start: &draft_tip.date_start,
+                    date_end: &draft_tip.date_end,
+                },
+                &conn,
+            )?;
+
+            for (i, cuecard_uuid) in draft_tip.cuecards.iter().enumerate() {
+                let cuecard = cuer_database::cuecard_by_uuid(cuecard_uuid, &conn)?;
+
+                programming::create_tip_cuecard(
+                    &TipCuecardData {
+                        tip_id: &tip.id,
+                        cuecard_id: &cuecard.id,
+                        sort_order: &(i as i32 + 1),
+                        cued_at: None,
+                    },
+                    &conn,
+                )?;
+            }
+
+            tips.push(tip);
+        }
+
+        Ok(tips)
+    });
+
+    result.map(|tips| Json(tips.into_iter().map(|t| full_tip(t, &conn)).collect()))
+}
+
+/// Applies `change` to the tips of a program in a transaction. The change is rolled back if
+/// it leads to overlapping tips or tips outside of the time of the event.
+fn change_tips<T, F>(program_id: i32, conn: &DbConn, change: F) -> Result<T, Status>
+where
+    F: FnOnce() -> QueryResult<T>,
+{
+    let program = match programming::get_program_by_id(program_id, conn) {
+        Ok(program) => program,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let mut problems = Vec::new();
+
+    let result = conn.transaction::<_, diesel::result::Error, _>(|| {
+        // problems which already existed don't prevent other changes
+        let before = programming::tip_problems(&program, conn)?;
+        let value = change()?;
+
+        problems = programming::tip_problems(&program, conn)?
+            .into_iter()
+            .filter(|p| !before.iter().any(|b| b.same_as(p)))
+            .map(|p| p.message)
+            .collect();
+
+        if problems.is_empty() {
+            Ok(value)
+        } else {
+            Err(diesel::result::Error::RollbackTransaction)
         }
+    });
+
+    match result {
+        Ok(value) => Ok(value),
+        Err(diesel::result::Error::RollbackTransaction) => {
+            error!("Rejected change of program {}: {}", program.uuid, problems.join(", "));
+            Err(Status::Conflict)
+        }
+        Err(diesel::result::Error::NotFound) => Err(Status::NotFound),
+        Err(err) => {
+            error!("Error changing the tips of program {}: {:?}", program.uuid, err);
+            Err(Status::BadRequest)
+        }
+    }
+}
+
+#[get("/v2/events/<uuid>/schedule")]
+pub fn get_event_schedule(uuid: String, conn: DbConn) -> Result<Json<Schedule>, Status> {
+    match programming::event_by_uuid(&uuid, &conn) {
+        Ok(event) => Ok(Json(schedule::of_event(&event).unwrap_or_default())),
+        Err(_) => Err(Status::NotFound),
+    }
+}
+
+#[put(
+    "/v2/events/<uuid>/schedule",
+    format = "application/json",
+    data = "<event_schedule>"
+)]
+pub fn set_event_schedule(
+    uuid: String,
+    event_schedule: Json<Schedule>,
+    conn: DbConn,
+) -> Result<Json<Event>, Status> {
+    let event_schedule = event_schedule.into_inner();
+
+    let event = match programming::event_by_uuid(&uuid, &conn) {
+        Ok(event) => event,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let problems = schedule::validate(&event_schedule, &event);
+    if !problems.is_empty() {
+        error!("Invalid schedule of event {}: {}", uuid, problems.join(", "));
+        return Err(Status::BadRequest);
+    }
+
+    let event_schedule = if event_schedule.blocks.is_empty() {
+        None
+    } else {
+        Some(&event_schedule)
+    };
+
+    match programming::set_event_schedule(&event, event_schedule, &conn) {
+        Ok(event) => Ok(Json(event)),
+        Err(_) => Err(Status::BadRequest),
+    }
+}
+
+/// Lists the problems of the schedule and the tips of an event.
+#[get("/v2/events/<uuid>/schedule/problems")]
+pub fn schedule_problems(uuid: String, conn: DbConn) -> Result<Json<Vec<String>>, Status> {
+    let event = match programming::event_by_uuid(&uuid, &conn) {
+        Ok(event) => event,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let mut problems = match schedule::of_event(&event) {
+        Some(event_schedule) => schedule::validate(&event_schedule, &event),
+        None => Vec::new(),
+    };
+
+    match programming::program_by_event_id(event.id, &conn) {
+        Ok(Some(program)) => match programming::tip_problems(&program, &conn) {
+            Ok(tip_problems) => problems.extend(tip_problems.into_iter().map(|p| p.message)),
+            Err(_) => return Err(Status::BadRequest),
+        },
+        Ok(None) => (),
+        Err(_) => return Err(Status::BadRequest),
+    }
+
+    Ok(Json(problems))
+}
+
+/// Adds the tips of the schedule to the program of an event.
+#[post("/v2/events/<uuid>/schedule/tips")]
+pub fn create_scheduled_tips(
+    uuid: String,
+    conn: DbConn,
+    config: State<BackendConfig>,
+) -> Result<Json<Vec<FullTip>>, Status> {
+    let event = match programming::event_by_uuid(&uuid, &conn) {
+        Ok(event) => event,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let event_schedule = match schedule::of_event(&event) {
+        Some(event_schedule) => event_schedule,
+        None => return Err(Status::NotFound),
+    };
+
+    let program = match programming::program_by_event_id(event.id, &conn) {
+        Ok(Some(program)) => program,
+        _ => return Err(Status::NotFound),
+    };
+
+    let existing = match programming::tips_by_program_id(program.id, &conn) {
+        Ok(tips) => tips.len(),
+        Err(_) => return Err(Status::BadRequest),
+    };
+
+    let scheduled = schedule::tips(&event_schedule, existing + 1, config.minutes_per_tip);
+
+    let result = change_tips(program.id, &conn, || {
+        let mut tips = Vec::with_capacity(scheduled.len());
+
+        for scheduled_tip in &scheduled {
+            let u = Uuid::new_v4().to_hyphenated().to_string();
+
+            tips.push(programming::create_tip(
+                &TipData {
+                    name: &scheduled_tip.name,
+                    uuid: &u,
+                    program_id: &program.id,
+                    date_start: &scheduled_tip.date_start,
+                    date_end: &scheduled_tip.date_end,
+                },
+                &conn,
+            )?);
+        }
+
+        Ok(tips)
+    });
+
+    result.map(|tips| Json(tips.into_iter().map(FullTip::from).collect()))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FormProgramTemplate {
+    name: String,
+}
+
+/// Copies tips, cue cards and notes into the program of an event, shifted to its start.
+fn copy_into_event(
+    uuid: &str,
+    tips: &[TemplateTip],
+    notes: Option<&str>,
+    conn: &DbConn,
+) -> Result<CopiedTips, Status> {
+    let event = match programming::event_by_uuid(uuid, conn) {
+        Ok(event) => event,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let start = match DateTime::parse_from_rfc3339(&event.date_start) {
+        Ok(start) => start,
+        Err(_) => return Err(Status::BadRequest),
+    };
+
+    let program = match programming::program_by_event_id(event.id, conn) {
+        Ok(Some(program)) => program,
+        _ => return Err(Status::NotFound),
+    };
+
+    let result = change_tips(program.id, conn, || {
+        templates::apply(tips, notes, &program, start, conn)
+    })?;
+
+    if !result.missing_cuecards.is_empty() {
+        info!(
+            "Skipped missing cue cards copying into event {}: {}",
+            uuid,
+            result.missing_cuecards.join(", ")
+        );
+    }
+
+    Ok(result)
+}
+
+/// Copies the program of the event `source` into the program of the event `uuid`, which must
+/// be another event.
+#[post("/v2/events/<uuid>/program/from_event/<source>")]
+pub fn copy_program(
+    uuid: String,
+    source: String,
+    conn: DbConn,
+    config: State<BackendConfig>,
+) -> Result<Json<CopiedTips>, Status> {
+    if source == uuid {
+        error!("Program of event {} can't be copied into itself", uuid);
+        return Err(Status::BadRequest);
+    }
+
+    let source = match programming::event_program(&source, &conn) {
+        Ok(source) => source,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let tips = templates::template_tips(&source, config.minutes_per_tip);
+
+    copy_into_event(&uuid, &tips, source.program.notes.as_deref(), &conn).map(Json)
+}
+
+/// Saves the program of an event as a template.
+#[post("/v2/events/<uuid>/program/template", format = "application/json", data = "<template>")]
+pub fn create_program_template(
+    uuid: String,
+    template: Json<FormProgramTemplate>,
+    conn: DbConn,
+    config: State<BackendConfig>,
+) -> Result<Json<ProgramTemplate>, Status> {
+    let name = template.name.trim();
+
+    if name.is_empty() {
+        return Err(Status::BadRequest);
+    }
+
+    let program = match programming::event_program(&uuid, &conn) {
+        Ok(program) => program,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    match templates::create_template(name, &program, config.minutes_per_tip, &conn) {
+        Ok(template) => Ok(Json(template)),
+        Err(err) => {
+            error!("Error saving the program of event {} as template: {:?}", uuid, err);
+            Err(Status::BadRequest)
+        }
+    }
+}
+
+#[get("/v2/program_templates")]
+pub fn get_program_templates(conn: DbConn) -> Result<Json<Vec<ProgramTemplate>>, Status> {
+    match templates::get_templates(&conn) {
+        Ok(result) => Ok(Json(result)),
+        Err(_) => Err(Status::BadRequest),
+    }
+}
+
+#[get("/v2/program_templates/<uuid>")]
+pub fn get_program_template(uuid: String, conn: DbConn) -> Result<Json<ProgramTemplate>, Status> {
+    match templates::template_by_uuid(&uuid, &conn) {
+        Ok(template) => Ok(Json(template)),
         Err(_) => Err(Status::NotFound),
     }
 }
 
-#[get("/v2/tips/<program_id>")]
-pub fn get_tips(program_id: i32, conn: DbConn) -> Result<Json<Vec<FullTip>>, Status> {
-    programming::tips_by_program_id(program_id, &conn)
-        .map(|tips| {
-            let mut result: Vec<FullTip> = Vec::with_capacity(tips.len());
-            for tip in tips.into_iter() {
-                let cuecards =
-                    programming::get_cuecards(&tip, &conn).unwrap_or_else(|_| Vec::new());
+#[delete("/v2/program_templates/<uuid>")]
+pub fn delete_program_template(uuid: String, conn: DbConn) -> Result<Json<()>, Status> {
+    let template = match templates::template_by_uuid(&uuid, &conn) {
+        Ok(template) => template,
+        Err(_) => return Err(Status::NotFound),
+    };
 
-                let tip_cuecards =
-                    programming::get_tip_cuecards(&tip, &conn).unwrap_or_else(|_| Vec::new());
+    match template.delete(&conn) {
+        Ok(_) => Ok(Json(())),
+        Err(_) => Err(Status::BadRequest),
+    }
+}
 
-                let mut full_tip = FullTip::from(tip);
-                full_tip.cuecards = cuecards;
-                full_tip.tip_cuecards = tip_cuecards;
-                result.push(full_tip)
-            }
-            Json(result)
-        })
-        .or_else(|_| Err(Status::BadRequest))
+/// Adds the tips of a template to the program of an event.
+#[post("/v2/events/<uuid>/program/from_template/<template_uuid>")]
+pub fn apply_program_template(
+    uuid: String,
+    template_uuid: String,
+    conn: DbConn,
+) -> Result<Json<CopiedTips>, Status> {
+    let template = match templates::template_by_uuid(&template_uuid, &conn) {
+        Ok(template) => template,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let tips = match templates::tips_of(&template) {
+        Ok(tips) => tips,
+        Err(err) => {
+            error!("Invalid tips in template {}: {:?}", template.uuid, err);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    copy_into_event(&uuid, &tips, template.notes.as_deref(), &conn).map(Json)
 }
 
 #[put("/v2/tips", format = "application/json", data = "<tip>")]
@@ -556,12 +1829,11 @@ pub fn create_tip(tip: Json<FormTip>, conn: DbConn) -> Result<Json<FullTip>, Sta
         date_end: &data.date_end,
     };
 
-    let result = programming::create_tip(&tip_data, &conn);
+    let result = change_tips(data.program_id, &conn, || {
+        programming::create_tip(&tip_data, &conn)
+    });
 
-    match result {
-        Ok(result) => Ok(Json(FullTip::from(result))),
-        Err(_) => Err(Status::BadRequest),
-    }
+    result.map(|tip| Json(FullTip::from(tip)))
 }
 
 #[post("/v2/tips", format = "application/json", data = "<tip>")]
@@ -581,10 +1853,7 @@ pub fn update_tip(tip: Json<FormUpdateTip>, conn: DbConn) -> Result<Json<Tip>, S
         date_end: &data.date_end,
     };
 
-    match tip_data.update(&conn) {
-        Ok(tip) => Ok(Json(tip)),
-        Err(_err) => Err(Status::BadRequest),
-    }
+    change_tips(tip.program_id, &conn, || tip_data.update(&conn)).map(Json)
 }
 
 #[delete("/v2/tips/<tip_uuid>", format = "application/json")]
@@ -619,14 +1888,7 @@ pub fn create_tip_cuecard(
         Err(_) => return Err(Status::NotFound),
     };
 
-    let tip_cuecard_data = TipCuecardData {
-        tip_id: &tip.id,
-        cuecard_id: &cuecard.id,
-        sort_order: &data.sort_order,
-        cued_at: None,
-    };
-
-    let result = programming::create_tip_cuecard(&tip_cuecard_data, &conn);
+    let result = programming::append_tip_cuecard(&tip, &cuecard, &conn);
 
     match result {
         Ok(_) => Ok(Json(())),
@@ -666,6 +1928,63 @@ pub fn update_tip_cuecard(
     }
 }
 
+/// Sets the order of all cue cards of a tip. Cue cards can be moved from another tip of the
+/// same program with `from_tip`. Cue cards of the tip missing in the list are a conflict, cue
+/// cards in neither tip a bad request.
+#[put("/v2/tips/<uuid>/order", format = "application/json", data = "<order>")]
+pub fn order_tip_cuecards(
+    uuid: String,
+    order: Json<FormTipOrder>,
+    conn: DbConn,
+) -> Result<Json<Vec<TipCuecard>>, Status> {
+    let data = order.into_inner();
+
+    let tip = match cuer_database::tip_by_uuid(&uuid, &conn) {
+        Ok(tip) => tip,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    let from = match &data.from_tip {
+        Some(from_uuid) => match cuer_database::tip_by_uuid(from_uuid, &conn) {
+            Ok(from) => Some(from),
+            Err(_) => return Err(Status::NotFound),
+        },
+        None => None,
+    };
+
+    if from
+        .as_ref()
+        .is_some_and(|from| from.id == tip.id || from.program_id != tip.program_id)
+    {
+        return Err(Status::BadRequest);
+    }
+
+    let mut cuecards: Vec<Cuecard> = Vec::with_capacity(data.cuecards.len());
+
+    for cuecard_uuid in &data.cuecards {
+        let cuecard = match cuer_database::cuecard_by_uuid(cuecard_uuid, &conn) {
+            Ok(cuecard) => cuecard,
+            Err(_) => return Err(Status::NotFound),
+        };
+
+        if cuecards.iter().any(|c| c.id == cuecard.id) {
+            return Err(Status::BadRequest);
+        }
+
+        cuecards.push(cuecard);
+    }
+
+    match programming::order_tip_cuecards(&tip, &cuecards, from.as_ref(), &conn) {
+        Ok(TipOrder::Ordered(tip_cuecards)) => Ok(Json(tip_cuecards)),
+        Ok(TipOrder::Incomplete) => Err(Status::Conflict),
+        Ok(TipOrder::Unknown) => Err(Status::BadRequest),
+        Err(err) => {
+            error!("Error ordering the cue cards of tip {}: {:?}", tip.uuid, err);
+            Err(Status::BadRequest)
+        }
+    }
+}
+
 #[delete(
     "/v2/tips/<tip_uuid>/cuecard/<cuecard_uuid>",
     format = "application/json"
@@ -705,6 +2024,82 @@ pub fn remove_tip_cuecard(
     }
 }
 
+/// Loads all cue cards and their cues, optionally only the cues of one event.
+fn cueing_history(
+    event: Option<String>,
+    conn: &DbConn,
+) -> Result<(Vec<Cuecard>, Vec<stats::Cue>), Status> {
+    match (cuecards::get_all(conn), stats::cues(event.as_deref(), conn)) {
+        (Ok(cuecards), Ok(cues)) => Ok((cuecards, cues)),
+        (Err(err), _) | (_, Err(err)) => {
+            error!("Error loading cueing history: {:?}", err);
+            Err(Status::BadRequest)
+        }
+    }
+}
+
+#[get("/v2/stats/cuecards?<event>")]
+pub fn cuecard_stats(event: Option<String>, conn: DbConn) -> Result<Json<Vec<CuecardStats>>, Status> {
+    let (cuecards, cues) = cueing_history(event, &conn)?;
+
+    Ok(Json(stats::cuecard_stats(&cuecards, &cues)))
+}
+
+#[get("/v2/stats/events")]
+pub fn event_stats(conn: DbConn) -> Result<Json<Vec<GroupStats>>, Status> {
+    let (cuecards, cues) = cueing_history(None, &conn)?;
+
+    Ok(Json(stats::group_stats(&cuecards, &cues, |_, cue| {
+        cue.event_uuid.clone().zip(cue.event_name.clone())
+    })))
+}
+
+#[get("/v2/stats/rhythms?<event>")]
+pub fn rhythm_stats(event: Option<String>, conn: DbConn) -> Result<Json<Vec<GroupStats>>, Status> {
+    let (cuecards, cues) = cueing_history(event, &conn)?;
+
+    Ok(Json(stats::group_stats(&cuecards, &cues, |cuecard, _| {
+        Some((cuecard.rhythm.trim().to_lowercase(), cuecard.rhythm.trim().to_string()))
+    })))
+}
+
+#[get("/v2/stats/phases?<event>")]
+pub fn phase_stats(event: Option<String>, conn: DbConn) -> Result<Json<Vec<GroupStats>>, Status> {
+    let (cuecards, cues) = cueing_history(event, &conn)?;
+
+    Ok(Json(stats::group_stats(&cuecards, &cues, |cuecard, _| {
+        Some((cuecard.phase.trim().to_uppercase(), cuecard.phase.trim().to_string()))
+    })))
+}
+
+#[get("/v2/stats/never_cued")]
+pub fn never_cued(conn: DbConn) -> Result<Json<Vec<CuecardStats>>, Status> {
+    let (cuecards, cues) = cueing_history(None, &conn)?;
+
+    let mut result: Vec<CuecardStats> = stats::cuecard_stats(&cuecards, &cues)
+        .into_iter()
+        .filter(|s| s.times_cued == 0)
+        .collect();
+    result.sort_by(|a, b| a.title.cmp(&b.title));
+
+    Ok(Json(result))
+}
+
+/// Cue cards not cued in the last `weeks` weeks, six by default.
+#[get("/v2/stats/rotation?<weeks>")]
+pub fn rotation(weeks: Option<u32>, conn: DbConn) -> Result<Json<Vec<CuecardStats>>, Status> {
+    let (cuecards, cues) = cueing_history(None, &conn)?;
+
+    // cue times are stored in the same format, so they can be compared as strings
+    let cutoff = Local::now() - chrono::Duration::weeks(i64::from(weeks.unwrap_or(6)));
+    let cutoff = cutoff.format("%FT%T%.3fZ").to_string();
+
+    Ok(Json(stats::rotation(
+        stats::cuecard_stats(&cuecards, &cues),
+        &cutoff,
+    )))
+}
+
 #[get("/favicon.ico")]
 pub fn favicon() -> io::Result<NamedFile> {
     NamedFile::open("public/favicon.ico")
@@ -732,20 +2127,90 @@ pub struct FormFilename {
     filename: String,
 }
 
+/// Decodes a base64 encoded file name sent by the client.
+fn decode_filename(filename: &str) -> Option<String> {
+    decode(filename)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
 #[post("/v2/audio", format = "application/json", data = "<filedata>")]
-pub fn audio_file(filedata: Json<FormFilename>, config: State<BackendConfig>) -> Option<NamedFile> {
+pub fn audio_file(
+    filedata: Json<FormFilename>,
+    config: State<BackendConfig>,
+) -> Result<NamedFile, Status> {
     let filedata = filedata.into_inner();
 
-    let file_name = match String::from_utf8(decode(&filedata.filename).unwrap()) {
-        Ok(s) => s,
-        Err(_) => return None,
+    let file_name = match decode_filename(&filedata.filename) {
+        Some(s) => s,
+        None => return Err(Status::BadRequest),
     };
 
-    let file_path = Path::new(&file_name);
+    let path = match sandbox::resolve_existing(&config.music_files_dir, &file_name) {
+        Ok(path) => path,
+        Err(err) => {
+            error!("Invalid music file path {:?}: {:?}", file_name, err);
+            return Err(sandbox::error_status(&err));
+        }
+    };
 
-    let path = Path::new(&config.music_files_dir).join(file_path);
+    NamedFile::open(path).map_err(|_| Status::NotFound)
+}
 
-    NamedFile::open(path).ok()
+/// Streams the music file of a cue card, with `speed` a practice version at `speed` percent of
+/// the tempo. The practice version is rendered first, meanwhile the response is 202 Accepted.
+#[get("/v2/audio/<uuid>?<speed>")]
+pub fn stream_audio_file(
+    uuid: String,
+    speed: Option<u32>,
+    conn: DbConn,
+    config: State<BackendConfig>,
+) -> Result<AudioFile, Status> {
+    let cuecard = match cuer_database::cuecard_by_uuid(&uuid, &conn) {
+        Ok(cuecard) => cuecard,
+        Err(_) => return Err(Status::NotFound),
+    };
+
+    if cuecard.music_file.is_empty() {
+        return Err(Status::NotFound);
+    }
+
+    let path = match sandbox::resolve_existing(&config.music_files_dir, &cuecard.music_file) {
+        Ok(path) => path,
+        Err(err) => {
+            error!("Invalid music file path {:?}: {:?}", cuecard.music_file, err);
+            return Err(sandbox::error_status(&err));
+        }
+    };
+
+    let path = match speed {
+        Some(speed) if speed != 100 => {
+            if !tempo::valid_speed(speed) {
+                return Err(Status::BadRequest);
+            }
+
+            match tempo::rendition(&config.derived_files_dir, &path, speed) {
+                Ok(tempo::Rendition::Ready(rendition)) => rendition,
+                // the client asks again until the rendition is ready
+                Ok(tempo::Rendition::Pending) => return Err(Status::Accepted),
+                Err(err) => {
+                    error!("Error rendering {:?} at {}% speed: {:?}", path, speed, err);
+                    return match err.kind() {
+                        io::ErrorKind::NotFound => Err(Status::NotFound),
+                        // the music file can't be decoded
+                        io::ErrorKind::InvalidData => Err(Status::UnsupportedMediaType),
+                        _ => Err(Status::InternalServerError),
+                    };
+                }
+            }
+        }
+        _ => path,
+    };
+
+    AudioFile::open(path).map_err(|err| {
+        error!("Error opening music file for cuecard {}: {:?}", uuid, err);
+        Status::NotFound
+    })
 }
 
 fn refresh_library(config: State<BackendConfig>) -> io::Result<()> {
@@ -777,15 +2242,29 @@ pub fn refresh_cuecards_library(config: State<BackendConfig>) -> Result<(), Stat
 
 #[post("/v2/migrations/run")]
 pub fn run_migrations(conn: DbConn) -> Result<Json<bool>, Status> {
-    match run_pending_migrations(&conn.0) {
+    // migrations rebuild tables, dropping them must not delete the rows referencing them
+    if cuer_database::set_foreign_keys(false, &conn).is_err() {
+        return Err(Status::InternalServerError);
+    }
+
+    let result = run_pending_migrations(&**conn);
+
+    if cuer_database::set_foreign_keys(true, &conn).is_err() {
+        return Err(Status::InternalServerError);
+    }
+
+    match result {
         Ok(()) => Ok(Json(true)),
-        Err(_) => Err(Status::BadRequest),
+        Err(err) => {
+            error!("Error running migrations: {:?}", err);
+            Err(Status::BadRequest)
+        }
     }
 }
 
 #[get("/v2/migrations/check")]
 pub fn check_migrations(conn: DbConn) -> Result<Json<bool>, Status> {
-    match any_pending_migrations(&conn.0) {
+    match any_pending_migrations(&**conn) {
         Ok(result) => Ok(Json(result)),
         Err(_) => Err(Status::BadRequest),
     }
@@ -913,16 +2392,191 @@ fn empty_file_response<'r>() -> rocket::Response<'r> {
         .finalize()
 }
 
+/// Largest uploaded cue sheet which is converted for a preview
+const MAX_CUE_SHEET_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Largest uploaded archive of cue sheets
+const MAX_ARCHIVE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Reads an upload of at most `limit` bytes.
+fn read_upload(data: Data, limit: u64) -> Result<Vec<u8>, Status> {
+    let mut input = Vec::new();
+
+    if let Err(err) = data.open().take(limit + 1).read_to_end(&mut input) {
+        error!("Error reading the uploaded file: {:?}", err);
+        return Err(Status::BadRequest);
+    }
+
+    if input.len() as u64 > limit {
+        error!("Uploaded file is larger than {} bytes", limit);
+        return Err(Status::PayloadTooLarge);
+    }
+
+    Ok(input)
+}
+
+/// Route of older clients, the format is detected like by `convert_any_file`.
 #[post(
     "/v2/convert/odt",
     format = "application/octet-stream",
     data = "<data>"
 )]
-pub fn convert_odt_file<'r>(data: Data, filename: FileNameHeader, config: State<BackendConfig>) -> rocket::Response<'r> {
-    let src_file = tempfile::NamedTempFile::new().unwrap();
-    let file = std::fs::File::create(&src_file).unwrap();
+pub fn convert_odt_file<'r>(
+    data: Data,
+    filename: FileNameHeader,
+    config: State<BackendConfig>,
+    conn: DbConn,
+) -> rocket::Response<'r> {
+    convert_file(data, filename, config, conn)
+}
+
+/// Converts an ODT, DOCX or PDF cue sheet, the format is detected from the content of the file.
+#[post("/v2/convert", format = "application/octet-stream", data = "<data>")]
+pub fn convert_any_file<'r>(
+    data: Data,
+    filename: FileNameHeader,
+    config: State<BackendConfig>,
+    conn: DbConn,
+) -> rocket::Response<'r> {
+    convert_file(data, filename, config, conn)
+}
+
+/// Converts a cue sheet without storing it. The preview contains the metadata the indexer will
+/// read and warnings, so the user can check the result before it is added to the library.
+#[post("/v2/convert/preview", format = "application/octet-stream", data = "<data>")]
+pub fn preview_conversion(
+    data: Data,
+    filename: FileNameHeader,
+    config: State<BackendConfig>,
+    conn: DbConn,
+) -> Result<Json<ConversionPreview>, Status> {
+    let input = read_upload(data, MAX_CUE_SHEET_SIZE)?;
+
+    let mut output = Vec::new();
+
+    let conversion = match convert::convert_to_markdown(&mut io::Cursor::new(input), &mut output) {
+        Ok(conversion) => conversion,
+        Err(err) => {
+            error!("Error converting file to markdown: {}", err);
+            return Err(Status::BadRequest);
+        }
+    };
+
+    let markdown = String::from_utf8_lossy(&output).into_owned();
+    let lib_dir = if config.cuecards_self_managed {
+        None
+    } else {
+        Some(Path::new(&config.cuecards_lib_dir))
+    };
+
+    let template = &config.cuecards_placement;
+
+    match importing::preview(markdown, conversion, &filename.0, lib_dir, template, &conn) {
+        Ok(preview) => Ok(Json(preview)),
+        Err(err) => {
+            error!("Error checking the converted file {}: {:?}", filename.0, err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// Imports the ODT, DOCX and PDF cue sheets of an uploaded zip archive into the library and indexes
+/// them in one run. Existing cue cards are skipped, the result of every file is returned.
+#[post("/v2/convert/batch", format = "application/octet-stream", data = "<data>")]
+pub fn import_cue_sheets(
+    data: Data,
+    config: State<BackendConfig>,
+    conn: DbConn,
+) -> Result<Json<Vec<ImportedFile>>, Status> {
+    if config.cuecards_self_managed {
+        error!("Cue sheets can't be imported into a self managed library");
+        return Err(Status::BadRequest);
+    }
+
+    let input = read_upload(data, MAX_ARCHIVE_SIZE)?;
+
+    let cuecard_paths = match placement::cuecard_paths(&conn) {
+        Ok(paths) => paths,
+        Err(err) => {
+            error!("Error reading the paths of the cue cards: {:?}", err);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    let lib_dir = Path::new(&config.cuecards_lib_dir);
+    let template = &config.cuecards_placement;
+    let input = io::Cursor::new(input);
+    let result = match import::import_archive(input, lib_dir, template, &cuecard_paths) {
+        Ok(result) => result,
+        Err(err) => {
+            error!("Error reading the uploaded archive: {:?}", err);
+            return Err(Status::BadRequest);
+        }
+    };
+
+    if result.iter().any(|f| f.status == import::ImportStatus::Imported) {
+        if let Err(err) = refresh_library(config) {
+            error!("Error indexing the imported cue sheets: {}", err);
+            return Err(Status::InternalServerError);
+        }
+    }
+
+    Ok(Json(result))
+}
+
+/// Adds whether the converted cue card should be checked and why to the headers of a response,
+/// as its body is the converted file or empty.
+fn conversion_response<'r>(
+    mut response: rocket::Response<'r>,
+    conversion: &convert::Conversion,
+) -> rocket::Response<'r> {
+    let warnings = header_text(&conversion.warnings.join("; "));
+
+    response.set_raw_header("X-Needs-Review", conversion.needs_review().to_string());
+    if !warnings.is_empty() {
+        response.set_raw_header("X-Conversion-Warnings", warnings);
+    }
+
+    response
+}
+
+/// Replaces the characters which can't be sent in a header.
+fn header_text(text: &str) -> String {
+    text.chars()
+        .map(|c| if c == ' ' || c.is_ascii_graphic() { c } else { '?' })
+        .collect()
+}
+
+/// Status of a failed conversion or import of a cue sheet.
+fn import_error_status(err: &io::Error) -> Status {
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => Status::Forbidden,
+        _ => Status::InternalServerError,
+    }
+}
+
+/// Converts a cue sheet and adds it to the library or, for self managed libraries, returns the
+/// converted file. Whether the cue card needs a review is returned in the headers, as is the
+/// path `X-Stored-As` if the path of the placement template was taken.
+fn convert_file<'r>(
+    data: Data,
+    filename: FileNameHeader,
+    config: State<BackendConfig>,
+    conn: DbConn,
+) -> rocket::Response<'r> {
+    let temp_file = |suffix: &str| {
+        tempfile::Builder::new().suffix(suffix).tempfile().map_err(|err| {
+            error!("Error creating temporary file: {:?}", err);
+            error_response(Status::InternalServerError)
+        })
+    };
 
-    let mut writer = std::io::BufWriter::new(&file);
+    let src_file = match temp_file("") {
+        Ok(file) => file,
+        Err(response) => return response,
+    };
+
+    let mut writer = std::io::BufWriter::new(src_file.as_file());
     match data.stream_to(&mut writer) {
         Ok(size) => info!("Saved {} bytes to temporary file", size),
         Err(error) => {
@@ -934,52 +2588,101 @@ pub fn convert_odt_file<'r>(data: Data, filename: FileNameHeader, config: State<
         Ok(_) => (),
         Err(err) => {
             error!("Error flushing output file: {:?}", err);
-            return error_response(Status::BadRequest);
+            return error_response(Status::InternalServerError);
         }
     }
+    drop(writer);
 
-    let file = std::fs::File::open(src_file).unwrap();
+    let file = match src_file.reopen() {
+        Ok(file) => file,
+        Err(err) => {
+            error!("Error reading temporary file: {:?}", err);
+            return error_response(Status::InternalServerError);
+        }
+    };
     let mut reader = std::io::BufReader::new(file);
 
-    let target = tempfile::Builder::new().suffix(".md").tempfile().unwrap();
-    let target_file = std::fs::File::create(&target).unwrap();
-    let mut writer = std::io::BufWriter::new(target_file);
+    let target = match temp_file(".md") {
+        Ok(file) => file,
+        Err(response) => return response,
+    };
+    let mut writer = std::io::BufWriter::new(target.as_file());
 
-    match convert::convert_to_markdown(&mut reader, &mut writer) {
-        Ok(_) => {
-            if config.cuecards_self_managed {
-                match NamedFile::open(target) {
-                    Ok(file) => {
-                        let mut p = PathBuf::new();
-                        p.push(&filename.0);
-                        p.set_extension("md");
-
-                        file_response(MarkdownFile(file), p.to_str().unwrap())
-                    },
-                    Err(_) => error_response(Status::BadRequest),
-                }
-            } else {
+    let conversion = match convert::convert_to_markdown(&mut reader, &mut writer) {
+        Ok(conversion) => conversion,
+        Err(error) => {
+            error!("Error converting file to markdown: {}", error);
+            return error_response(Status::BadRequest);
+        }
+    };
+
+    if let Err(err) = writer.flush() {
+        error!("Error flushing the converted file: {:?}", err);
+        return error_response(Status::InternalServerError);
+    }
+    drop(writer);
+
+    if conversion.needs_review() {
+        info!("Conversion of {} needs a review: {:?}", filename.0, conversion.warnings);
+    }
+
+    if config.cuecards_self_managed {
+        return match NamedFile::open(target.path()) {
+            Ok(file) => {
                 let mut p = PathBuf::new();
-                p.push(&config.cuecards_lib_dir);
-                p.push(format!("{}", filename.0.to_lowercase().chars().nth(0).unwrap()));
                 p.push(&filename.0);
                 p.set_extension("md");
-                info!("Path: {:?}", p);
-                let mut builder = std::fs::DirBuilder::new();
-                builder.recursive(true).create(p.as_path().parent().unwrap()).unwrap();
-                writer.flush().unwrap();
-                std::fs::copy(target, p.as_path()).unwrap();
-
-                match refresh_library(config) {
-                    Ok(_) => empty_file_response(),
-                    Err(_) => error_response(Status::BadRequest)
-                }
+
+                conversion_response(
+                    file_response(MarkdownFile(file), &p.to_string_lossy()),
+                    &conversion,
+                )
             }
-        },
-        Err(error) => {
-            error!("Error converting file to markdown: {}", error);
-            error_response(Status::BadRequest)
-        },
+            Err(_) => error_response(Status::BadRequest),
+        };
+    }
+
+    let markdown = match std::fs::read_to_string(target.path()) {
+        Ok(markdown) => markdown,
+        Err(err) => {
+            error!("Error reading the converted file: {:?}", err);
+            return error_response(Status::InternalServerError);
+        }
+    };
+    let cuecard_paths = match placement::cuecard_paths(&conn) {
+        Ok(paths) => paths,
+        Err(err) => {
+            error!("Error reading the paths of the cue cards: {:?}", err);
+            return error_response(Status::InternalServerError);
+        }
+    };
+    let lib_dir = Path::new(&config.cuecards_lib_dir);
+    let template = &config.cuecards_placement;
+    let stored = importing::store(&markdown, &filename.0, lib_dir, template, &cuecard_paths);
+
+    let stored = match stored {
+        Ok(stored) => stored,
+        Err(err) => {
+            error!("Error storing the converted file {}: {:?}", filename.0, err);
+            return error_response(import_error_status(&err));
+        }
+    };
+    info!("Path: {:?}", stored.path);
+
+    match refresh_library(config) {
+        Ok(_) => {
+            let mut response = conversion_response(empty_file_response(), &conversion);
+
+            if stored.path != stored.planned {
+                response.set_raw_header(
+                    "X-Stored-As",
+                    header_text(&stored.path.to_string_lossy()),
+                );
+            }
+
+            response
+        }
+        Err(_) => error_response(Status::BadRequest),
     }
 }
 
@@ -1002,16 +2705,28 @@ pub struct MusicFileEntry {
 pub fn list_music_files(filedata: Json<FormFilename>, config: State<BackendConfig>)  -> Result<Json<Vec<MusicFileEntry>>, Status> {
     let filedata = filedata.into_inner();
 
-    let file_name = match String::from_utf8(decode(&filedata.filename).unwrap()) {
-        Ok(s) => s,
-        Err(_) => return Ok(Json(Vec::default())),
+    let file_name = match decode_filename(&filedata.filename) {
+        Some(s) => s,
+        None => return Ok(Json(Vec::default())),
     };
 
     let file_name = file_name.trim_end();
-    let file_path = Path::new(&file_name);
 
-    let base_path = Path::new(&config.music_files_dir);
-    let path = Path::new(&config.music_files_dir).join(file_path);
+    let base_path = match sandbox::resolve_existing(&config.music_files_dir, "") {
+        Ok(path) => path,
+        Err(err) => {
+            error!("Invalid music files directory: {:?}", err);
+            return Err(sandbox::error_status(&err));
+        }
+    };
+
+    let path = match sandbox::resolve_existing(&base_path, file_name) {
+        Ok(path) => path,
+        Err(err) => {
+            error!("Invalid music file path {:?}: {:?}", file_name, err);
+            return Err(sandbox::error_status(&err));
+        }
+    };
     
     //info!("{:?}", path);
 
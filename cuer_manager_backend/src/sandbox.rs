@@ -0,0 +1,186 @@
+/**
+
+Resolution of client supplied paths below the configured directories.
+
+Music files and cue cards are addressed by paths relative to `music_files_dir` and
+`cuecards_lib_dir`. All such paths are resolved here, so absolute paths, `..` components and
+symbolic links can't be used to read or write files outside of these directories.
+
+**/
+use rocket::http::Status;
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Largest number of dangling symbolic links followed while resolving a path
+const MAX_LINKS: usize = 40;
+
+fn outside_root(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{:?} is outside of the allowed directory", path),
+    )
+}
+
+/// Resolves `relative` below `root` and returns the canonical path.
+///
+/// The target doesn't need to exist, which allows resolving paths of files to be created.
+/// Its deepest existing ancestor is canonicalized, so symbolic links pointing outside of
+/// `root` are rejected as well. Dangling links are followed to the path they would create.
+pub fn resolve<R, P>(root: R, relative: P) -> io::Result<PathBuf>
+where
+    R: AsRef<Path>,
+    P: AsRef<Path>,
+{
+    let relative = relative.as_ref();
+
+    for component in relative.components() {
+        match component {
+            Component::Normal(_) | Component::CurDir => (),
+            _ => return Err(outside_root(relative)),
+        }
+    }
+
+    let root = root.as_ref().canonicalize()?;
+
+    let mut existing = root.join(relative);
+    let mut missing: Vec<PathBuf> = Vec::new();
+    let mut links = 0;
+
+    // `exists` follows links, so dangling links are found by their own metadata
+    loop {
+        match fs::symlink_metadata(&existing) {
+            Ok(_) if existing.exists() => break,
+            Ok(metadata) if metadata.file_type().is_symlink() && links < MAX_LINKS => {
+                let target = fs::read_link(&existing)?;
+                links += 1;
+
+                existing = match existing.parent() {
+                    Some(parent) => parent.join(target),
+                    None => return Err(outside_root(relative)),
+                };
+            }
+            Ok(_) => return Err(outside_root(relative)),
+            Err(_) => match (existing.file_name(), existing.parent()) {
+                (Some(name), Some(parent)) => {
+                    missing.push(PathBuf::from(name));
+                    existing = parent.to_path_buf();
+                }
+                _ => return Err(outside_root(relative)),
+            },
+        }
+    }
+
+    let mut path = existing.canonicalize()?;
+
+    if !path.starts_with(&root) {
+        return Err(outside_root(relative));
+    }
+
+    for name in missing.iter().rev() {
+        path.push(name);
+    }
+
+    Ok(path)
+}
+
+/// Like `resolve` but requires the target to exist.
+pub fn resolve_existing<R, P>(root: R, relative: P) -> io::Result<PathBuf>
+where
+    R: AsRef<Path>,
+    P: AsRef<Path>,
+{
+    let path = resolve(root, relative)?;
+
+    if !path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{:?} not found", path),
+        ));
+    }
+
+    Ok(path)
+}
+
+/// Maps a resolution error to the status of the response.
+pub fn error_status(err: &io::Error) -> Status {
+    match err.kind() {
+        io::ErrorKind::PermissionDenied => Status::Forbidden,
+        io::ErrorKind::NotFound => Status::NotFound,
+        _ => Status::BadRequest,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::write(root.join("a/file.mp3"), "").unwrap();
+        fs::write(dir.path().join("secret"), "").unwrap();
+
+        let canonical_root = root.canonicalize().unwrap();
+
+        assert_eq!(
+            resolve_existing(&root, "a/file.mp3").unwrap(),
+            canonical_root.join("a/file.mp3")
+        );
+        assert_eq!(
+            resolve(&root, "b/new.md").unwrap(),
+            canonical_root.join("b/new.md")
+        );
+        assert_eq!(resolve(&root, "").unwrap(), canonical_root);
+
+        for path in &["../secret", "a/../../secret", "/etc/passwd", "a/../a/file.mp3"] {
+            let err = resolve(&root, path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{}", path);
+        }
+
+        assert_eq!(
+            resolve_existing(&root, "a/missing.mp3").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        // a dangling link would create its target outside of the root
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path().join("missing"), root.join("a.md")).unwrap();
+            std::os::unix::fs::symlink(root.join("b/new.md"), root.join("b.md")).unwrap();
+
+            let err = resolve(&root, "a.md").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+            assert_eq!(
+                resolve(&root, "b.md").unwrap(),
+                canonical_root.join("b/new.md")
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(dir.path().join("secret"), "").unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("link")).unwrap();
+
+        let err = resolve(&root, "link/secret").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        let err = resolve(&root, "link/new.md").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        std::os::unix::fs::symlink(root.join("loop"), root.join("loop")).unwrap();
+
+        let err = resolve(&root, "loop").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
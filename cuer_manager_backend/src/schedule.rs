@@ -0,0 +1,375 @@
+/**
+
+Structured schedules of events.
+
+The schedule of an event is stored as JSON in its `schedule` column. It consists of time
+blocks like round dance tips, square dance tips, breaks, workshops and teaching. Tips of the
+program can be generated from the blocks: blocks of round dance tips are split into tips of
+about the configured length, workshops and teaching blocks become a single tip each.
+
+Blocks and tips have to stay within the time of the event and must not overlap each other.
+
+**/
+use crate::programming;
+use chrono::{DateTime, Duration, FixedOffset};
+use cuer_database::models::{Event, Tip};
+use log::info;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockKind {
+    RoundDanceTips,
+    SquareDanceTips,
+    Break,
+    Workshop,
+    Teaching,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Block {
+    pub kind: BlockKind,
+    pub name: Option<String>,
+    pub date_start: String,
+    pub date_end: String,
+    /// Length of the tips of a block of round dance tips, defaults to the configured minutes
+    pub minutes_per_tip: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Schedule {
+    pub blocks: Vec<Block>,
+}
+
+/// A tip to be created from a block of the schedule.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ScheduledTip {
+    pub name: String,
+    pub date_start: String,
+    pub date_end: String,
+}
+
+/// Reads the schedule of `event`. Free text stored before schedules were structured is ignored.
+pub fn of_event(event: &Event) -> Option<Schedule> {
+    let text = event.schedule.as_ref()?;
+
+    match serde_json::from_str(text) {
+        Ok(schedule) => Some(schedule),
+        Err(err) => {
+            info!(
+                "Schedule of event {} is not structured: {}",
+                event.uuid, err
+            );
+            None
+        }
+    }
+}
+
+/// A span of time with the key and the name of what it belongs to.
+type Span<K> = (K, String, DateTime<FixedOffset>, DateTime<FixedOffset>);
+
+/// What is wrong with a span, other spans are referred to by their key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProblemKind<K> {
+    InvalidTime,
+    EndsBeforeStart,
+    StartsBeforeEvent,
+    EndsAfterEvent,
+    Overlaps(K),
+}
+
+/// A problem of the span with `key`, with a message for the user.
+#[derive(Debug, Clone)]
+pub struct Problem<K> {
+    pub key: K,
+    pub kind: ProblemKind<K>,
+    pub message: String,
+}
+
+impl<K: PartialEq> Problem<K> {
+    /// Checks whether both are the same problem of the same span, even if it was renamed.
+    pub fn same_as(&self, other: &Problem<K>) -> bool {
+        self.key == other.key && self.kind == other.kind
+    }
+}
+
+fn parse_span<K>(key: K, name: &str, start: &str, end: &str) -> Result<Span<K>, Problem<K>> {
+    let problem = |key, kind, message| Err(Problem { key, kind, message });
+
+    match (
+        DateTime::parse_from_rfc3339(start),
+        DateTime::parse_from_rfc3339(end),
+    ) {
+        (Ok(start), Ok(end)) if end > start => Ok((key, name.to_string(), start, end)),
+        (Ok(_), Ok(_)) => problem(
+            key,
+            ProblemKind::EndsBeforeStart,
+            format!("{} ends before it starts", name),
+        ),
+        _ => problem(
+            key,
+            ProblemKind::InvalidTime,
+            format!("{} has an invalid time", name),
+        ),
+    }
+}
+
+/// Returns the problems of the spans: invalid times, spans outside of the event and overlaps.
+fn check_spans<K: Clone>(
+    event: &Event,
+    spans: Vec<Result<Span<K>, Problem<K>>>,
+) -> Vec<Problem<K>> {
+    let bounds = parse_span((), &event.name, &event.date_start, &event.date_end).ok();
+    let mut problems = Vec::new();
+    let mut valid = Vec::new();
+
+    for span in spans {
+        match span {
+            Ok(span) => valid.push(span),
+            Err(problem) => problems.push(problem),
+        }
+    }
+
+    if let Some((_, _, start, end)) = bounds {
+        for (key, name, span_start, span_end) in &valid {
+            if *span_start < start {
+                problems.push(Problem {
+                    key: key.clone(),
+                    kind: ProblemKind::StartsBeforeEvent,
+                    message: format!("{} starts before the event", name),
+                });
+            }
+            if *span_end > end {
+                problems.push(Problem {
+                    key: key.clone(),
+                    kind: ProblemKind::EndsAfterEvent,
+                    message: format!("{} ends after the event", name),
+                });
+            }
+        }
+    }
+
+    valid.sort_by_key(|(_, _, start, _)| *start);
+
+    for (i, (key, name, _, end)) in valid.iter().enumerate() {
+        for (other_key, other, other_start, _) in &valid[i + 1..] {
+            if other_start < end {
+                problems.push(Problem {
+                    key: key.clone(),
+                    kind: ProblemKind::Overlaps(other_key.clone()),
+                    message: format!("{} overlaps {}", name, other),
+                });
+            }
+        }
+    }
+
+    problems
+}
+
+fn block_name(block: &Block) -> String {
+    match (&block.name, block.kind) {
+        (Some(name), _) if !name.trim().is_empty() => name.trim().to_string(),
+        (_, BlockKind::RoundDanceTips) => "Round dance tips".to_string(),
+        (_, BlockKind::SquareDanceTips) => "Square dance tips".to_string(),
+        (_, BlockKind::Break) => "Break".to_string(),
+        (_, BlockKind::Workshop) => "Workshop".to_string(),
+        (_, BlockKind::Teaching) => "Teaching".to_string(),
+    }
+}
+
+/// Returns the problems of a schedule of `event`.
+pub fn validate(schedule: &Schedule, event: &Event) -> Vec<String> {
+    let spans = schedule
+        .blocks
+        .iter()
+        .enumerate()
+        .map(|(i, b)| parse_span(i, &block_name(b), &b.date_start, &b.date_end))
+        .collect();
+
+    let mut problems: Vec<String> = check_spans(event, spans)
+        .into_iter()
+        .map(|p| p.message)
+        .collect();
+
+    for block in &schedule.blocks {
+        if block.minutes_per_tip == Some(0) {
+            problems.push(format!("{} has tips without a length", block_name(block)));
+        }
+    }
+
+    problems
+}
+
+/// Returns the problems of the tips of the program of `event`, keyed by the ids of the tips.
+pub fn check_tips(event: &Event, tips: &[Tip]) -> Vec<Problem<i32>> {
+    let spans = tips
+        .iter()
+        .map(|t| parse_span(t.id, &t.name, &t.date_start, &t.date_end))
+        .collect();
+
+    check_spans(event, spans)
+}
+
+/// Splits the blocks of a valid schedule into tips, numbering round dance tips from
+/// `first_number`.
+pub fn tips(schedule: &Schedule, first_number: usize, minutes_per_tip: u32) -> Vec<ScheduledTip> {
+    let mut result = Vec::new();
+    let mut number = first_number;
+
+    for block in &schedule.blocks {
+        let (start, end) = match parse_span((), "", &block.date_start, &block.date_end) {
+            Ok((_, _, start, end)) => (start, end),
+            Err(_) => continue,
+        };
+
+        match block.kind {
+            BlockKind::RoundDanceTips => {
+                let length =
+                    i64::from(block.minutes_per_tip.unwrap_or(minutes_per_tip).max(1)) * 60;
+                let seconds = (end - start).num_seconds();
+                // the tips fill the whole block with about the requested length
+                let count = std::cmp::max(1, (seconds + length / 2) / length);
+
+                for i in 0..count {
+                    result.push(ScheduledTip {
+                        name: format!("Tip {}", number),
+                        date_start: programming::format_time(
+                            &(start + Duration::seconds(seconds * i / count)),
+                        ),
+                        date_end: programming::format_time(
+                            &(start + Duration::seconds(seconds * (i + 1) / count)),
+                        ),
+                    });
+                    number += 1;
+                }
+            }
+            BlockKind::Workshop | BlockKind::Teaching => result.push(ScheduledTip {
+                name: block_name(block),
+                date_start: programming::format_time(&start),
+                date_end: programming::format_time(&end),
+            }),
+            BlockKind::SquareDanceTips | BlockKind::Break => (),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> Event {
+        Event {
+            id: 1,
+            uuid: "event".to_string(),
+            date_start: "2019-11-01T19:00:00.000Z".to_string(),
+            date_end: "2019-11-01T22:00:00.000Z".to_string(),
+            name: "Club Night".to_string(),
+            schedule: None,
+            date_created: String::new(),
+            date_modified: String::new(),
+            series_id: None,
+            recurrence_id: None,
+        }
+    }
+
+    fn block(kind: BlockKind, start: &str, end: &str) -> Block {
+        Block {
+            kind,
+            name: None,
+            date_start: format!("2019-11-01T{}:00.000Z", start),
+            date_end: format!("2019-11-01T{}:00.000Z", end),
+            minutes_per_tip: None,
+        }
+    }
+
+    fn tip(name: &str, start: &str, end: &str) -> Tip {
+        Tip {
+            id: 0,
+            uuid: name.to_string(),
+            name: name.to_string(),
+            program_id: 1,
+            date_start: format!("2019-11-01T{}:00.000Z", start),
+            date_end: format!("2019-11-01T{}:00.000Z", end),
+            date_modified: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let schedule = Schedule {
+            blocks: vec![
+                block(BlockKind::RoundDanceTips, "19:00", "20:00"),
+                block(BlockKind::Break, "20:00", "20:15"),
+                block(BlockKind::Workshop, "20:10", "21:00"),
+                block(BlockKind::SquareDanceTips, "21:30", "22:30"),
+            ],
+        };
+
+        assert_eq!(
+            validate(&schedule, &event()),
+            vec![
+                "Square dance tips ends after the event",
+                "Break overlaps Workshop"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_tips() {
+        let mut tips = vec![
+            tip("Tip 1", "19:00", "19:15"),
+            tip("Tip 2", "19:10", "19:30"),
+            tip("Tip 3", "18:45", "19:00"),
+            tip("Tip 4", "20:00", "19:50"),
+        ];
+        for (i, tip) in tips.iter_mut().enumerate() {
+            tip.id = i as i32 + 1;
+        }
+
+        let problems = check_tips(&event(), &tips);
+        let messages: Vec<&str> = problems.iter().map(|p| p.message.as_str()).collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                "Tip 4 ends before it starts",
+                "Tip 3 starts before the event",
+                "Tip 1 overlaps Tip 2",
+            ]
+        );
+        assert_eq!(problems[2].key, 1);
+        assert_eq!(problems[2].kind, ProblemKind::Overlaps(2));
+
+        // renaming a tip doesn't change its problems
+        tips[0].name = "Opening".to_string();
+        let renamed = check_tips(&event(), &tips);
+        assert!(renamed[2].same_as(&problems[2]));
+        assert_eq!(renamed[2].message, "Opening overlaps Tip 2");
+    }
+
+    #[test]
+    fn test_tips() {
+        let mut workshop = block(BlockKind::Workshop, "20:15", "21:00");
+        workshop.name = Some("Workshop: Rumba".to_string());
+
+        let schedule = Schedule {
+            blocks: vec![
+                block(BlockKind::RoundDanceTips, "19:00", "20:05"),
+                block(BlockKind::Break, "20:05", "20:15"),
+                workshop,
+            ],
+        };
+
+        let tips = tips(&schedule, 3, 15);
+        let names: Vec<&str> = tips.iter().map(|t| t.name.as_str()).collect();
+
+        assert_eq!(
+            names,
+            vec!["Tip 3", "Tip 4", "Tip 5", "Tip 6", "Workshop: Rumba"]
+        );
+        assert_eq!(tips[1].date_start, "2019-11-01T19:16:15.000Z");
+        assert_eq!(tips[3].date_end, "2019-11-01T20:05:00.000Z");
+        assert_eq!(tips[4].date_start, "2019-11-01T20:15:00.000Z");
+    }
+}
@@ -0,0 +1,251 @@
+/**
+
+Statistics about the cueing history.
+
+Every cue in the cue log is counted, which allows finding the dances cued most often and the
+ones which have not been cued for a long time.
+
+**/
+use cuer_database::models::Cuecard;
+use diesel::prelude::*;
+
+use std::collections::{HashMap, HashSet};
+
+type DBConnection = SqliteConnection;
+
+/// A single cue of a cue card.
+pub struct Cue {
+    pub cuecard_id: i32,
+    pub cued_at: String,
+    pub event_uuid: Option<String>,
+    pub event_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CuecardStats {
+    pub uuid: String,
+    pub title: String,
+    pub rhythm: String,
+    pub phase: String,
+    pub times_cued: usize,
+    pub last_cued: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GroupStats {
+    pub key: String,
+    pub label: String,
+    pub times_cued: usize,
+    /// Number of different cue cards cued
+    pub cuecards: usize,
+    pub last_cued: Option<String>,
+}
+
+/// Loads all cues of the cue log, optionally only those of one event.
+pub fn cues(event: Option<&str>, conn: &DBConnection) -> QueryResult<Vec<Cue>> {
+    use cuer_database::schema::*;
+
+    let mut query = cues::table
+        .left_join(events::table)
+        .select((
+            cues::columns::cuecard_id,
+            cues::columns::started_at,
+            events::columns::uuid.nullable(),
+            events::columns::name.nullable(),
+        ))
+        .into_boxed();
+
+    if let Some(event) = event {
+        query = query.filter(events::columns::uuid.eq(event.to_string()));
+    }
+
+    let rows = query.load::<(i32, String, Option<String>, Option<String>)>(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(cuecard_id, cued_at, event_uuid, event_name)| Cue {
+            cuecard_id,
+            cued_at,
+            event_uuid,
+            event_name,
+        })
+        .collect())
+}
+
+/// Counts the cues of every cue card, most often cued cards first.
+pub fn cuecard_stats(cuecards: &[Cuecard], cues: &[Cue]) -> Vec<CuecardStats> {
+    let mut counts: HashMap<i32, (usize, &str)> = HashMap::new();
+
+    for cue in cues {
+        let entry = counts.entry(cue.cuecard_id).or_insert((0, ""));
+        entry.0 += 1;
+        if cue.cued_at.as_str() > entry.1 {
+            entry.1 = &cue.cued_at;
+        }
+    }
+
+    let mut result: Vec<CuecardStats> = cuecards
+        .iter()
+        .map(|cuecard| {
+            let (times_cued, last_cued) = match counts.get(&cuecard.id) {
+                Some((count, last)) => (*count, Some(last.to_string())),
+                None => (0, None),
+            };
+
+            CuecardStats {
+                uuid: cuecard.uuid.clone(),
+                title: cuecard.title.clone(),
+                rhythm: cuecard.rhythm.clone(),
+                phase: cuecard.phase.clone(),
+                times_cued,
+                last_cued,
+            }
+        })
+        .collect();
+
+    result.sort_by(|a, b| {
+        b.times_cued
+            .cmp(&a.times_cued)
+            .then_with(|| a.title.cmp(&b.title))
+    });
+
+    result
+}
+
+/// Counts the cues grouped by a key and label of a cue, cues without a group are skipped.
+pub fn group_stats<F>(cuecards: &[Cuecard], cues: &[Cue], group: F) -> Vec<GroupStats>
+where
+    F: Fn(&Cuecard, &Cue) -> Option<(String, String)>,
+{
+    let cuecards: HashMap<i32, &Cuecard> = cuecards.iter().map(|c| (c.id, c)).collect();
+    let mut groups: HashMap<String, (GroupStats, HashSet<i32>)> = HashMap::new();
+
+    for cue in cues {
+        let cuecard = match cuecards.get(&cue.cuecard_id) {
+            Some(cuecard) => cuecard,
+            None => continue,
+        };
+
+        let (key, label) = match group(cuecard, cue) {
+            Some(group) => group,
+            None => continue,
+        };
+        let (stats, cued) = groups.entry(key.clone()).or_insert_with(|| {
+            (
+                GroupStats {
+                    key,
+                    label,
+                    times_cued: 0,
+                    cuecards: 0,
+                    last_cued: None,
+                },
+                HashSet::new(),
+            )
+        });
+
+        stats.times_cued += 1;
+        cued.insert(cue.cuecard_id);
+        stats.cuecards = cued.len();
+        if stats.last_cued.as_ref().is_none_or(|last| cue.cued_at > *last) {
+            stats.last_cued = Some(cue.cued_at.clone());
+        }
+    }
+
+    let mut result: Vec<GroupStats> = groups.into_iter().map(|(_, (stats, _))| stats).collect();
+    result.sort_by(|a, b| b.times_cued.cmp(&a.times_cued).then_with(|| a.key.cmp(&b.key)));
+
+    result
+}
+
+/// Returns the cue cards cued before, but not since `cutoff`, longest ago first.
+pub fn rotation(stats: Vec<CuecardStats>, cutoff: &str) -> Vec<CuecardStats> {
+    let mut result: Vec<CuecardStats> = stats
+        .into_iter()
+        .filter(|s| s.last_cued.as_ref().is_some_and(|last| last.as_str() < cutoff))
+        .collect();
+
+    result.sort_by(|a, b| a.last_cued.cmp(&b.last_cued));
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cuecard(id: i32, title: &str, rhythm: &str) -> Cuecard {
+        Cuecard {
+            id,
+            uuid: title.to_lowercase(),
+            phase: "IV".to_string(),
+            rhythm: rhythm.to_string(),
+            title: title.to_string(),
+            steplevel: String::new(),
+            difficulty: String::new(),
+            choreographer: String::new(),
+            meta: String::new(),
+            content: String::new(),
+            karaoke_marks: String::new(),
+            music_file: String::new(),
+            file_path: String::new(),
+            date_created: String::new(),
+            date_modified: String::new(),
+        }
+    }
+
+    fn cue(cuecard_id: i32, cued_at: &str, event: &str) -> Cue {
+        Cue {
+            cuecard_id,
+            cued_at: cued_at.to_string(),
+            event_uuid: Some(event.to_string()),
+            event_name: Some(event.to_uppercase()),
+        }
+    }
+
+    #[test]
+    fn test_stats() {
+        let cuecards = vec![
+            cuecard(1, "Alpha", "Waltz"),
+            cuecard(2, "Beta", "Waltz"),
+            cuecard(3, "Gamma", "Rumba"),
+        ];
+        let cues = vec![
+            cue(1, "2019-10-01T20:00:00.000Z", "a"),
+            cue(1, "2019-11-01T20:00:00.000Z", "b"),
+            cue(2, "2019-06-01T20:00:00.000Z", "a"),
+            Cue {
+                cuecard_id: 2,
+                cued_at: "2019-05-01T20:00:00.000Z".to_string(),
+                event_uuid: None,
+                event_name: None,
+            },
+        ];
+
+        let stats = cuecard_stats(&cuecards, &cues);
+        assert_eq!(stats[0].title, "Alpha");
+        assert_eq!(stats[0].times_cued, 2);
+        assert_eq!(stats[1].times_cued, 2);
+        assert_eq!(stats[0].last_cued.as_deref(), Some("2019-11-01T20:00:00.000Z"));
+        assert_eq!(stats[2].title, "Gamma");
+        assert_eq!(stats[2].last_cued, None);
+
+        let rhythms = group_stats(&cuecards, &cues, |c, _| {
+            Some((c.rhythm.clone(), c.rhythm.clone()))
+        });
+        assert_eq!(rhythms.len(), 1);
+        assert_eq!(rhythms[0].times_cued, 4);
+        assert_eq!(rhythms[0].cuecards, 2);
+
+        let events = group_stats(&cuecards, &cues, |_, e| {
+            e.event_uuid.clone().zip(e.event_name.clone())
+        });
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].key, "a");
+        assert_eq!(events[0].times_cued, 2);
+        assert_eq!(events[0].label, "A");
+
+        let candidates = rotation(stats, "2019-09-01");
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].title, "Beta");
+    }
+}
@@ -0,0 +1,299 @@
+/**
+
+Reuse of programs.
+
+The tips of a program can be copied into the program of another event, or saved as a named
+template without dates. Times of tips are kept relative to the start of the event, so copied
+tips are shifted to the start of the target event. The cue cards of every tip keep their
+order.
+
+**/
+use crate::programming;
+use crate::programming::EventProgram;
+use chrono::{DateTime, Duration, FixedOffset, Local, Utc};
+use cuer_database::models::{
+    Program, ProgramData, ProgramTemplate, ProgramTemplateData, Tip, TipCuecardData, TipData,
+};
+use diesel::prelude::*;
+use uuidcrate::Uuid;
+
+type DBConnection = SqliteConnection;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TemplateTip {
+    pub name: String,
+    /// Start of the tip in seconds after the start of the event
+    pub offset_seconds: i64,
+    pub duration_seconds: i64,
+    /// Uuids of the cue cards in their order
+    pub cuecards: Vec<String>,
+}
+
+/// Result of copying tips into a program.
+#[derive(Serialize, Deserialize, Default)]
+pub struct CopiedTips {
+    pub tips: Vec<Tip>,
+    /// Cue cards which are not in the library anymore
+    pub missing_cuecards: Vec<String>,
+}
+
+/// Returns the tips of a program relative to the start of its event.
+///
+/// Tips without valid times follow the tip before them and last `minutes_per_tip`.
+pub fn template_tips(program: &EventProgram, minutes_per_tip: u32) -> Vec<TemplateTip> {
+    let event_start = DateTime::parse_from_rfc3339(&program.event.date_start).ok();
+    let mut previous_end = 0;
+
+    program
+        .tips
+        .iter()
+        .map(|(tip, cuecards)| {
+            let start = DateTime::parse_from_rfc3339(&tip.date_start);
+            let end = DateTime::parse_from_rfc3339(&tip.date_end);
+
+            let (offset_seconds, duration_seconds) = match (event_start, start, end) {
+                (Some(event_start), Ok(start), Ok(end)) => (
+                    (start - event_start).num_seconds(),
+                    (end - start).num_seconds(),
+                ),
+                _ => (previous_end, i64::from(minutes_per_tip) * 60),
+            };
+            previous_end = offset_seconds + duration_seconds;
+
+            TemplateTip {
+                name: tip.name.clone(),
+                offset_seconds,
+                duration_seconds,
+                cuecards: cuecards.iter().map(|c| c.uuid.clone()).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Adds `notes` to the notes of `program`.
+fn append_notes(program: &Program, notes: Option<&str>, conn: &DBConnection) -> QueryResult<()> {
+    let notes = match notes.map(str::trim).filter(|n| !n.is_empty()) {
+        Some(notes) => notes,
+        None => return Ok(()),
+    };
+
+    let merged = match program
+        .notes
+        .as_ref()
+        .map(|n| n.trim())
+        .filter(|n| !n.is_empty())
+    {
+        Some(existing) => format!("{}\n\n{}", existing, notes),
+        None => notes.to_string(),
+    };
+    let now = Utc::now().format("%FT%T%.3fZ").to_string();
+
+    ProgramData {
+        uuid: &program.uuid,
+        notes: Some(&merged),
+        event_id: program.event_id,
+        date_created: &program.date_created,
+        date_modified: &now,
+    }
+    .update(conn)
+    .map(|_| ())
+}
+
+/// Creates `tips` in `program`, shifted to `start`, and adds `notes` to the program notes.
+pub fn apply(
+    tips: &[TemplateTip],
+    notes: Option<&str>,
+    program: &Program,
+    start: DateTime<FixedOffset>,
+    conn: &DBConnection,
+) -> QueryResult<CopiedTips> {
+    let mut result = CopiedTips::default();
+
+    append_notes(program, notes, conn)?;
+
+    for template_tip in tips {
+        let tip_start = start + Duration::seconds(template_tip.offset_seconds);
+        let tip_end = tip_start + Duration::seconds(template_tip.duration_seconds);
+        let u = Uuid::new_v4().to_hyphenated().to_string();
+
+        let tip = programming::create_tip(
+            &TipData {
+                name: &template_tip.name,
+                uuid: &u,
+                program_id: &program.id,
+                date_start: &programming::format_time(&tip_start),
+                date_end: &programming::format_time(&tip_end),
+            },
+            conn,
+        )?;
+
+        let mut sort_order = 0;
+
+        for cuecard_uuid in &template_tip.cuecards {
+            let cuecard = match cuer_database::cuecard_by_uuid(cuecard_uuid, conn).optional()? {
+                Some(cuecard) => cuecard,
+                None => {
+                    result.missing_cuecards.push(cuecard_uuid.clone());
+                    continue;
+                }
+            };
+
+            sort_order += 1;
+
+            programming::create_tip_cuecard(
+                &TipCuecardData {
+                    tip_id: &tip.id,
+                    cuecard_id: &cuecard.id,
+                    sort_order: &sort_order,
+                    cued_at: None,
+                },
+                conn,
+            )?;
+        }
+
+        result.tips.push(tip);
+    }
+
+    Ok(result)
+}
+
+pub fn create_template(
+    name: &str,
+    program: &EventProgram,
+    minutes_per_tip: u32,
+    conn: &DBConnection,
+) -> QueryResult<ProgramTemplate> {
+    let tips = template_tips(program, minutes_per_tip);
+    let tips = match serde_json::to_string(&tips) {
+        Ok(tips) => tips,
+        Err(err) => return Err(diesel::result::Error::SerializationError(Box::new(err))),
+    };
+
+    let u = Uuid::new_v4().to_hyphenated().to_string();
+    let now = Local::now().format("%FT%T%.3fZ").to_string();
+
+    ProgramTemplateData {
+        uuid: &u,
+        name,
+        notes: program.program.notes.as_deref(),
+        tips: &tips,
+        date_created: &now,
+        date_modified: &now,
+    }
+    .create(conn)
+}
+
+pub fn get_templates(conn: &DBConnection) -> QueryResult<Vec<ProgramTemplate>> {
+    use cuer_database::schema::program_templates::dsl::*;
+
+    program_templates
+        .order(name.asc())
+        .load::<ProgramTemplate>(conn)
+}
+
+pub fn template_by_uuid(template_uuid: &str, conn: &DBConnection) -> QueryResult<ProgramTemplate> {
+    use cuer_database::schema::program_templates::dsl::*;
+
+    program_templates
+        .filter(uuid.eq(template_uuid))
+        .first::<ProgramTemplate>(conn)
+}
+
+/// Reads the tips stored in a template.
+pub fn tips_of(template: &ProgramTemplate) -> QueryResult<Vec<TemplateTip>> {
+    serde_json::from_str(&template.tips)
+        .map_err(|err| diesel::result::Error::DeserializationError(Box::new(err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cuer_database::models::{Cuecard, Event};
+
+    fn cuecard(uuid: &str) -> Cuecard {
+        Cuecard {
+            id: 0,
+            uuid: uuid.to_string(),
+            phase: String::new(),
+            rhythm: String::new(),
+            title: String::new(),
+            steplevel: String::new(),
+            difficulty: String::new(),
+            choreographer: String::new(),
+            meta: String::new(),
+            content: String::new(),
+            karaoke_marks: String::new(),
+            music_file: String::new(),
+            file_path: String::new(),
+            date_created: String::new(),
+            date_modified: String::new(),
+        }
+    }
+
+    fn tip(name: &str, date_start: &str, date_end: &str) -> Tip {
+        Tip {
+            id: 0,
+            uuid: name.to_string(),
+            name: name.to_string(),
+            program_id: 1,
+            date_start: date_start.to_string(),
+            date_end: date_end.to_string(),
+            date_modified: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_template_tips() {
+        let program = EventProgram {
+            event: Event {
+                id: 1,
+                uuid: "event".to_string(),
+                date_start: "2019-11-01T19:00:00.000Z".to_string(),
+                date_end: "2019-11-01T22:00:00.000Z".to_string(),
+                name: "Club Night".to_string(),
+                schedule: None,
+                date_created: String::new(),
+                date_modified: String::new(),
+                series_id: None,
+                recurrence_id: None,
+            },
+            program: Program {
+                id: 1,
+                uuid: "program".to_string(),
+                notes: None,
+                event_id: 1,
+                date_created: String::new(),
+                date_modified: String::new(),
+            },
+            tips: vec![
+                (
+                    tip(
+                        "Tip 1",
+                        "2019-11-01T19:30:00.000Z",
+                        "2019-11-01T19:45:00.000Z",
+                    ),
+                    vec![cuecard("b"), cuecard("a")],
+                ),
+                (tip("Tip 2", "", ""), Vec::new()),
+            ],
+        };
+
+        assert_eq!(
+            template_tips(&program, 20),
+            vec![
+                TemplateTip {
+                    name: "Tip 1".to_string(),
+                    offset_seconds: 1800,
+                    duration_seconds: 900,
+                    cuecards: vec!["b".to_string(), "a".to_string()],
+                },
+                TemplateTip {
+                    name: "Tip 2".to_string(),
+                    offset_seconds: 2700,
+                    duration_seconds: 1200,
+                    cuecards: Vec::new(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,356 @@
+/**
+
+Rendering of practice versions of music files with a changed tempo.
+
+The tempo is changed with WSOLA (waveform similarity overlap-add), which keeps the pitch of
+the music. Rendered files are cached as WAV files in the derived files directory, named by a
+SHA-256 hash of the source path, its modification time and the speed, so a changed source file
+gets a new rendition. Rendering takes a while, so it runs in the background and requests for a
+rendition in progress are answered as pending.
+
+The whole track is decoded into memory and stretched in one piece, which takes about 95 MB per
+minute of stereo music at 44.1 kHz rendered at half speed. Tracks longer than `MAX_DURATION`
+are therefore not rendered.
+
+**/
+use crate::audio;
+use log::{error, info};
+use sha2::{Digest, Sha256};
+
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+pub const MIN_SPEED: u32 = 50;
+pub const MAX_SPEED: u32 = 200;
+
+/// Length of an analysis frame in seconds
+const FRAME_DURATION: f32 = 0.046;
+
+/// Maximum playing time of a rendered music file in seconds
+const MAX_DURATION: f64 = 10.0 * 60.0;
+
+/// Time until a music file which could not be decoded is tried again
+const FAILURE_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+/// Maximum number of remembered failures, the oldest is dropped first
+const MAX_FAILURES: usize = 100;
+
+/// Renditions currently rendered in the background
+static RENDERING: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Renditions which could not be rendered as their music files can't be decoded, with the time
+/// of the failure
+static FAILED: Mutex<BTreeMap<PathBuf, Instant>> = Mutex::new(BTreeMap::new());
+
+pub fn valid_speed(speed: u32) -> bool {
+    (MIN_SPEED..=MAX_SPEED).contains(&speed)
+}
+
+/// Returns the cache location of the rendition of `source` at `speed` percent.
+pub fn rendition_path(derived_files_dir: &str, source: &Path, speed: u32) -> io::Result<PathBuf> {
+    let modified = source
+        .metadata()?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+    let mut hasher = Sha256::new();
+    hasher.input(source.to_string_lossy().as_bytes());
+    hasher.input(modified.as_nanos().to_le_bytes());
+    hasher.input(speed.to_le_bytes());
+
+    let hash = hasher
+        .result()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    let mut path = PathBuf::from(derived_files_dir);
+    path.push("tempo");
+    path.push(format!("{}.wav", hash));
+    Ok(path)
+}
+
+pub enum Rendition {
+    Ready(PathBuf),
+    /// The rendition is being rendered in the background
+    Pending,
+}
+
+/// Returns the path of a rendition of `source` at `speed` percent of the original tempo. If
+/// there is no rendition in the cache yet, rendering it is started in the background.
+pub fn rendition(derived_files_dir: &str, source: &Path, speed: u32) -> io::Result<Rendition> {
+    if !valid_speed(speed) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("speed must be between {} and {}", MIN_SPEED, MAX_SPEED),
+        ));
+    }
+
+    let target = rendition_path(derived_files_dir, source, speed)?;
+
+    if target.exists() {
+        return Ok(Rendition::Ready(target));
+    }
+
+    if let Some(failed) = FAILED.lock().unwrap().get(&target) {
+        if failed.elapsed() < FAILURE_EXPIRY {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the music file could not be rendered",
+            ));
+        }
+    }
+
+    let mut rendering = RENDERING.lock().unwrap();
+
+    // the rendition may have been finished meanwhile
+    if target.exists() {
+        return Ok(Rendition::Ready(target));
+    }
+
+    if !rendering.contains(&target) {
+        rendering.push(target.clone());
+
+        let source = source.to_path_buf();
+
+        thread::spawn(move || {
+            if let Err(err) = render(&source, &target, speed) {
+                error!(
+                    "Error rendering {:?} at {}% speed: {:?}",
+                    source, speed, err
+                );
+
+                // other errors, like a full disk, may be gone with the next request
+                if err.kind() == io::ErrorKind::InvalidData {
+                    record_failure(&target);
+                }
+            }
+
+            RENDERING.lock().unwrap().retain(|path| *path != target);
+        });
+    }
+
+    Ok(Rendition::Pending)
+}
+
+fn record_failure(target: &Path) {
+    let mut failed = FAILED.lock().unwrap();
+    failed.retain(|_, time| time.elapsed() < FAILURE_EXPIRY);
+
+    if failed.len() >= MAX_FAILURES {
+        let oldest = failed
+            .iter()
+            .min_by_key(|(_, time)| **time)
+            .map(|(path, _)| path.clone());
+
+        if let Some(oldest) = oldest {
+            failed.remove(&oldest);
+        }
+    }
+
+    failed.insert(target.to_path_buf(), Instant::now());
+}
+
+fn render(source: &Path, target: &Path, speed: u32) -> io::Result<()> {
+    info!("Rendering {:?} at {}% speed", source, speed);
+
+    if audio::duration(source)? > MAX_DURATION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "music files longer than {} seconds are not rendered",
+                MAX_DURATION
+            ),
+        ));
+    }
+
+    let decoded = audio::decode(source)?;
+    let samples = stretch(
+        &decoded.samples,
+        decoded.channels,
+        decoded.sample_rate,
+        speed as f32 / 100.0,
+    );
+
+    let parent = target.parent().unwrap();
+    std::fs::DirBuilder::new().recursive(true).create(parent)?;
+
+    // render into a temporary file first so requests never see a partial file
+    let temp = tempfile::NamedTempFile::new_in(parent)?;
+    write_wav(temp.path(), &samples, decoded.channels, decoded.sample_rate)?;
+    temp.persist(target).map_err(|err| err.error)?;
+
+    Ok(())
+}
+
+fn wav_error(err: hound::Error) -> io::Error {
+    match err {
+        hound::Error::IoError(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+fn write_wav(path: &Path, samples: &[f32], channels: usize, sample_rate: u32) -> io::Result<()> {
+    let spec = hound::WavSpec {
+        channels: channels as u16,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec).map_err(wav_error)?;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+        writer.write_sample(value).map_err(wav_error)?;
+    }
+    writer.finalize().map_err(wav_error)
+}
+
+/// Changes the tempo of interleaved `samples` by `factor` without changing the pitch.
+///
+/// A factor below 1.0 slows the music down, a factor above 1.0 speeds it up.
+pub fn stretch(samples: &[f32], channels: usize, sample_rate: u32, factor: f32) -> Vec<f32> {
+    let frames = samples.len() / channels;
+    let frame_size = (FRAME_DURATION * sample_rate as f32) as usize & !1;
+    let synthesis_hop = frame_size / 2;
+    let analysis_hop = synthesis_hop as f32 * factor;
+    let tolerance = frame_size / 8;
+
+    if (factor - 1.0).abs() < f32::EPSILON || frames < frame_size + 2 * tolerance {
+        return samples.to_vec();
+    }
+
+    // the similarity search runs on a mono mix of the channels
+    let mono: Vec<f32> = samples
+        .chunks(channels)
+        .map(|c| c.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    let window: Vec<f32> = (0..frame_size)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / frame_size as f32).cos())
+        .collect();
+
+    let output_frames = (frames as f32 / factor) as usize + frame_size;
+    let mut output = vec![0.0f32; output_frames * channels];
+    let mut weights = vec![0.0f32; output_frames];
+
+    let last_start = frames - frame_size;
+    let mut previous: Option<usize> = None;
+    let mut k = 0;
+
+    loop {
+        let nominal = (k as f32 * analysis_hop) as usize;
+        if nominal > last_start {
+            break;
+        }
+
+        let start = match previous {
+            Some(prev) => {
+                // the natural continuation of the previous frame is the template
+                let template = std::cmp::min(prev + synthesis_hop, last_start);
+                let from = nominal.saturating_sub(tolerance);
+                let to = std::cmp::min(nominal + tolerance, last_start);
+                best_match(&mono, template, from, to, synthesis_hop)
+            }
+            None => nominal,
+        };
+
+        let offset = k * synthesis_hop;
+        for i in 0..frame_size {
+            let w = window[i];
+            for c in 0..channels {
+                output[(offset + i) * channels + c] += samples[(start + i) * channels + c] * w;
+            }
+            weights[offset + i] += w;
+        }
+
+        previous = Some(start);
+        k += 1;
+    }
+
+    let used_frames = (k.saturating_sub(1)) * synthesis_hop + frame_size;
+    output.truncate(used_frames * channels);
+
+    for (i, weight) in weights.iter().take(used_frames).enumerate() {
+        if *weight > 1e-3 {
+            for c in 0..channels {
+                output[i * channels + c] /= weight;
+            }
+        }
+    }
+
+    output
+}
+
+/// Finds the start position in `from..=to` whose segment is most similar to the segment at
+/// `template`, using a coarse search followed by a fine search around the coarse result.
+fn best_match(mono: &[f32], template: usize, from: usize, to: usize, length: usize) -> usize {
+    let similarity = |candidate: usize, step: usize| -> f32 {
+        (0..length)
+            .step_by(step)
+            .map(|i| mono[template + i] * mono[candidate + i])
+            .sum()
+    };
+
+    let mut best = from;
+    let mut best_value = f32::MIN;
+
+    for candidate in (from..=to).step_by(4) {
+        let value = similarity(candidate, 4);
+        if value > best_value {
+            best = candidate;
+            best_value = value;
+        }
+    }
+
+    let fine_from = std::cmp::max(from, best.saturating_sub(3));
+    let fine_to = std::cmp::min(to, best + 3);
+    best_value = f32::MIN;
+
+    for candidate in fine_from..=fine_to {
+        let value = similarity(candidate, 1);
+        if value > best_value {
+            best = candidate;
+            best_value = value;
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zero_crossings(samples: &[f32]) -> usize {
+        samples
+            .windows(2)
+            .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+            .count()
+    }
+
+    #[test]
+    fn test_stretch_keeps_pitch() {
+        let sample_rate = 8000;
+        let samples: Vec<f32> = (0..sample_rate * 2)
+            .map(|i| (2.0 * PI * 440.0 * i as f32 / sample_rate as f32).sin())
+            .collect();
+
+        for factor in [0.8f32, 1.25].iter() {
+            let result = stretch(&samples, 1, sample_rate as u32, *factor);
+
+            let expected_len = samples.len() as f32 / factor;
+            assert!((result.len() as f32 - expected_len).abs() < expected_len * 0.05);
+
+            let seconds = result.len() as f32 / sample_rate as f32;
+            let frequency = zero_crossings(&result) as f32 / seconds;
+            assert!((frequency - 440.0).abs() < 10.0, "frequency {}", frequency);
+        }
+    }
+}
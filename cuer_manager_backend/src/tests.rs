@@ -3,9 +3,16 @@ use serde_json::Value;
 
 use rocket::http::Status;
 
-use super::rocket;
+use super::{build, embedded_migrations, rocket, DbConn};
+use rocket::config::{Config, Environment, Value as ConfigValue};
+use rocket::http::{ContentType, Header};
 use rocket::local;
 
+use cuer_database::models::CuecardData;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
 #[test]
 fn test_cuesheet_by_id() {
     let documents = test_query_cuesheets("all", Status::Ok);
@@ -68,3 +75,1135 @@ fn test_query_cuesheets(query: &str, status: Status) -> Value {
 
     return data;
 }
+
+/// Creates a client with a fresh database, music files and cue card directories in `dir`.
+fn sandboxed_client(dir: &Path) -> local::Client {
+    let db_path = dir.join("library.db");
+    fs::copy(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../library.empty.db"),
+        &db_path,
+    )
+    .unwrap();
+
+    let db_url = db_path.to_str().unwrap();
+    let conn = cuer_database::establish_connection(db_url);
+    embedded_migrations::run(&conn).unwrap();
+
+    let music_files_dir = dir.join("music");
+    let cuecards_lib_dir = dir.join("cuecards");
+    fs::create_dir_all(&music_files_dir).unwrap();
+    fs::create_dir_all(cuecards_lib_dir.join("w")).unwrap();
+    fs::write(music_files_dir.join("waltz.mp3"), "music").unwrap();
+    fs::write(dir.join("secret.mp3"), "secret").unwrap();
+
+    let mut database_config = HashMap::new();
+    let mut databases = HashMap::new();
+    database_config.insert("url", ConfigValue::from(db_url));
+    databases.insert("sqlite_db", ConfigValue::from(database_config));
+
+    let config = Config::build(Environment::Development)
+        .extra("databases", databases)
+        .extra("music_files_dir", music_files_dir.to_str().unwrap())
+        .extra("cuecards_lib_dir", cuecards_lib_dir.to_str().unwrap())
+        .extra("derived_files_dir", dir.join("derived").to_str().unwrap())
+        .finalize()
+        .unwrap();
+
+    local::Client::new(build(rocket::custom(config))).expect("Rocket setup failed")
+}
+
+fn insert_cuecard(dir: &Path, uuid: &str, music_file: &str, file_path: &str) {
+    let conn = cuer_database::establish_connection(dir.join("library.db").to_str().unwrap());
+
+    CuecardData {
+        uuid,
+        phase: "IV",
+        rhythm: "Waltz",
+        title: "Test",
+        steplevel: "",
+        difficulty: "",
+        choreographer: "Test",
+        meta: "{}",
+        content: "# Test",
+        karaoke_marks: "",
+        music_file,
+        file_path,
+        date_created: "2019-11-01T10:00:00.000Z",
+        date_modified: "2019-11-01T10:00:00.000Z",
+    }
+    .create(&conn)
+    .unwrap();
+}
+
+fn post_filename(client: &local::Client, url: &str, filename: &str) -> Status {
+    client
+        .post(url)
+        .header(ContentType::JSON)
+        .body(format!(r#"{{"filename": "{}"}}"#, base64::encode(filename)))
+        .dispatch()
+        .status()
+}
+
+#[test]
+fn test_audio_file_path_traversal() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+    let secret = dir.path().join("secret.mp3");
+
+    assert_eq!(post_filename(&client, "/v2/audio", "waltz.mp3"), Status::Ok);
+    assert_eq!(post_filename(&client, "/v2/audio", "missing.mp3"), Status::NotFound);
+    assert_eq!(post_filename(&client, "/v2/audio", "../secret.mp3"), Status::Forbidden);
+    assert_eq!(
+        post_filename(&client, "/v2/audio", "./../music/../secret.mp3"),
+        Status::Forbidden
+    );
+    assert_eq!(
+        post_filename(&client, "/v2/audio", secret.to_str().unwrap()),
+        Status::Forbidden
+    );
+
+    let response = client
+        .post("/v2/audio")
+        .header(ContentType::JSON)
+        .body(r#"{"filename": "not base64!"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_audio_file_symlink_escape() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    std::os::unix::fs::symlink(
+        dir.path().join("secret.mp3"),
+        dir.path().join("music/link.mp3"),
+    )
+    .unwrap();
+
+    assert_eq!(post_filename(&client, "/v2/audio", "link.mp3"), Status::Forbidden);
+}
+
+#[test]
+fn test_music_files_path_traversal() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    assert_eq!(post_filename(&client, "/v2/music_files", ""), Status::Ok);
+    assert_eq!(post_filename(&client, "/v2/music_files", ".."), Status::Forbidden);
+    assert_eq!(post_filename(&client, "/v2/music_files", "/etc"), Status::Forbidden);
+}
+
+#[test]
+fn test_stream_audio_file_path_traversal() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "valid", "waltz.mp3", "w/valid.md");
+    insert_cuecard(dir.path(), "relative", "../secret.mp3", "w/relative.md");
+    insert_cuecard(
+        dir.path(),
+        "absolute",
+        dir.path().join("secret.mp3").to_str().unwrap(),
+        "w/absolute.md",
+    );
+
+    let response = client.get("/v2/audio/valid").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client.get("/v2/audio/relative").dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let response = client.get("/v2/audio/absolute").dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+}
+
+#[test]
+fn test_cuecard_content_path_traversal() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "valid", "", "w/valid.md");
+    insert_cuecard(dir.path(), "escape", "", "../escape.md");
+
+    let response = client
+        .post("/v2/cuecards/valid/content")
+        .header(ContentType::JSON)
+        .header(Header::new("If-Match", "\"2019-11-01T10:00:00.000Z\""))
+        .body(r##"{"content": "# Changed"}"##)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("cuecards/w/valid.md")).unwrap(),
+        "# Changed"
+    );
+
+    let response = client
+        .post("/v2/cuecards/escape/content")
+        .header(ContentType::JSON)
+        .header(Header::new("If-Match", "\"2019-11-01T10:00:00.000Z\""))
+        .body(r##"{"content": "# Changed"}"##)
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    assert!(!dir.path().join("escape.md").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cuecard_content_dangling_symlink() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "link", "", "w/link.md");
+    std::os::unix::fs::symlink(
+        dir.path().join("escape.md"),
+        dir.path().join("cuecards/w/link.md"),
+    )
+    .unwrap();
+
+    let response = client
+        .post("/v2/cuecards/link/content")
+        .header(ContentType::JSON)
+        .header(Header::new("If-Match", "\"2019-11-01T10:00:00.000Z\""))
+        .body(r##"{"content": "# Changed"}"##)
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    assert!(!dir.path().join("escape.md").exists());
+}
+
+#[test]
+fn test_cuecard_content_save() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "card", "", "w/card.md");
+    fs::write(dir.path().join("cuecards/w/card.md"), "# Test").unwrap();
+
+    let response = client
+        .post("/v2/cuecards/card/content")
+        .header(ContentType::JSON)
+        .header(Header::new("If-Match", "\"2019-11-01T10:00:00.000Z\""))
+        .body(r##"{"content": "# Moon River\n* **Phase**: V+1\n* **Rhythm**: Foxtrot"}"##)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let mut response = client.get("/v2/cuecards/card").dispatch();
+    let cuecard: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(cuecard["title"], "Moon River");
+    assert_eq!(cuecard["phase"], "V");
+    assert_eq!(cuecard["rhythm"], "Foxtrot");
+    assert_ne!(cuecard["date_modified"], "2019-11-01T10:00:00.000Z");
+
+    // the next indexer run takes the file for indexed
+    let file = fs::metadata(dir.path().join("cuecards/w/card.md")).unwrap();
+    let index_file = cuecard_indexer::index_file_path(&dir.path().join("cuecards/w/card.md"));
+    let index_file = fs::metadata(index_file.unwrap()).unwrap();
+    assert!(index_file.modified().unwrap() >= file.modified().unwrap());
+}
+
+#[test]
+fn test_cuecard_content_write_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "card", "", "w/card.md");
+    // a directory in place of the file can't be replaced
+    fs::create_dir_all(dir.path().join("cuecards/w/card.md")).unwrap();
+
+    let response = client
+        .post("/v2/cuecards/card/content")
+        .header(ContentType::JSON)
+        .header(Header::new("If-Match", "\"2019-11-01T10:00:00.000Z\""))
+        .body(r##"{"content": "# Changed"}"##)
+        .dispatch();
+    assert_eq!(response.status(), Status::InternalServerError);
+
+    let mut response = client.get("/v2/cuecards/card").dispatch();
+    let cuecard: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(cuecard["content"], "# Test");
+    assert_eq!(cuecard["date_modified"], "2019-11-01T10:00:00.000Z");
+
+    let mut response = client.get("/v2/cuecards/card/revisions").dispatch();
+    let revisions: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert!(revisions.as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_cuecard_revisions() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "card", "", "w/card.md");
+    fs::write(dir.path().join("cuecards/w/card.md"), "# Test").unwrap();
+
+    let response = client
+        .post("/v2/cuecards/card/content")
+        .header(ContentType::JSON)
+        .header(Header::new("If-Match", "\"2019-11-01T10:00:00.000Z\""))
+        .body(r##"{"content": "# Test\nWrong"}"##)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let mut response = client.get("/v2/cuecards/card/revisions").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let revisions: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(revisions.as_array().unwrap().len(), 1);
+    assert_eq!(revisions[0]["source"], "editor");
+    assert_eq!(revisions[0]["content"], "# Test");
+    let revision = revisions[0]["uuid"].as_str().unwrap();
+
+    let mut response = client
+        .get(format!("/v2/cuecards/card/revisions/{}/diff", revision))
+        .dispatch();
+    let diff: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(diff["content"][1]["change"], "added");
+    assert_eq!(diff["content"][1]["text"], "Wrong");
+
+    let mut response = client
+        .post(format!("/v2/cuecards/card/revisions/{}/restore", revision))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let cuecard: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(cuecard["content"], "# Test");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("cuecards/w/card.md")).unwrap(),
+        "# Test"
+    );
+
+    // the restore can be undone
+    let mut response = client.get("/v2/cuecards/card/revisions").dispatch();
+    let revisions: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(revisions.as_array().unwrap().len(), 2);
+    assert_eq!(revisions[0]["source"], "restore");
+    assert_eq!(revisions[0]["content"], "# Test\nWrong");
+
+    let response = client
+        .post("/v2/cuecards/card/revisions/missing/restore")
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_restore_revision_metadata() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "card", "", "w/card.md");
+    fs::write(dir.path().join("cuecards/w/card.md"), "# Test").unwrap();
+
+    let response = client
+        .post("/v2/cuecards/card/metadata")
+        .header(ContentType::JSON)
+        .header(Header::new("If-Match", "\"2019-11-01T10:00:00.000Z\""))
+        .body(r#"{"choreographer": "Jane", "phase": "V", "rhythm": "Rumba"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let mut response = client.get("/v2/cuecards/card/revisions").dispatch();
+    let revisions: Value = from_str(&response.body_string().unwrap()).unwrap();
+    let revision = revisions[0]["uuid"].as_str().unwrap();
+
+    let mut response = client
+        .post(format!("/v2/cuecards/card/revisions/{}/restore", revision))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let cuecard: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(cuecard["phase"], "IV");
+
+    // the indexer derives the restored metadata
+    let metadata =
+        cuecard_indexer::file_metadata(&dir.path().join("cuecards/w/card.md"), "# Test").unwrap();
+    assert_eq!(metadata["phase"], "IV");
+    assert_eq!(metadata["rhythm"], "Waltz");
+    assert_eq!(metadata["choreographer"], "Test");
+}
+
+#[test]
+fn test_cuecard_update_conflicts() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "card", "", "w/card.md");
+    fs::write(dir.path().join("cuecards/w/card.md"), "# Test").unwrap();
+
+    let if_match = |version: &str| Header::new("If-Match", format!("\"{}\"", version));
+
+    let response = client
+        .post("/v2/cuecards/card/content")
+        .header(ContentType::JSON)
+        .header(if_match("2019-11-01T10:00:00.000Z"))
+        .body(r##"{"content": "# Changed"}"##)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let version = response.headers().get_one("ETag").unwrap().trim_matches('"');
+    assert_ne!(version, "2019-11-01T10:00:00.000Z");
+
+    // a second editor still has the first version
+    let mut response = client
+        .post("/v2/cuecards/card/content")
+        .header(ContentType::JSON)
+        .header(if_match("2019-11-01T10:00:00.000Z"))
+        .body(r##"{"content": "# Overwritten"}"##)
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let cuecard: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(cuecard["content"], "# Changed");
+    assert_eq!(
+        fs::read_to_string(dir.path().join("cuecards/w/card.md")).unwrap(),
+        "# Changed"
+    );
+
+    let metadata = r#"{"choreographer": "Test", "phase": "V", "rhythm": "Waltz"}"#;
+
+    let response = client
+        .post("/v2/cuecards/card/metadata")
+        .header(ContentType::JSON)
+        .header(if_match("2019-11-01T10:00:00.000Z"))
+        .body(metadata)
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+
+    let response = client
+        .post("/v2/cuecards/card/metadata")
+        .header(ContentType::JSON)
+        .header(if_match(version))
+        .body(metadata)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    // an update has to name the version it is based on
+    let response = client
+        .post("/v2/cuecards/card/content")
+        .header(ContentType::JSON)
+        .body(r##"{"content": "# Unchecked"}"##)
+        .dispatch();
+    assert_eq!(response.status(), Status::PreconditionRequired);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("cuecards/w/card.md")).unwrap(),
+        "# Changed"
+    );
+
+    // any version matches `*`
+    let response = client
+        .post("/v2/cuecards/card/content")
+        .header(ContentType::JSON)
+        .header(if_match("*"))
+        .body(r##"{"content": "# Unchecked"}"##)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn test_cuecard_concurrent_update() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "card", "", "w/card.md");
+    let path = dir.path().join("cuecards/w/card.md");
+    fs::write(&path, "# Test").unwrap();
+
+    let conn = DbConn::get_one(client.rocket()).expect("No pooled connection");
+    let read = cuer_database::cuecard_by_uuid("card", &conn).unwrap();
+
+    // another editor saves after the cue card was read and checked
+    let saved = crate::cuecards::save_content(&read, "# First", &path, &conn).unwrap();
+    assert!(saved.is_some());
+
+    let saved = crate::cuecards::save_content(&read, "# Second", &path, &conn).unwrap();
+    assert!(saved.is_none());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "# First");
+
+    let mut response = client.get("/v2/cuecards/card/revisions").dispatch();
+    let revisions: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(revisions.as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_program_notes_conflicts() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_program(dir.path());
+
+    let response = client
+        .post("/v2/program/1/notes")
+        .header(ContentType::JSON)
+        .header(Header::new("If-Match", "\"2019-11-01T10:00:00.000Z\""))
+        .body(r#"{"notes": "Welcome"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let mut response = client
+        .post("/v2/program/1/notes")
+        .header(ContentType::JSON)
+        .header(Header::new("If-Match", "\"2019-11-01T10:00:00.000Z\""))
+        .body(r#"{"notes": "Overwritten"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let program: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(program["notes"], "Welcome");
+
+    let response = client
+        .post("/v2/program/1/notes")
+        .header(ContentType::JSON)
+        .body(r#"{"notes": "Unchecked"}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::PreconditionRequired);
+}
+
+#[test]
+fn test_convert_odt_path_traversal() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    let mut odt = std::io::Cursor::new(Vec::new());
+    {
+        let mut archive = zip::ZipWriter::new(&mut odt);
+        archive
+            .start_file("content.xml", zip::write::FileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut archive, b"<office:document-content xmlns:office=\"urn:oasis:names:tc:opendocument:xmlns:office:1.0\"/>").unwrap();
+        archive.finish().unwrap();
+    }
+
+    // only the file name is used for the path in the library
+    let response = client
+        .post("/v2/convert/odt")
+        .header(ContentType::Binary)
+        .header(rocket::http::Header::new("X-Input-FileName", "../../escape"))
+        .body(odt.into_inner())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(!dir.path().join("escape.md").exists());
+    assert!(dir.path().join("cuecards/e/escape.md").exists());
+}
+
+#[test]
+fn test_convert_path_taken() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    let mut odt = std::io::Cursor::new(Vec::new());
+    {
+        let mut archive = zip::ZipWriter::new(&mut odt);
+        archive
+            .start_file("content.xml", zip::write::FileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut archive, b"<office:document-content xmlns:office=\"urn:oasis:names:tc:opendocument:xmlns:office:1.0\"/>").unwrap();
+        archive.finish().unwrap();
+    }
+
+    fs::create_dir_all(dir.path().join("cuecards/t")).unwrap();
+    fs::write(dir.path().join("cuecards/t/taken.md"), "# Taken").unwrap();
+
+    let response = client
+        .post("/v2/convert")
+        .header(ContentType::Binary)
+        .header(Header::new("X-Input-FileName", "taken.odt"))
+        .body(odt.into_inner())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("X-Stored-As"),
+        Some("t/taken (2).md")
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("cuecards/t/taken.md")).unwrap(),
+        "# Taken"
+    );
+    assert!(dir.path().join("cuecards/t/taken (2).md").exists());
+}
+
+#[test]
+fn test_convert_needs_review() {
+    use printpdf::{BuiltinFont, Mm, PdfDocument};
+
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    let (doc, page, layer) = PdfDocument::new("Moon River", Mm(210.0), Mm(297.0), "Cue sheet");
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica).unwrap();
+    doc.get_page(page)
+        .get_layer(layer)
+        .use_text("Moon River", 16.0, Mm(20.0), Mm(270.0), &font);
+    let mut pdf = std::io::BufWriter::new(Vec::new());
+    doc.save(&mut pdf).unwrap();
+
+    let response = client
+        .post("/v2/convert")
+        .header(ContentType::Binary)
+        .header(Header::new("X-Input-FileName", "Moon River.pdf"))
+        .body(pdf.into_inner().unwrap())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("X-Needs-Review"), Some("true"));
+    assert_eq!(
+        response.headers().get_one("X-Conversion-Warnings"),
+        Some("No parts were recognized; No cues were recognized")
+    );
+    assert!(dir.path().join("cuecards/m/Moon River.md").exists());
+}
+
+#[test]
+fn test_export_cuecard() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "card", "", "t/test.md");
+
+    let mut response = client.get("/v2/cuecards/card/export?format=docx").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("Content-Disposition"),
+        Some("attachment; filename=\"Test.docx\"")
+    );
+
+    let mut markdown = Vec::new();
+    cuecard_indexer::convert::convert_to_markdown(
+        &mut std::io::Cursor::new(response.body_bytes().unwrap()),
+        &mut markdown,
+    )
+    .unwrap();
+    let markdown = String::from_utf8(markdown).unwrap();
+    assert!(markdown.starts_with("# Test\n"));
+    assert!(markdown.contains("* **Choreographer**: Test"));
+    assert!(markdown.contains("* **Phase**: IV"));
+
+    let response = client.get("/v2/cuecards/card/export?format=odt").dispatch();
+    assert_eq!(
+        response.content_type(),
+        Some(ContentType::new("application", "vnd.oasis.opendocument.text"))
+    );
+
+    let response = client.get("/v2/cuecards/card/export?format=pdf").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let response = client.get("/v2/cuecards/missing/export?format=odt").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_preview_conversion() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_cuecard(dir.path(), "card", "", "t/test.md");
+    fs::create_dir_all(dir.path().join("cuecards/t")).unwrap();
+    fs::write(dir.path().join("cuecards/t/test.md"), "# Test").unwrap();
+
+    let mut odt = std::io::Cursor::new(Vec::new());
+    {
+        let mut archive = zip::ZipWriter::new(&mut odt);
+        archive
+            .start_file("content.xml", zip::write::FileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(
+            &mut archive,
+            concat!(
+                "<office:document-content ",
+                r#"xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" "#,
+                r#"xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0">"#,
+                "<office:body><office:text>",
+                "<text:p>Test</text:p><text:p>Choreographer: Test</text:p>",
+                "</office:text></office:body></office:document-content>"
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        archive.finish().unwrap();
+    }
+
+    let mut response = client
+        .post("/v2/convert/preview")
+        .header(ContentType::Binary)
+        .header(rocket::http::Header::new("X-Input-FileName", "test.odt"))
+        .body(odt.into_inner())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let preview: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(preview["metadata"]["title"], "Test");
+    assert_eq!(preview["metadata"]["choreographer"], "Test");
+    assert_eq!(preview["target_path"], "t/test (2).md");
+    assert_eq!(preview["warnings"].as_array().unwrap().len(), 3);
+    assert_eq!(preview["needs_review"], false);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("cuecards/t/test.md")).unwrap(),
+        "# Test"
+    );
+
+    let response = client
+        .post("/v2/convert/preview")
+        .header(ContentType::Binary)
+        .header(rocket::http::Header::new("X-Input-FileName", "large.odt"))
+        .body(vec![0; 10 * 1024 * 1024 + 1])
+        .dispatch();
+    assert_eq!(response.status(), Status::PayloadTooLarge);
+}
+
+#[test]
+fn test_import_cue_sheets() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    let mut archive = std::io::Cursor::new(Vec::new());
+    {
+        let mut writer = zip::ZipWriter::new(&mut archive);
+        writer
+            .start_file("notes.txt", zip::write::FileOptions::default())
+            .unwrap();
+        std::io::Write::write_all(&mut writer, b"notes").unwrap();
+        writer.finish().unwrap();
+    }
+
+    let mut response = client
+        .post("/v2/convert/batch")
+        .header(ContentType::Binary)
+        .body(archive.into_inner())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let report: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(report[0]["source"], "notes.txt");
+    assert_eq!(report[0]["status"], "skipped");
+
+    let response = client
+        .post("/v2/convert/batch")
+        .header(ContentType::Binary)
+        .body("not an archive")
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn test_bundle_round_trip() {
+    let source_dir = tempfile::tempdir().unwrap();
+    let source = sandboxed_client(source_dir.path());
+
+    insert_cuecard(source_dir.path(), "card", "waltz.mp3", "w/card.md");
+    fs::write(source_dir.path().join("cuecards/w/card.md"), "# Test").unwrap();
+
+    {
+        use cuer_database::models::{EventData, ProgramData, TipCuecardData, TipData};
+
+        let conn = cuer_database::establish_connection(
+            source_dir.path().join("library.db").to_str().unwrap(),
+        );
+        let event = EventData {
+            uuid: "event",
+            name: "Club Night",
+            date_start: "2019-11-01T19:00:00.000Z",
+            date_end: "2019-11-01T22:00:00.000Z",
+            schedule: None,
+            date_created: "2019-11-01T10:00:00.000Z",
+            date_modified: "2019-11-01T10:00:00.000Z",
+            series_id: None,
+            recurrence_id: None,
+        }
+        .create(&conn)
+        .unwrap();
+        let program = ProgramData {
+            uuid: "program",
+            notes: None,
+            event_id: event.id,
+            date_created: "2019-11-01T10:00:00.000Z",
+            date_modified: "2019-11-01T10:00:00.000Z",
+        }
+        .create(&conn)
+        .unwrap();
+        let tip = TipData {
+            uuid: "tip",
+            name: "Tip 1",
+            program_id: &program.id,
+            date_start: "2019-11-01T19:00:00.000Z",
+            date_end: "2019-11-01T19:15:00.000Z",
+        }
+        .create(&conn)
+        .unwrap();
+        let cuecard = cuer_database::cuecard_by_uuid("card", &conn).unwrap();
+        TipCuecardData {
+            tip_id: &tip.id,
+            cuecard_id: &cuecard.id,
+            sort_order: &1,
+            cued_at: None,
+        }
+        .create(&conn)
+        .unwrap();
+    }
+
+    let mut response = source.get("/v2/events/event/bundle.zip").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let bundle = response.body_bytes().unwrap();
+
+    let target_dir = tempfile::tempdir().unwrap();
+    let target = sandboxed_client(target_dir.path());
+    fs::remove_file(target_dir.path().join("music/waltz.mp3")).unwrap();
+
+    let mut response = target
+        .post("/v2/bundles")
+        .header(ContentType::Binary)
+        .body(bundle.clone())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let report: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(report["created_cuecards"][0], "card");
+    assert_eq!(report["copied_music_files"][0], "waltz.mp3");
+
+    assert_eq!(
+        fs::read_to_string(target_dir.path().join("cuecards/w/card.md")).unwrap(),
+        "# Test"
+    );
+    assert_eq!(
+        fs::read_to_string(target_dir.path().join("cuecards/w/.de.sopicki.cuelib.card.md"))
+            .unwrap(),
+        "card"
+    );
+    assert!(target_dir.path().join("music/waltz.mp3").exists());
+
+    let response = target.get("/v2/events/event/program.html").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let mut response = target
+        .post("/v2/bundles")
+        .header(ContentType::Binary)
+        .body(bundle)
+        .dispatch();
+    let report: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(report["unchanged_cuecards"][0], "card");
+}
+
+#[test]
+fn test_bundle_path_of_other_cuecard() {
+    let source_dir = tempfile::tempdir().unwrap();
+    let source = sandboxed_client(source_dir.path());
+
+    insert_program(source_dir.path());
+
+    let mut response = source.get("/v2/events/event/bundle.zip").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let bundle = response.body_bytes().unwrap();
+
+    // the file of the cue card is missing, but its path is still taken
+    let target_dir = tempfile::tempdir().unwrap();
+    let target = sandboxed_client(target_dir.path());
+    insert_cuecard(target_dir.path(), "other", "waltz.mp3", "w/a.md");
+
+    let mut response = target
+        .post("/v2/bundles")
+        .header(ContentType::Binary)
+        .body(bundle)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let report: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(report["created_cuecards"].as_array().unwrap().len(), 2);
+
+    let conn = DbConn::get_one(target.rocket()).expect("No pooled connection");
+    let cuecard = cuer_database::cuecard_by_uuid("a", &conn).unwrap();
+    assert_eq!(cuecard.file_path, "w/a (2).md");
+    assert!(target_dir.path().join("cuecards/w/a (2).md").exists());
+    assert!(!target_dir.path().join("cuecards/w/a.md").exists());
+}
+
+/// Inserts the event `event` with the tips `tip1` and `tip2`, `tip1` contains the cue cards `a`
+/// and `b`.
+fn insert_program(dir: &Path) {
+    use cuer_database::models::{EventData, ProgramData, TipCuecardData, TipData};
+
+    insert_cuecard(dir, "a", "waltz.mp3", "w/a.md");
+    insert_cuecard(dir, "b", "waltz.mp3", "w/b.md");
+
+    let conn = cuer_database::establish_connection(dir.join("library.db").to_str().unwrap());
+    let event = EventData {
+        uuid: "event",
+        name: "Club Night",
+        date_start: "2019-11-01T19:00:00.000Z",
+        date_end: "2019-11-01T22:00:00.000Z",
+        schedule: None,
+        date_created: "2019-11-01T10:00:00.000Z",
+        date_modified: "2019-11-01T10:00:00.000Z",
+        series_id: None,
+        recurrence_id: None,
+    }
+    .create(&conn)
+    .unwrap();
+    let program = ProgramData {
+        uuid: "program",
+        notes: None,
+        event_id: event.id,
+        date_created: "2019-11-01T10:00:00.000Z",
+        date_modified: "2019-11-01T10:00:00.000Z",
+    }
+    .create(&conn)
+    .unwrap();
+
+    for (uuid, start, end) in &[("tip1", "19:00", "19:15"), ("tip2", "19:15", "19:30")] {
+        TipData {
+            uuid,
+            name: uuid,
+            program_id: &program.id,
+            date_start: &format!("2019-11-01T{}:00.000Z", start),
+            date_end: &format!("2019-11-01T{}:00.000Z", end),
+        }
+        .create(&conn)
+        .unwrap();
+    }
+
+    let tip = cuer_database::tip_by_uuid("tip1", &conn).unwrap();
+
+    for uuid in &["a", "b"] {
+        let cuecard = cuer_database::cuecard_by_uuid(uuid, &conn).unwrap();
+        TipCuecardData {
+            tip_id: &tip.id,
+            cuecard_id: &cuecard.id,
+            sort_order: &1,
+            cued_at: None,
+        }
+        .create(&conn)
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_order_tip_cuecards() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_program(dir.path());
+
+    let put_order = |tip: &str, body: &str| {
+        let mut response = client
+            .put(format!("/v2/tips/{}/order", tip))
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch();
+        let status = response.status();
+        let body = response.body_string().unwrap_or_default();
+        (status, from_str::<Value>(&body).unwrap_or(Value::Null))
+    };
+
+    let (status, order) = put_order("tip1", r#"{"cuecards": ["b", "a"]}"#);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(order[0]["cuecard_id"], 2);
+    assert_eq!(order[0]["sort_order"], 1);
+    assert_eq!(order[1]["sort_order"], 2);
+
+    // cue cards missing in the list would be lost
+    let (status, _) = put_order("tip1", r#"{"cuecards": ["a"]}"#);
+    assert_eq!(status, Status::Conflict);
+
+    let (status, _) = put_order("tip1", r#"{"cuecards": ["a", "a", "b"]}"#);
+    assert_eq!(status, Status::BadRequest);
+
+    let (status, order) = put_order("tip2", r#"{"cuecards": ["b"], "from_tip": "tip1"}"#);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(order.as_array().unwrap().len(), 1);
+
+    let (status, order) = put_order("tip1", r#"{"cuecards": ["a"]}"#);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(order[0]["cuecard_id"], 1);
+    assert_eq!(order[0]["sort_order"], 1);
+
+    // cue cards of other tips are only moved with from_tip
+    let (status, _) = put_order("tip2", r#"{"cuecards": ["b", "a"]}"#);
+    assert_eq!(status, Status::BadRequest);
+
+    let (status, order) = put_order("tip2", r#"{"cuecards": ["b"]}"#);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(order.as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_add_tip_cuecards() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_program(dir.path());
+
+    for uuid in &["a", "b"] {
+        let response = client
+            .put("/v2/tip_cuecard")
+            .header(ContentType::JSON)
+            .body(format!(r#"{{"tip_uuid": "tip2", "cuecard_uuid": "{}"}}"#, uuid))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    let conn = DbConn::get_one(client.rocket()).expect("No pooled connection");
+    let tip = cuer_database::tip_by_uuid("tip2", &conn).unwrap();
+    let order: Vec<(i32, i32)> = crate::programming::get_tip_cuecards(&tip, &conn)
+        .unwrap()
+        .iter()
+        .map(|c| (c.cuecard_id, c.sort_order))
+        .collect();
+    assert_eq!(order, vec![(1, 1), (2, 2)]);
+}
+
+#[test]
+fn test_series_occurrences() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_program(dir.path());
+
+    let mut response = client
+        .put("/v2/event_series")
+        .header(ContentType::JSON)
+        .body(
+            r#"{"name": "Practice", "date_start": "2019-11-05T19:00:00.000Z",
+                "date_end": "2019-11-05T21:00:00.000Z", "rrule": "FREQ=WEEKLY;COUNT=2"}"#,
+        )
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let series: Value = from_str(&response.body_string().unwrap()).unwrap();
+
+    let mut response = client
+        .get("/v2/events/2019-10-01T00:00:00Z/2019-12-01T00:00:00Z")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let events: Value = from_str(&response.body_string().unwrap()).unwrap();
+    let events = events.as_array().unwrap();
+    assert_eq!(events.len(), 3);
+
+    // occurrences are no events yet and can't be mistaken for one
+    let occurrences: Vec<&Value> = events.iter().filter(|e| e.get("uuid").is_none()).collect();
+    assert_eq!(occurrences.len(), 2);
+    for occurrence in occurrences {
+        assert!(occurrence.get("id").is_none());
+        assert_eq!(occurrence["series_uuid"], series["uuid"]);
+        assert!(occurrence["recurrence_id"].is_string());
+    }
+
+    let response = client.get("/v2/event/program/0").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    let response = client.get("/v2/event/0/program/notes").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    let response = client
+        .post("/v2/cues")
+        .header(ContentType::JSON)
+        .body(r#"{"cuecard_uuid": "a", "event_uuid": ""}"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_delete_event() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_program(dir.path());
+
+    let mut response = client.delete("/v2/events/event?dry_run=true").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let preview: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(preview["dry_run"], true);
+    assert_eq!(preview["programs"].as_array().unwrap().len(), 1);
+    assert_eq!(preview["tips"].as_array().unwrap().len(), 2);
+    assert_eq!(preview["tip_cuecards"].as_array().unwrap().len(), 2);
+
+    let conn = cuer_database::establish_connection(dir.path().join("library.db").to_str().unwrap());
+    let rows = || {
+        use cuer_database::schema::{programs, tip_cuecards, tips};
+        use diesel::prelude::*;
+
+        (
+            programs::table.count().get_result::<i64>(&conn).unwrap(),
+            tips::table.count().get_result::<i64>(&conn).unwrap(),
+            tip_cuecards::table.count().get_result::<i64>(&conn).unwrap(),
+        )
+    };
+
+    // the dry run leaves everything in place
+    assert_eq!(rows(), (1, 2, 2));
+
+    let response = client.get("/v2/events/event").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let mut response = client.delete("/v2/events/event").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let deletion: Value = from_str(&response.body_string().unwrap()).unwrap();
+    assert_eq!(deletion["dry_run"], false);
+    assert_eq!(deletion["event"]["uuid"], "event");
+
+    assert!(cuer_database::tip_by_uuid("tip1", &conn).is_err());
+    assert_eq!(rows(), (0, 0, 0));
+
+    let response = client.delete("/v2/events/event").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_pooled_connection_foreign_keys() {
+    use diesel::dsl::sql;
+    use diesel::sql_types::Integer;
+    use diesel::RunQueryDsl;
+
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    let conn = DbConn::get_one(client.rocket()).expect("No pooled connection");
+    let foreign_keys = sql::<Integer>("PRAGMA foreign_keys")
+        .get_result::<i32>(&**conn)
+        .unwrap();
+    assert_eq!(foreign_keys, 1);
+}
+
+#[test]
+fn test_start_cue_of_other_event() {
+    use cuer_database::models::EventData;
+
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_program(dir.path());
+
+    let conn = cuer_database::establish_connection(dir.path().join("library.db").to_str().unwrap());
+    EventData {
+        uuid: "other",
+        name: "Practice",
+        date_start: "2019-11-02T19:00:00.000Z",
+        date_end: "2019-11-02T22:00:00.000Z",
+        schedule: None,
+        date_created: "2019-11-01T10:00:00.000Z",
+        date_modified: "2019-11-01T10:00:00.000Z",
+        series_id: None,
+        recurrence_id: None,
+    }
+    .create(&conn)
+    .unwrap();
+
+    let start_cue = |event: &str| {
+        client
+            .post("/v2/cues")
+            .header(ContentType::JSON)
+            .body(format!(
+                r#"{{"cuecard_uuid": "a", "event_uuid": "{}", "tip_uuid": "tip1"}}"#,
+                event
+            ))
+            .dispatch()
+            .status()
+    };
+
+    assert_eq!(start_cue("other"), Status::NotFound);
+    assert_eq!(start_cue("event"), Status::Ok);
+}
+
+#[test]
+fn test_copy_program_into_itself() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = sandboxed_client(dir.path());
+
+    insert_program(dir.path());
+
+    let response = client.post("/v2/events/event/program/from_event/event").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let conn = cuer_database::establish_connection(dir.path().join("library.db").to_str().unwrap());
+    assert!(cuer_database::tip_by_uuid("tip1", &conn).is_ok());
+}
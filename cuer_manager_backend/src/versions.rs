@@ -0,0 +1,49 @@
+/**
+
+Versions of cue cards and programs.
+
+The modification date of a cue card or a program is its version. Responses to updates carry the
+new version as `ETag` header, and clients have to send the version they based an update on as
+`If-Match` header (see `guards::IfMatch`). An update based on an outdated version is rejected with
+a conflict, which carries the current state so the client can show what changed meanwhile. The
+database only applies an update while the version is unchanged, so concurrent updates conflict
+as well.
+
+**/
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+
+/// A response for the version `.1` of a resource.
+pub struct Versioned<R>(pub R, pub String);
+
+impl<'r, R: Responder<'r>> Responder<'r> for Versioned<R> {
+    fn respond_to(self, req: &Request) -> response::Result<'r> {
+        Response::build_from(self.0.respond_to(req)?)
+            .raw_header("ETag", format!("\"{}\"", self.1))
+            .ok()
+    }
+}
+
+/// A failed update, a conflict responds with the current state of the resource.
+pub enum Rejection<R> {
+    Status(Status),
+    Conflict(R),
+}
+
+impl<R> From<Status> for Rejection<R> {
+    fn from(status: Status) -> Self {
+        Rejection::Status(status)
+    }
+}
+
+impl<'r, R: Responder<'r>> Responder<'r> for Rejection<R> {
+    fn respond_to(self, req: &Request) -> response::Result<'r> {
+        match self {
+            Rejection::Status(status) => Err(status),
+            Rejection::Conflict(current) => Response::build_from(current.respond_to(req)?)
+                .status(Status::Conflict)
+                .ok(),
+        }
+    }
+}
@@ -144,9 +144,15 @@ export class CuecardComponent implements OnInit, OnDestroy, AfterViewInit {
     let content = document.getElementById('markdown').innerText;
 
     if (cuecard.content != content) {
-      this.service.updateContent(cuecard.uuid, content).subscribe(_ => {
+      this.service.updateContent(cuecard, content).subscribe(_ => {
         this.loadCuecardContent();
         cuecard.content = content;
+      }, (error) => {
+        if (error.status === 409) {
+          this._logError("The cuecard has been changed meanwhile, reload it to edit the current version.");
+        } else {
+          this._logError("Saving the cuecard failed!");
+        }
       })
     }
     
@@ -1,5 +1,5 @@
 import { Injectable } from '@angular/core';
-import { HttpClient, HttpHeaders } from '@angular/common/http';
+import { HttpClient, HttpHeaders, HttpResponse } from '@angular/common/http';
 import { Observable, Subject } from 'rxjs';
 import { map } from 'rxjs/operators';
 
@@ -65,17 +65,15 @@ export class CuecardService {
     return this.http.post<String>('/v2/cuecards/' + uuid + '/marks', data);
   }
 
-  updateContent(uuid: String, content: String): Observable<String> {
+  updateContent(cuecard: Cuecard, content: String): Observable<void> {
     let data = {
       content: content
     }
 
-    const httpOptions = {
-      headers: new HttpHeaders({ 'Content-Type': 'text/html' }),
-      responseType: 'text' as 'json'
-    };
-
-    return this.http.post<String>('/v2/cuecards/' + uuid + '/content', data);
+    return this.http.post('/v2/cuecards/' + cuecard.uuid + '/content', data, CuecardService._versionOptions(cuecard))
+      .pipe(
+        map(response => { cuecard.date_modified = CuecardService._version(response) })
+      );
   }
 
   refresh(): Observable<void> {
@@ -111,7 +109,26 @@ export class CuecardService {
   }
 
   saveMetaData(cuecard: Cuecard, metaData: MetaData): Observable<void> {
-    return this.http.post<void>("/v2/cuecards/" + cuecard.uuid + "/metadata", metaData);
+    return this.http.post("/v2/cuecards/" + cuecard.uuid + "/metadata", metaData, CuecardService._versionOptions(cuecard))
+      .pipe(
+        map(response => { cuecard.date_modified = CuecardService._version(response) })
+      );
+  }
+
+  /**
+   * Options for an update of the version of the cuecard the client knows, the server rejects
+   * the update with status 409 if the cuecard has been changed meanwhile.
+   */
+  private static _versionOptions(cuecard: Cuecard) {
+    return {
+      headers: new HttpHeaders({ 'If-Match': '"' + cuecard.date_modified + '"' }),
+      observe: 'response' as 'response',
+      responseType: 'text' as 'text'
+    };
+  }
+
+  private static _version(response: HttpResponse<string>): String {
+    return response.headers.get('ETag').replace(/"/g, '');
   }
   
   private static _b64EncodeUnicode(str: string): string {
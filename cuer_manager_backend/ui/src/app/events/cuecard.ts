@@ -11,6 +11,7 @@ export class Cuecard {
 	content: String;
 	karaoke_marks: String;
 	music_file: String;
+	date_modified: String;
 
     constructor(data: any) {
         Object.assign(this, data);
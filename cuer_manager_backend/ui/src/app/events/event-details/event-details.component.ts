@@ -372,51 +372,23 @@ export class EventDetailsComponent implements OnInit {
       let previousTip = <Tip>event.previousContainer.data;
       let tip = <Tip>event.container.data;
       let cuecard = <Cuecard>event.item.data;
-      // console.log("Moving from index", event.previousIndex, "to index", event.currentIndex);
 
-      //Step 1: add cuecard to it's new location
-      this.tipService.addCuecard(tip.uuid, cuecard.uuid, event.currentIndex+1).subscribe(_ => {
+      previousTip.cuecards.splice(event.previousIndex, 1);
+      tip.cuecards.splice(event.currentIndex, 0, cuecard);
 
-        //Step 2: Adjust position of the cuecards from the currentIndex onward
-        for(let i = event.currentIndex; i < tip.cuecards.length; i++) {
-          let current = (<Cuecard[]>tip.cuecards)[i];
-          this.tipService.updateCuecard(tip.uuid, current.uuid, i+2).subscribe(_ => {});
-        }
-        //Add cuecard to container
-        tip.cuecards.splice(event.currentIndex,0,cuecard);
-      })
-     
-      //Step 3: remove cuecard from it's previous location
-      this.tipService.removeCuecard(previousTip.uuid, cuecard.uuid).subscribe(_ => {
-        //Step 4: Adjust position of the cuecards after the previousIndex
-        for(let i = event.previousIndex+1; i < previousTip.cuecards.length; i++) {
-          let current = (<Cuecard[]>previousTip.cuecards)[i];
-          this.tipService.updateCuecard(previousTip.uuid, current.uuid, i).subscribe(_ => {});
-        }
-        //Step 5: Remove the cuecard from the container
-        previousTip.cuecards.splice(event.previousIndex,1);
-      })
+      let uuids = (<Cuecard[]>tip.cuecards).map(card => card.uuid);
+      this.tipService.orderCuecards(tip.uuid, uuids, previousTip.uuid).subscribe(_ => {});
 
       this.analyze(this.tips);
     } else if (event.currentIndex != event.previousIndex) {
       let tip = <Tip>event.container.data;
 
-      let cuecards = [];
-
-      for (let card of tip.cuecards) {
-        cuecards.push({...card});
-      }
       moveItemInArray(tip.cuecards, event.previousIndex, event.currentIndex);
-  
-      cuecards.forEach((cuecard, index) => {
-        let newIndex = tip.cuecards.findIndex((card: Cuecard) => card.uuid == cuecard.uuid )
-  
-        if (index != newIndex) {
-          this.tipService.updateCuecard(tip.uuid, (<Cuecard>cuecard).uuid, newIndex+1).subscribe(_ => {});
-        }
-      });
-      
-      this.analyze(this.tips);      
+
+      let uuids = (<Cuecard[]>tip.cuecards).map(card => card.uuid);
+      this.tipService.orderCuecards(tip.uuid, uuids, null).subscribe(_ => {});
+
+      this.analyze(this.tips);
     }
   }
 
@@ -12,7 +12,8 @@ import { Message, MessageType } from '../message';
 const urls = {
   "events": "v2/events",
   "event": "v2/events/",
-  "new_event": "v2/event"
+  "new_event": "v2/event",
+  "event_series": "v2/event_series/"
 }
 
 const httpOptions = {
@@ -72,6 +73,18 @@ export class EventService {
     );
   }
 
+  /**
+   * Turns the occurrence of an event series into an event, which gives it a uuid.
+   */
+  materializeOccurrence(event: Event): Observable<Event> {
+    let url = urls['event_series'] + event.series_id + '/occurrences/' + event.recurrence_id;
+
+    return this.http.put<Event>(url, null, httpOptions).pipe(
+      map(eventData => new Event(eventData)),
+      catchError(this.handleError<Event>('materializeOccurrence'))
+    );
+  }
+
   /**
    * Handle Http operation that failed.
    * Let the app continue.
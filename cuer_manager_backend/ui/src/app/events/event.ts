@@ -8,6 +8,10 @@ export class Event {
     date_start: Date;
     date_end: Date;
     name: String;
+    // occurrences of an event series have neither an id nor a uuid until they are materialized
+    series_id: number | null;
+    series_uuid?: String;
+    recurrence_id: String | null;
     program: Program | null;
     tips: Tip[];
 
@@ -128,7 +128,15 @@ export class EventsComponent implements OnInit {
   }
 
   showDetails(event: Event) {
-    this.router.navigate(['/events/details/' + event.uuid]);
+    if (event.uuid || event.series_id == null) {
+      this.router.navigate(['/events/details/' + event.uuid]);
+      return;
+    }
 
+    this.eventService.materializeOccurrence(event).subscribe(created => {
+      if (created) {
+        this.router.navigate(['/events/details/' + created.uuid]);
+      }
+    });
   }
 }
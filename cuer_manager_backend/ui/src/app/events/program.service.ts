@@ -1,6 +1,6 @@
 import { Injectable } from '@angular/core';
 import { HttpClient, HttpHeaders, HttpParams } from '@angular/common/http';
-import { Observable, of } from 'rxjs';
+import { EMPTY, Observable, of } from 'rxjs';
 import { map, catchError } from 'rxjs/operators'; //tap
 
 import { OptionalProgram, Program } from './program';
@@ -51,12 +51,33 @@ export class ProgramService {
 
   updateNotes(program: Program, notes: String): Observable<String> {
     const data = {
-      notes: notes,
-      date_modified: DateTime.utc().toISO()
+      notes: notes
     }
-    return this.http.post(urls['program_update_notes'] + program.id + '/notes', data, {responseType:'text'})
+    const version = program.date_modified instanceof Date
+      ? DateTime.fromJSDate(program.date_modified).toUTC().toISO()
+      : program.date_modified;
+    const options = {
+      headers: new HttpHeaders({ 'If-Match': '"' + version + '"' }),
+      observe: 'response' as 'response',
+      responseType: 'text' as 'text'
+    };
+
+    return this.http.post(urls['program_update_notes'] + program.id + '/notes', data, options)
       .pipe(
-        catchError(this.handleError<String>('updateNotes', notes))
+        map(response => {
+          const modified = response.headers.get('ETag').replace(/"/g, '');
+          program.date_modified = DateTime.fromISO(modified).toJSDate();
+
+          return response.body;
+        }),
+        catchError(error => {
+          if (error.status === 409) {
+            this.log('updateNotes failed: the notes have been changed meanwhile, reload the event to edit them.');
+            return EMPTY;
+          }
+
+          return this.handleError<String>('updateNotes', notes)(error);
+        })
       )
   }
 
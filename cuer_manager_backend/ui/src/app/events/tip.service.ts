@@ -64,6 +64,16 @@ class FormTipCuecard {
   }
 }
 
+class FormTipOrder {
+  cuecards: String[]
+  from_tip: String | null
+
+  constructor(cuecards: String[], from_tip: String | null) {
+    this.cuecards = cuecards;
+    this.from_tip = from_tip;
+  }
+}
+
 @Injectable({
   providedIn: 'root'
 })
@@ -149,6 +159,16 @@ export class TipService {
     );
   }
 
+  orderCuecards(tip_uuid: String, cuecard_uuids: String[], from_tip_uuid: String | null): Observable<any> {
+    var url = `/v2/tips/${tip_uuid}/order`;
+    let formTipOrder = new FormTipOrder(cuecard_uuids, from_tip_uuid);
+
+    return this.http.put(url, formTipOrder, httpOptions).pipe(
+      tap((_) => console.debug('Cuecards ordered!')),
+      catchError(this.handleError<void>('orderCuecards'))
+    );
+  }
+
   private log(message: string) {
     let msg = new Message(MessageType.ErrorMessage, `TipService: ${message}`)
     this.messageService.add(msg);
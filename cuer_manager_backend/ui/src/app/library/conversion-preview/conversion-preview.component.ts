@@ -0,0 +1,39 @@
+import { Component, Inject } from '@angular/core';
+import { MatDialogRef, MAT_DIALOG_DATA } from '@angular/material/dialog';
+
+import { ConversionPreview } from '../file-conversion.service';
+
+export interface ConversionPreviewData {
+  filename: String
+  preview: ConversionPreview
+}
+
+@Component({
+  selector: 'app-conversion-preview',
+  templateUrl: './conversion-preview.component.html',
+  styleUrls: ['./conversion-preview.component.scss']
+})
+export class ConversionPreviewComponent {
+
+  filename: String
+  preview: ConversionPreview
+  metadata: { key: string, value: string }[]
+
+  constructor(
+    public dialogRef: MatDialogRef<ConversionPreviewComponent>,
+    @Inject(MAT_DIALOG_DATA) public data: ConversionPreviewData) {
+    this.filename = data.filename;
+    this.preview = data.preview;
+    this.metadata = Object.keys(data.preview.metadata)
+      .sort()
+      .map(key => ({ key: key, value: data.preview.metadata[key] }));
+  }
+
+  onCancelClick(): void {
+    this.dialogRef.close(false);
+  }
+
+  onImportClick(): void {
+    this.dialogRef.close(true);
+  }
+}
@@ -1,9 +1,17 @@
 import { Injectable } from '@angular/core';
 import { HttpHeaders, HttpClient } from '@angular/common/http';
-import { Subject } from 'rxjs';
+import { Subject, Observable } from 'rxjs';
 
 import { MessageService } from '../message.service';
 
+export interface ConversionPreview {
+  markdown: string
+  metadata: { [key: string]: string }
+  target_path: string | null
+  warnings: string[]
+  needs_review: boolean
+}
+
 @Injectable({
   providedIn: 'root'
 })
@@ -15,22 +23,46 @@ export class FileConversionService {
 
   constructor(private client: HttpClient, private messageService: MessageService) { }
 
-  convertOdtFile(buffer: ArrayBuffer, filename: String) {
+  previewFile(buffer: ArrayBuffer, filename: String): Observable<ConversionPreview> {
+    let httpOptions = {
+      headers: new HttpHeaders({ 'Content-Type': 'application/octet-stream' })
+        .append('X-Input-FileName', filename.toString())
+    }
+
+    return this.client.post<ConversionPreview>("/v2/convert/preview", buffer, httpOptions);
+  }
+
+  convertFile(buffer: ArrayBuffer, filename: String) {
     let httpOptions = {
       headers: new HttpHeaders({ 'Content-Type': 'application/octet-stream' })
         .append('X-Input-FileName', filename.toString()),
+      observe: "response" as "response",
       responseType: "blob" as "text"
     }
     
-    this.client.post("/v2/convert/odt", buffer, httpOptions).subscribe((blob => {
+    this.client.post("/v2/convert", buffer, httpOptions).subscribe((response => {
+      let blob = response.body;
+
+      if (response.headers.get('X-Needs-Review') === 'true') {
+        let warnings = response.headers.get('X-Conversion-Warnings');
+        this.messageService.warn('Please review the converted cue card: ' + (warnings || filename));
+      }
+
       if (blob && blob.length) {
         let a = document.createElement('a');
         a.href = window.URL.createObjectURL(blob);
-        let name = filename.replace('.odt', '.md');
+        let name = filename.replace(/\.(odt|docx|pdf)$/i, '.md');
         a.download = name;
         a.dispatchEvent(new MouseEvent('click'));
       } else {
-        this.messageService.info('File has been converted and added to the library');
+        let storedAs = response.headers.get('X-Stored-As');
+
+        if (storedAs) {
+          this.messageService.info('File has been converted and added to the library as ' + storedAs
+            + ', as its path was already taken');
+        } else {
+          this.messageService.info('File has been converted and added to the library');
+        }
         this.announceUpdate();
       }
     }));
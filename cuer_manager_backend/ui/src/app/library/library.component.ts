@@ -6,6 +6,7 @@ import { MatDialog } from '@angular/material/dialog';
 import { SearchService } from '../search/search.service';
 import { Cuecard } from '../events/cuecard';
 import { MetaDataEditorData, MetadataEditorComponent } from './metadata-editor/metadata-editor.component';
+import { ConversionPreviewComponent } from './conversion-preview/conversion-preview.component';
 import { rhythms, phases } from '../shared/rhythms';
 import { MetaData, CuecardService } from '../cuecard/cuecard.service';
 import { MessageService } from '../message.service';
@@ -157,8 +158,12 @@ export class LibraryComponent implements OnInit, OnDestroy {
         updated_cuecard.meta['music'] = result.music;
         updated_cuecard.meta['plusfigures'] = result.plusfigures;
         this.messageService.info("Changes have been saved.");
-      }, () => {
-        this.messageService.error("Error saving meta data!");
+      }, (error) => {
+        if (error.status === 409) {
+          this.messageService.error("The cuecard has been changed meanwhile, reload the library to edit it.");
+        } else {
+          this.messageService.error("Error saving meta data!");
+        }
       });
 
       
@@ -190,7 +195,25 @@ export class LibraryComponent implements OnInit, OnDestroy {
     
     if (target.files.length > 0) {
       let reader = new FileReader();
-      (<any>target.files[0]).arrayBuffer().then((buffer) => this.fileService.convertOdtFile(buffer, target.files[0].name));
+      let name = target.files[0].name;
+
+      (<any>target.files[0]).arrayBuffer().then((buffer) => this.previewFile(buffer, name));
     }
   }
+
+  previewFile(buffer: ArrayBuffer, filename: String) {
+    this.fileService.previewFile(buffer, filename).subscribe(preview => {
+      const dialogRef = this.dialog.open(ConversionPreviewComponent, {
+        data: { filename: filename, preview: preview },
+        height: '70%',
+        width: '60%'
+      });
+
+      dialogRef.afterClosed().subscribe(confirmed => {
+        if (confirmed) {
+          this.fileService.convertFile(buffer, filename);
+        }
+      });
+    });
+  }
 }
@@ -25,6 +25,7 @@ import { CuecardCardComponent } from './cuecard-card/cuecard-card.component';
 import { TagsEditorComponent } from './tags-editor/tags-editor.component';
 import { MetadataEditorComponent } from './metadata-editor/metadata-editor.component';
 import { FileSelectorComponent } from './metadata-editor/file-selector/file-selector.component';
+import { ConversionPreviewComponent } from './conversion-preview/conversion-preview.component';
 
 const modules = [
   ReactiveFormsModule,
@@ -48,13 +49,13 @@ const modules = [
 ]
 
 @NgModule({
-  declarations: [LibraryComponent, CuecardCardComponent, TagsEditorComponent, MetadataEditorComponent, FileSelectorComponent],
+  declarations: [LibraryComponent, CuecardCardComponent, TagsEditorComponent, MetadataEditorComponent, FileSelectorComponent, ConversionPreviewComponent],
   imports: [
     ...modules,
     LibraryRoutingModule,
     CommonModule
   ],
-  entryComponents: [TagsEditorComponent, MetadataEditorComponent, FileSelectorComponent],
+  entryComponents: [TagsEditorComponent, MetadataEditorComponent, FileSelectorComponent, ConversionPreviewComponent],
   exports: [LibraryComponent, CuecardCardComponent]
 })
 export class LibraryModule { }
@@ -0,0 +1 @@
+DROP TABLE cues;
\ No newline at end of file
@@ -0,0 +1,25 @@
+CREATE TABLE cues (
+	id INTEGER NOT NULL PRIMARY KEY,
+	uuid TEXT NOT NULL UNIQUE,
+	cuecard_id INTEGER NOT NULL,
+	event_id INTEGER DEFAULT NULL,
+	tip_id INTEGER DEFAULT NULL,
+	source TEXT NOT NULL DEFAULT 'adhoc',
+	started_at TEXT NOT NULL,
+	stopped_at TEXT DEFAULT NULL,
+	FOREIGN KEY (cuecard_id) REFERENCES cuecards(id) ON UPDATE CASCADE ON DELETE CASCADE,
+	FOREIGN KEY (event_id) REFERENCES events(id) ON UPDATE CASCADE ON DELETE SET NULL,
+	FOREIGN KEY (tip_id) REFERENCES tips(id) ON UPDATE CASCADE ON DELETE SET NULL
+);
+
+CREATE INDEX cues_cuecard_id ON cues(cuecard_id);
+CREATE INDEX cues_event_id ON cues(event_id);
+
+INSERT INTO cues (uuid, cuecard_id, event_id, tip_id, source, started_at)
+    SELECT lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-' || hex(randomblob(2)) || '-' ||
+        hex(randomblob(2)) || '-' || hex(randomblob(6))), tip_cuecards.cuecard_id, programs.event_id,
+        tip_cuecards.tip_id, 'program', tip_cuecards.cued_at
+    FROM tip_cuecards
+    INNER JOIN tips ON tips.id = tip_cuecards.tip_id
+    INNER JOIN programs ON programs.id = tips.program_id
+    WHERE tip_cuecards.cued_at IS NOT NULL;
@@ -0,0 +1,19 @@
+DROP INDEX events_series;
+
+-- the old table is dropped before the new one is renamed, so references to events are kept
+CREATE TABLE events_new (
+    id INTEGER NOT NULL PRIMARY KEY,
+    uuid TEXT NOT NULL UNIQUE,
+    date_start TEXT NOT NULL, -- ISO8601 date and time string
+    date_end TEXT NOT NULL, -- ISO8601 date and time string
+    name TEXT NOT NULL,
+    schedule TEXT NULL,
+    date_created TEXT NOT NULL,
+    date_modified TEXT NOT NULL
+);
+
+INSERT INTO events_new select id, uuid, date_start, date_end, name, schedule, date_created, date_modified from events;
+DROP TABLE events;
+ALTER TABLE events_new RENAME TO events;
+
+DROP TABLE event_series;
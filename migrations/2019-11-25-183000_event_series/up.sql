@@ -0,0 +1,16 @@
+CREATE TABLE event_series (
+    id INTEGER NOT NULL PRIMARY KEY,
+    uuid TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    date_start TEXT NOT NULL, -- ISO8601 date and time string of the first occurrence
+    date_end TEXT NOT NULL, -- ISO8601 date and time string
+    rrule TEXT NOT NULL, -- iCalendar recurrence rule, e.g. FREQ=WEEKLY;BYDAY=TU
+    exceptions TEXT NOT NULL DEFAULT '', -- comma separated dates of cancelled occurrences
+    date_created TEXT NOT NULL,
+    date_modified TEXT NOT NULL
+);
+
+ALTER TABLE events ADD series_id INTEGER NULL REFERENCES event_series(id) ON DELETE SET NULL;
+ALTER TABLE events ADD recurrence_id TEXT NULL; -- date of the occurrence of the series
+
+CREATE INDEX events_series ON events(series_id, recurrence_id);
@@ -0,0 +1 @@
+DROP TABLE program_templates;
@@ -0,0 +1,9 @@
+CREATE TABLE program_templates (
+    id INTEGER NOT NULL PRIMARY KEY,
+    uuid TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    notes TEXT,
+    tips TEXT NOT NULL, -- JSON list of tips with their times relative to the start of the event
+    date_created TEXT NOT NULL,
+    date_modified TEXT NOT NULL
+);
@@ -0,0 +1,28 @@
+-- Requires PRAGMA foreign_keys = OFF, see up.sql
+DROP INDEX tip_cuecards_tip_id;
+
+CREATE TABLE tips_new (
+    id INTEGER NOT NULL PRIMARY KEY,
+    uuid TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    program_id INTEGER NOT NULL,
+    date_start TEXT NOT NULL,
+    date_end TEXT NOT NULL
+);
+
+INSERT INTO tips_new SELECT id, uuid, name, program_id, date_start, date_end FROM tips;
+DROP TABLE tips;
+ALTER TABLE tips_new RENAME TO tips;
+
+CREATE TABLE programs_new (
+    id INTEGER NOT NULL PRIMARY KEY,
+    uuid TEXT NOT NULL UNIQUE,
+    notes TEXT,
+    event_id INTEGER NOT NULL,
+    date_created TEXT NOT NULL,
+    date_modified TEXT NOT NULL
+);
+
+INSERT INTO programs_new SELECT id, uuid, notes, event_id, date_created, date_modified FROM programs;
+DROP TABLE programs;
+ALTER TABLE programs_new RENAME TO programs;
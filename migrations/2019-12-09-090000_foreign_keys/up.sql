@@ -0,0 +1,73 @@
+-- Requires PRAGMA foreign_keys = OFF, otherwise dropping the old tables deletes the rows
+-- referencing them. Tables are rebuilt by copying them to new tables, as renaming the old
+-- tables would make the references of other tables follow them.
+
+-- rows left behind by deleted events
+DELETE FROM programs WHERE event_id NOT IN (SELECT id FROM events);
+DELETE FROM tips WHERE program_id NOT IN (SELECT id FROM programs);
+DELETE FROM tip_cuecards WHERE tip_id NOT IN (SELECT id FROM tips)
+    OR cuecard_id NOT IN (SELECT id FROM cuecards);
+DELETE FROM cuecard_tags WHERE cuecard_id NOT IN (SELECT id FROM cuecards)
+    OR tag_id NOT IN (SELECT id FROM tags);
+UPDATE cues SET tip_id = NULL WHERE tip_id NOT IN (SELECT id FROM tips);
+
+CREATE TABLE programs_new (
+    id INTEGER NOT NULL PRIMARY KEY,
+    uuid TEXT NOT NULL UNIQUE,
+    notes TEXT,
+    event_id INTEGER NOT NULL,
+    date_created TEXT NOT NULL,
+    date_modified TEXT NOT NULL,
+    FOREIGN KEY (event_id) REFERENCES events(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+INSERT INTO programs_new SELECT id, uuid, notes, event_id, date_created, date_modified FROM programs;
+DROP TABLE programs;
+ALTER TABLE programs_new RENAME TO programs;
+
+CREATE INDEX programs_event_id ON programs(event_id);
+
+CREATE TABLE tips_new (
+    id INTEGER NOT NULL PRIMARY KEY,
+    uuid TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    program_id INTEGER NOT NULL,
+    date_start TEXT NOT NULL,
+    date_end TEXT NOT NULL,
+    FOREIGN KEY (program_id) REFERENCES programs(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+INSERT INTO tips_new SELECT id, uuid, name, program_id, date_start, date_end FROM tips;
+DROP TABLE tips;
+ALTER TABLE tips_new RENAME TO tips;
+
+CREATE INDEX tips_program_id ON tips(program_id);
+
+-- rebuilding the cue cards made these reference the dropped cuecards_drop table
+CREATE TABLE tip_cuecards_new (
+    id INTEGER NOT NULL PRIMARY KEY,
+    tip_id INTEGER NOT NULL,
+    cuecard_id INTEGER NOT NULL,
+    sort_order INTEGER NOT NULL DEFAULT 1,
+    cued_at TEXT DEFAULT NULL,
+    FOREIGN KEY (tip_id) REFERENCES tips(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    FOREIGN KEY (cuecard_id) REFERENCES cuecards(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+INSERT INTO tip_cuecards_new SELECT id, tip_id, cuecard_id, sort_order, cued_at FROM tip_cuecards;
+DROP TABLE tip_cuecards;
+ALTER TABLE tip_cuecards_new RENAME TO tip_cuecards;
+
+CREATE INDEX tip_cuecards_tip_id ON tip_cuecards(tip_id);
+
+CREATE TABLE cuecard_tags_new (
+    id INTEGER NOT NULL PRIMARY KEY,
+    cuecard_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+    FOREIGN KEY (cuecard_id) REFERENCES cuecards(id) ON UPDATE CASCADE ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES tags(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+INSERT INTO cuecard_tags_new SELECT id, cuecard_id, tag_id FROM cuecard_tags;
+DROP TABLE cuecard_tags;
+ALTER TABLE cuecard_tags_new RENAME TO cuecard_tags;
@@ -0,0 +1 @@
+DROP TABLE cuecard_revisions;
@@ -0,0 +1,19 @@
+-- previous versions of the content and metadata of cue cards
+CREATE TABLE cuecard_revisions (
+    id INTEGER NOT NULL PRIMARY KEY,
+    uuid TEXT NOT NULL UNIQUE,
+    cuecard_id INTEGER NOT NULL,
+    phase TEXT NOT NULL,
+    rhythm TEXT NOT NULL,
+    title TEXT NOT NULL,
+    steplevel TEXT NOT NULL,
+    difficulty TEXT NOT NULL,
+    choreographer TEXT NOT NULL,
+    meta TEXT NOT NULL,
+    content TEXT NOT NULL,
+    source TEXT NOT NULL, -- editor, indexer, import or restore
+    date_created TEXT NOT NULL, -- ISO8601 date and time string
+    FOREIGN KEY (cuecard_id) REFERENCES cuecards(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+CREATE INDEX cuecard_revisions_cuecard_id ON cuecard_revisions(cuecard_id);
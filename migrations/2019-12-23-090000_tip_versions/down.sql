@@ -0,0 +1,24 @@
+-- Requires PRAGMA foreign_keys = OFF, see 2019-12-09-090000_foreign_keys/up.sql
+DROP TRIGGER tip_cuecards_deleted;
+DROP TRIGGER tip_cuecards_modified;
+DROP TRIGGER tip_cuecards_created;
+DROP TRIGGER tips_modified;
+DROP TRIGGER tips_created;
+
+DROP INDEX tips_program_id;
+
+CREATE TABLE tips_new (
+    id INTEGER NOT NULL PRIMARY KEY,
+    uuid TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    program_id INTEGER NOT NULL,
+    date_start TEXT NOT NULL,
+    date_end TEXT NOT NULL,
+    FOREIGN KEY (program_id) REFERENCES programs(id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+INSERT INTO tips_new SELECT id, uuid, name, program_id, date_start, date_end FROM tips;
+DROP TABLE tips;
+ALTER TABLE tips_new RENAME TO tips;
+
+CREATE INDEX tips_program_id ON tips(program_id);
@@ -0,0 +1,32 @@
+-- the version of a tip, changed whenever the tip or its cue cards change
+ALTER TABLE tips ADD date_modified TEXT NOT NULL DEFAULT ''; -- ISO8601 date and time string
+
+UPDATE tips SET date_modified = (
+    SELECT programs.date_modified FROM programs WHERE programs.id = tips.program_id
+);
+
+CREATE TRIGGER tips_created AFTER INSERT ON tips WHEN NEW.date_modified = ''
+BEGIN
+    UPDATE tips SET date_modified = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER tips_modified AFTER UPDATE OF name, program_id, date_start, date_end ON tips
+BEGIN
+    UPDATE tips SET date_modified = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER tip_cuecards_created AFTER INSERT ON tip_cuecards
+BEGIN
+    UPDATE tips SET date_modified = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = NEW.tip_id;
+END;
+
+CREATE TRIGGER tip_cuecards_modified AFTER UPDATE ON tip_cuecards
+BEGIN
+    UPDATE tips SET date_modified = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+        WHERE id IN (OLD.tip_id, NEW.tip_id);
+END;
+
+CREATE TRIGGER tip_cuecards_deleted AFTER DELETE ON tip_cuecards
+BEGIN
+    UPDATE tips SET date_modified = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = OLD.tip_id;
+END;